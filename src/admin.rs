@@ -0,0 +1,330 @@
+//! Administration commands which work directly against configured storage, without a running
+//! server. Changes which are audited when made through the API are recorded with a nil actor.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::audit::{AuditAction, AuditEntry};
+use crate::cli::{Command, QuizCommand, UserCommand};
+use crate::config::{Backend, Config, ConfigSource};
+use crate::convert::{self, ConversionReport, Format};
+use crate::data::{Grade, Part, Quiz};
+use crate::error::Problem;
+use crate::grading::grade_attempt;
+use crate::id::{PartId, QuizId, UserId};
+use crate::route::{remove_user, UserAuthInfo};
+use crate::store::{self, AttemptStore, MongoStore, QuizStore, Storage, StoreError, UserStore};
+use crate::user::{hash_password, User};
+use crate::validation::validate_quiz;
+
+/// Problem display only carries the title, detail is what tells an operator what went wrong.
+fn describe(problem: Problem) -> anyhow::Error {
+    match problem.detail {
+        Some(detail) => anyhow!("{} {}", problem.title, detail),
+        None => anyhow!("{}", problem.title),
+    }
+}
+
+fn read_password() -> anyhow::Result<String> {
+    eprint!("Password: ");
+    io::stderr().flush()?;
+
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password)?;
+
+    Ok(password.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+async fn find_user(store: &Storage, username: &str) -> anyhow::Result<User> {
+    match store.find_user_by_username(username).await? {
+        Some(it) => Ok(it),
+        None => bail!("User '{}' doesn't exist.", username),
+    }
+}
+
+async fn find_quiz(store: &Storage, id: QuizId) -> anyhow::Result<Quiz> {
+    match store.find_quiz(id).await? {
+        Some(it) => Ok(it),
+        None => bail!("Quiz {} doesn't exist.", id),
+    }
+}
+
+fn parse_format(format: &str) -> anyhow::Result<Format> {
+    format.parse::<Format>()
+        .map_err(|_| anyhow!("Format must be one of: moodle, gift, qti, yaml, toml."))
+}
+
+async fn user_command(store: &Storage, command: UserCommand) -> anyhow::Result<()> {
+    match command {
+        UserCommand::Create { username, roles } => {
+            let password = read_password()?;
+            UserAuthInfo::new(username.clone(), password.clone())
+                .validate()
+                .map_err(describe)?;
+
+            let mut user = User::new(username, password);
+            for role in roles {
+                if !user.user_roles.contains(&role) {
+                    user.user_roles.push(role);
+                }
+            }
+
+            match store.insert_user(&user).await {
+                Ok(()) => {}
+                Err(StoreError::Duplicate(key)) if key == "username" => {
+                    bail!("Username '{}' is taken.", user.username)
+                }
+                Err(e) => return Err(e.into()),
+            }
+            println!("Created user {} ({}).", user.username, user.id);
+        }
+        UserCommand::Passwd { username } => {
+            let user = find_user(store, &username).await?;
+            let password = read_password()?;
+            UserAuthInfo::new(username.clone(), password.clone())
+                .validate()
+                .map_err(describe)?;
+
+            store.set_password(user.id, hash_password(&password)).await?;
+            println!("Changed password of {}.", username);
+        }
+        UserCommand::Promote { username, role } => {
+            let user = find_user(store, &username).await?;
+            if store.add_user_role(user.id, role).await?.is_none() {
+                bail!("User '{}' doesn't exist.", username);
+            }
+
+            AuditEntry::new(UserId::nil(), user.id, AuditAction::RoleGranted(role))
+                .record(store)
+                .await
+                .map_err(describe)?;
+            println!("Granted {} role to {}.", role, username);
+        }
+        UserCommand::Delete { username, cascade, transfer_to } => {
+            let user = find_user(store, &username).await?;
+
+            let (quizzes, transfer_to) = match (cascade, transfer_to) {
+                (true, Some(_)) => bail!("Quizzes can either be deleted or transferred, not both."),
+                (true, None) => (Some("cascade".to_string()), None),
+                (false, Some(author)) => {
+                    let author = find_user(store, &author).await?;
+                    (Some("transfer".to_string()), Some(author.id.to_string()))
+                }
+                (false, None) => (None, None),
+            };
+
+            remove_user(store, &user, quizzes, transfer_to).await.map_err(describe)?;
+            println!("Deleted user {}.", username);
+        }
+    }
+
+    Ok(())
+}
+
+async fn quiz_command(store: &Storage, command: QuizCommand) -> anyhow::Result<()> {
+    match command {
+        QuizCommand::Import { path, format, author, name } => {
+            let format = match format {
+                Some(it) => parse_format(&it)?,
+                None => format_from_path(&path)?,
+            };
+            let author = find_user(store, &author).await?;
+
+            let source = fs::read(&path)
+                .with_context(|| format!("Unable to read {}", path.display()))?;
+            let imported = convert::import(format, &source).map_err(describe)?;
+            print_issues(&imported.report);
+
+            let mut quiz = imported.quiz;
+            if quiz.parts.is_empty() {
+                bail!("Imported quiz has no questions.");
+            }
+            quiz.author = author.id;
+            if let Some(name) = name {
+                quiz.name = name;
+            }
+            validate_quiz(&quiz).map_err(describe)?;
+
+            store.insert_quiz(&quiz).await?;
+            println!("Imported '{}' as {} with {} part(s).", quiz.name, quiz.id, quiz.parts.len());
+        }
+        QuizCommand::Export { quiz, format, output } => {
+            let format = parse_format(&format)?;
+            let quiz = find_quiz(store, quiz).await?;
+
+            let (content, report) = convert::export(format, &quiz).map_err(describe)?;
+            print_issues(&report);
+
+            match output {
+                Some(path) => fs::write(&path, content)
+                    .with_context(|| format!("Unable to write {}", path.display()))?,
+                None => io::stdout().write_all(&content)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_issues(report: &ConversionReport) {
+    for issue in &report.issues {
+        eprintln!("{}: {}", issue.question, issue.message);
+    }
+}
+
+fn format_from_path(path: &Path) -> anyhow::Result<Format> {
+    let extension = path.extension().and_then(|it| it.to_str()).unwrap_or_default();
+    match extension {
+        "zip" => Ok(Format::Qti),
+        "txt" => Ok(Format::Gift),
+        other => parse_format(other).context("Format couldn't be guessed from file extension, use --format"),
+    }
+}
+
+/// Grades finished attempts again, so that fixed validations apply to answers submitted earlier.
+async fn regrade(store: &Storage, id: QuizId) -> anyhow::Result<()> {
+    let quiz = find_quiz(store, id).await?;
+
+    let mut changed = 0;
+    for attempt in store.list_attempts(id).await? {
+        if attempt.finished_on.is_none() {
+            continue;
+        }
+
+        let grades: HashMap<PartId, Grade> = grade_attempt(&quiz, &attempt)
+            .into_iter()
+            .filter(|(part, grade)| {
+                attempt.grades.get(part)
+                    .map(|current| current.score != grade.score || current.graded_by != grade.graded_by)
+                    .unwrap_or(true)
+            })
+            .collect();
+        if grades.is_empty() {
+            continue;
+        }
+
+        // Every changed grade of an attempt is stored at once, so an attempt is never left
+        // partially regraded.
+        store.set_grades(id, attempt.id, &grades).await?;
+        changed += grades.len();
+    }
+
+    println!("Changed {} grade(s) of '{}'.", changed, quiz.name);
+    Ok(())
+}
+
+/// Writes scores of every attempt at a quiz as CSV.
+async fn report(store: &Storage, id: QuizId) -> anyhow::Result<()> {
+    let quiz = find_quiz(store, id).await?;
+    let max_score: f32 = quiz.parts.iter()
+        .map(|part| match part {
+            Part::Interact { value, .. } => *value,
+            Part::Content { .. } => 0.0,
+        })
+        .sum();
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer.write_record(&["user", "username", "started_on", "finished_on", "score", "max_score", "ungraded"])?;
+
+    for attempt in store.list_attempts(id).await? {
+        let username = store.find_user(attempt.id).await?
+            .map(|user| user.username)
+            .unwrap_or_default();
+        let score: f32 = attempt.grades.values().map(|grade| grade.score).sum();
+        let ungraded = attempt.answers.keys()
+            .filter(|part| !attempt.grades.contains_key(part))
+            .count();
+
+        writer.write_record(&[
+            attempt.id.to_string(),
+            username,
+            attempt.started_on.to_rfc3339(),
+            attempt.finished_on.map(|it| it.to_rfc3339()).unwrap_or_default(),
+            score.to_string(),
+            max_score.to_string(),
+            ungraded.to_string(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Lists applied and pending migrations of MongoDB storage and applies pending ones, unless
+/// `dry_run` is set.
+async fn migrate(config: &Config, dry_run: bool) -> anyhow::Result<()> {
+    if config.storage != Backend::MongoDb {
+        println!("Embedded storage doesn't have migrations.");
+        return Ok(());
+    }
+
+    let store = MongoStore::connect(&config.mongodb_uri, &config.mongodb_db).await
+        .context("Unable to open storage")?;
+    println!("Applied migrations up to version {}.", store.migration_version().await?);
+
+    let pending = store.pending_migrations().await?;
+    if pending.is_empty() {
+        println!("No migrations are pending.");
+        return Ok(());
+    }
+    for migration in &pending {
+        println!("Pending migration {}: {}", migration.version, migration.name);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    let applied = store.migrate().await?;
+    println!("Applied {} migration(s), storage is at version {}.", applied.len(), store.migration_version().await?);
+    Ok(())
+}
+
+/// Runs an administration command and returns process exit code.
+pub async fn run(source: &ConfigSource, command: Command) -> i32 {
+    let config = match source.load() {
+        Ok(it) => it,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    // Opening storage applies migrations, so they have to be listed before that.
+    if let Command::Migrate { dry_run } = command {
+        return exit_code(migrate(&config, dry_run).await);
+    }
+
+    let store = match store::open(&config).await {
+        Ok(it) => it,
+        Err(e) => {
+            eprintln!("Unable to open storage: {}", e);
+            return 2;
+        }
+    };
+
+    let result = match command {
+        Command::User(command) => user_command(&store, command).await,
+        Command::Quiz(command) => quiz_command(&store, command).await,
+        Command::Regrade { quiz } => regrade(&store, quiz).await,
+        Command::Report { quiz } => report(&store, quiz).await,
+        Command::Migrate { .. } | Command::Serve | Command::Validate { .. } | Command::Config(_) => {
+            unreachable!("Command doesn't use storage.")
+        }
+    };
+
+    exit_code(result)
+}
+
+fn exit_code(result: anyhow::Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            1
+        }
+    }
+}
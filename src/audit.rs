@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::error::Problem;
+use crate::id::UserId;
+use crate::logging::SECURITY_TARGET;
+use crate::role::Role;
+use crate::store::{AuditStore, Storage};
+
+pub static AUDIT_COLLECTION_NAME: &'static str = "audit";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AuditAction {
+    RoleGranted(Role),
+    RoleRevoked(Role),
+}
+
+/// Record of a privileged change made by `actor` to `target` user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub time: DateTime<Utc>,
+    pub actor: UserId,
+    pub target: UserId,
+    pub action: AuditAction,
+}
+
+impl AuditEntry {
+    pub fn new(actor: UserId, target: UserId, action: AuditAction) -> AuditEntry {
+        AuditEntry {
+            id: Uuid::new_v4(),
+            time: Utc::now(),
+            actor,
+            target,
+            action,
+        }
+    }
+
+    pub async fn record(self, store: &Storage) -> Result<(), Problem> {
+        log::info!(target: SECURITY_TARGET, "{} performed {:?} on {}", self.actor, self.action, self.target);
+
+        store.record_audit(&self).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1071 @@
+//! Author-friendly quiz format.
+//!
+//! Quizzes are written in YAML (or TOML) with human readable durations (`10m`, `1h 30m`) and
+//! question types inferred from the fields which are present:
+//!
+//! ```yaml
+//! name: Geography
+//! time_limit: 10m
+//! parts:
+//!   - content: Answer the following questions.
+//!   - text: Capital of France?
+//!     options: [Paris, Lyon, Marseille]
+//!     answer: Paris
+//!   - text: Pi to two decimals?
+//!     answer: 3.14
+//!     tolerance: 0.005
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use uuid::Uuid;
+
+use crate::data::{AnswerType, AnswerValidation, FuzzyMetric, Normalizer, Part, Quiz, RegexAlternative};
+use crate::validation::{self, ValidationIssue};
+use crate::error::Problem;
+use crate::id::{PartId, QuizId, UserId};
+
+fn true_bool() -> bool {
+    true
+}
+
+#[inline]
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+#[inline]
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Duration written as `90s`, `10m`, `1h 30m` or a number of seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HumanDuration(pub Duration);
+
+impl<'de> serde::Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DurationVisitor;
+
+        impl<'de> Visitor<'de> for DurationVisitor {
+            type Value = HumanDuration;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a duration like \"10m\" or \"1h 30m\", or a number of seconds")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(HumanDuration(Duration::from_secs(v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                if v < 0 {
+                    return Err(E::custom("duration can't be negative"));
+                }
+                Ok(HumanDuration(Duration::from_secs(v as u64)))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                if v < 0.0 || !v.is_finite() {
+                    return Err(E::custom("duration must be a positive number of seconds"));
+                }
+                // Larger values don't fit into a `Duration`.
+                if v >= u64::MAX as f64 {
+                    return Err(E::custom("duration is too long"));
+                }
+                Ok(HumanDuration(Duration::from_secs_f64(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                match v.trim().parse::<u64>() {
+                    Ok(seconds) => Ok(HumanDuration(Duration::from_secs(seconds))),
+                    Err(_) => humantime::parse_duration(v.trim())
+                        .map(HumanDuration)
+                        .map_err(|e| E::custom(format!("invalid duration '{}': {}", v, e))),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(DurationVisitor)
+    }
+}
+
+impl serde::Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&humantime::format_duration(self.0).to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartKind {
+    Content,
+    Bool,
+    Number,
+    Short,
+    Long,
+    FillIn,
+    Match,
+    Single,
+    Multiple,
+}
+
+/// Expected answer, its meaning depends on the question type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AnswerSpec {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    List(Vec<String>),
+}
+
+impl AnswerSpec {
+    /// Textual form of a scalar answer; YAML turns unquoted `2` into a number.
+    fn text(&self) -> Option<String> {
+        match self {
+            AnswerSpec::Bool(value) => Some(value.to_string()),
+            AnswerSpec::Number(value) => Some(value.to_string()),
+            AnswerSpec::Text(value) => Some(value.clone()),
+            AnswerSpec::List(_) => None,
+        }
+    }
+}
+
+/// Accepted range of numbers, written either as `"min..max"` or `[min, max]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RangeSpec {
+    Bounds(Vec<f64>),
+    Text(String),
+}
+
+impl RangeSpec {
+    fn bounds(&self) -> Option<(f64, f64)> {
+        let (min, max) = match self {
+            RangeSpec::Bounds(bounds) if bounds.len() == 2 => (bounds[0], bounds[1]),
+            RangeSpec::Bounds(_) => return None,
+            RangeSpec::Text(text) => {
+                let separator = text.find("..")?;
+                (
+                    text[..separator].trim().parse::<f64>().ok()?,
+                    text[separator + 2..].trim().parse::<f64>().ok()?,
+                )
+            }
+        };
+        if min <= max { Some((min, max)) } else { None }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthoredPart {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<PartId>,
+    /// Question type, inferred from other fields when missing.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<PartKind>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shuffle: Option<bool>,
+    /// Match question pairs, each written as a single `left: right` entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pairs: Option<Vec<BTreeMap<String, String>>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<AnswerSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case_sensitive: Option<bool>,
+    /// Accepts text answers within this many edits of `answer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_distance: Option<usize>,
+    /// Accepts text answers at least this similar (0 to 1) to `answer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_similarity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<FuzzyMetric>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity_credit: Option<bool>,
+    /// Normalizers applied to text answers before they're validated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<Vec<Normalizer>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    /// Whether `regex` has to match the whole answer, defaults to `true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_match: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiline: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize_whitespace: Option<bool>,
+    /// Partially correct `regex` patterns, each with a `score` between 0 and 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alternatives: Option<Vec<RegexAlternative>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<RangeSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external: Option<String>,
+    /// Validation written in the same structure as in JSON, used for composite validations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<AnswerValidation>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<HumanDuration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthoredQuiz {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub desc: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<HumanDuration>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub expect_focus: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub show_answer: bool,
+    #[serde(default = "true_bool", skip_serializing_if = "is_true")]
+    pub show_results: bool,
+
+    #[serde(default = "true_bool", skip_serializing_if = "is_true")]
+    pub public: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_on: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_on: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub begin_buffer: Option<HumanDuration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub course: Option<Uuid>,
+
+    pub parts: Vec<AuthoredPart>,
+}
+
+/// Problem found in an authored quiz. Syntax and schema errors have a position in the source,
+/// while semantic errors point to the offending part.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthoringIssue {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    pub message: String,
+}
+
+impl AuthoringIssue {
+    fn at_part<S: Into<String>>(index: usize, message: S) -> AuthoringIssue {
+        AuthoringIssue {
+            line: None,
+            column: None,
+            pointer: Some(format!("/parts/{}", index)),
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ValidationIssue> for AuthoringIssue {
+    /// Authored parts don't have the same structure as stored ones, so pointers into parts are
+    /// shortened to the part itself.
+    fn from(issue: ValidationIssue) -> AuthoringIssue {
+        let segments: Vec<&str> = issue.pointer.split('/').collect();
+        let pointer = match segments.as_slice() {
+            ["", "parts", index, ..] => format!("/parts/{}", index),
+            _ => issue.pointer.clone(),
+        };
+
+        AuthoringIssue {
+            line: None,
+            column: None,
+            pointer: Some(pointer),
+            message: issue.message,
+        }
+    }
+}
+
+impl fmt::Display for AuthoringIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column, &self.pointer) {
+            (Some(line), Some(column), _) => write!(f, "{}:{}: {}", line, column, self.message),
+            (Some(line), None, _) => write!(f, "{}: {}", line, self.message),
+            (None, _, Some(pointer)) => write!(f, "{}: {}", pointer, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+pub fn authoring_problem(issues: Vec<AuthoringIssue>) -> Problem {
+    Problem::new_untyped(
+        Status::UnprocessableEntity,
+        "Invalid quiz."
+    )
+        .detail(format!("Quiz has {} issue(s).", issues.len()))
+        .insert_serialized("issues", issues)
+        .clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Yaml,
+    Toml,
+}
+
+impl Syntax {
+    /// Picks syntax based on file extension, defaulting to YAML.
+    pub fn from_path(path: &std::path::Path) -> Syntax {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Syntax::Toml,
+            _ => Syntax::Yaml,
+        }
+    }
+}
+
+pub fn parse(source: &str, syntax: Syntax) -> Result<AuthoredQuiz, AuthoringIssue> {
+    match syntax {
+        Syntax::Yaml => serde_yaml::from_str(source).map_err(|e| {
+            let location = e.location();
+            AuthoringIssue {
+                line: location.as_ref().map(|l| l.line()),
+                column: location.as_ref().map(|l| l.column()),
+                pointer: None,
+                message: strip_location(e.to_string()),
+            }
+        }),
+        Syntax::Toml => toml::from_str(source).map_err(|e| {
+            let position = e.line_col();
+            AuthoringIssue {
+                line: position.map(|(line, _)| line + 1),
+                column: position.map(|(_, column)| column + 1),
+                pointer: None,
+                message: strip_location(e.to_string()),
+            }
+        }),
+    }
+}
+
+/// Both YAML and TOML errors end with their position which is already reported separately.
+fn strip_location(message: String) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
+
+#[inline]
+pub fn write_problem<S: Into<String>>(detail: S) -> Problem {
+    Problem::new_untyped(
+        Status::InternalServerError,
+        "Unable to write authored quiz."
+    )
+        .detail(detail)
+        .clone()
+}
+
+/// Writes quiz in given syntax.
+pub fn write(quiz: &AuthoredQuiz, syntax: Syntax) -> Result<String, Problem> {
+    let written = match syntax {
+        Syntax::Yaml => serde_yaml::to_string(quiz).map_err(|e| e.to_string()),
+        Syntax::Toml => write_toml(quiz),
+    };
+
+    written.map_err(write_problem)
+}
+
+/// Writes quiz as TOML with every part field inline. TOML serializer doesn't support enum
+/// variants with fields (e.g. composite validations) and such enums can only be read back from
+/// inline tables. Writing fields inline also avoids the rule that plain values have to come before
+/// tables.
+fn write_toml(quiz: &AuthoredQuiz) -> Result<String, String> {
+    let value = serde_json::to_value(quiz).map_err(|e| e.to_string())?;
+    let mut quiz = match toml::Value::try_from(without_nulls(value)).map_err(|e| e.to_string())? {
+        toml::Value::Table(table) => table,
+        _ => return Err("Quiz isn't written as a table.".to_string()),
+    };
+    let parts = quiz.remove("parts");
+
+    let mut written = String::new();
+    for (key, value) in &quiz {
+        written.push_str(&format!("{} = {}\n", toml_key(key), inline_toml(value)));
+    }
+
+    for part in parts.as_ref().and_then(toml::Value::as_array).into_iter().flatten() {
+        written.push_str("\n[[parts]]\n");
+        for (key, value) in part.as_table().into_iter().flatten() {
+            written.push_str(&format!("{} = {}\n", toml_key(key), inline_toml(value)));
+        }
+    }
+
+    Ok(written)
+}
+
+/// Leaves out `null` values which TOML can't represent. Missing fields have the same meaning.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map.into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key, without_nulls(value)))
+            .collect(),
+        serde_json::Value::Array(values) => values.into_iter()
+            .filter(|value| !value.is_null())
+            .map(without_nulls)
+            .collect(),
+        other => other,
+    }
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+fn inline_toml(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(inline_toml).collect();
+            format!("[{}]", values.join(", "))
+        }
+        toml::Value::Table(table) if table.is_empty() => "{}".to_string(),
+        toml::Value::Table(table) => {
+            let entries: Vec<String> = table.iter()
+                .map(|(key, value)| format!("{} = {}", toml_key(key), inline_toml(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Parses and checks an authored quiz, producing a quiz authored by `author`.
+pub fn read_quiz(source: &str, syntax: Syntax, author: UserId) -> Result<Quiz, Vec<AuthoringIssue>> {
+    parse(source, syntax)
+        .map_err(|issue| vec![issue])?
+        .into_quiz(author)
+}
+
+impl AuthoredPart {
+    fn infer_kind(&self) -> PartKind {
+        if let Some(kind) = self.kind {
+            return kind;
+        }
+
+        if self.content.is_some() {
+            PartKind::Content
+        } else if self.options.is_some() {
+            match self.answer {
+                Some(AnswerSpec::List(_)) => PartKind::Multiple,
+                _ => PartKind::Single,
+            }
+        } else if self.pairs.is_some() {
+            PartKind::Match
+        } else if self.range.is_some() || self.tolerance.is_some() {
+            PartKind::Number
+        } else {
+            match self.answer {
+                Some(AnswerSpec::Bool(_)) => PartKind::Bool,
+                Some(AnswerSpec::Number(_)) => PartKind::Number,
+                Some(_) => PartKind::Short,
+                None if self.regex.is_some() || self.external.is_some() || self.validation.is_some() => PartKind::Short,
+                None => PartKind::Long,
+            }
+        }
+    }
+
+    /// Validation of text answers, used by short, long and fill in questions.
+    fn text_validation(&self, index: usize, issues: &mut Vec<AuthoringIssue>) -> Option<AnswerValidation> {
+        let case_sensitive = self.case_sensitive.unwrap_or(false);
+
+        if let Some(expr) = &self.regex {
+            return Some(AnswerValidation::Regex {
+                case_sensitive,
+                expr: expr.clone(),
+                full_match: self.full_match.unwrap_or(true),
+                multiline: self.multiline.unwrap_or(false),
+                normalize_whitespace: self.normalize_whitespace.unwrap_or(false),
+                alternatives: self.alternatives.clone().unwrap_or_default(),
+                normalize: self.normalize.clone().unwrap_or_default(),
+            });
+        }
+        let regex_options = [self.full_match.is_some(), self.multiline.is_some(), self.normalize_whitespace.is_some(), self.alternatives.is_some()];
+        if regex_options.iter().any(|used| *used) {
+            issues.push(AuthoringIssue::at_part(index, "`full_match`, `multiline`, `normalize_whitespace` and `alternatives` can only be used with `regex`."));
+        }
+
+        if let Some(command) = &self.external {
+            return Some(AnswerValidation::External {
+                command: command.clone(),
+            });
+        }
+
+        let fuzzy = self.max_distance.is_some() || self.min_similarity.is_some();
+        if fuzzy && self.answer.is_some() {
+            let expected = match &self.answer {
+                Some(AnswerSpec::List(expected)) => expected.clone(),
+                Some(answer) => answer.text().into_iter().collect(),
+                None => vec![],
+            };
+            return Some(AnswerValidation::Fuzzy {
+                case_sensitive,
+                expected,
+                metric: self.metric.unwrap_or_default(),
+                max_distance: self.max_distance,
+                min_similarity: self.min_similarity,
+                similarity_credit: self.similarity_credit.unwrap_or(false),
+                normalize: self.normalize.clone().unwrap_or_default(),
+            });
+        }
+        if fuzzy || self.metric.is_some() || self.similarity_credit.is_some() {
+            issues.push(AuthoringIssue::at_part(index, "`max_distance`, `min_similarity`, `metric` and `similarity_credit` need a text `answer`."));
+        }
+
+        match &self.answer {
+            Some(AnswerSpec::List(expected)) => Some(AnswerValidation::Multiple {
+                case_sensitive,
+                expected: expected.clone(),
+                normalize: self.normalize.clone().unwrap_or_default(),
+            }),
+            Some(answer) => Some(AnswerValidation::Exact {
+                case_sensitive,
+                expected: answer.text().unwrap_or_default(),
+                normalize: self.normalize.clone().unwrap_or_default(),
+            }),
+            None => None,
+        }
+    }
+
+    pub fn into_part(self, index: usize, issues: &mut Vec<AuthoringIssue>) -> Option<Part> {
+        let kind = self.infer_kind();
+        let id = self.id.unwrap_or_else(PartId::new);
+
+        let validations = [self.answer.is_some(), self.regex.is_some(), self.range.is_some(), self.external.is_some(), self.validation.is_some()]
+            .iter()
+            .filter(|it| **it)
+            .count();
+        if validations > 1 {
+            issues.push(AuthoringIssue::at_part(index, "Only one of `answer`, `regex`, `range`, `external` and `validation` can be used."));
+        }
+        if self.options.is_some() && kind != PartKind::Single && kind != PartKind::Multiple {
+            issues.push(AuthoringIssue::at_part(index, "`options` can only be used by single and multiple choice questions."));
+        }
+        if self.pairs.is_some() && kind != PartKind::Match {
+            issues.push(AuthoringIssue::at_part(index, "`pairs` can only be used by match questions."));
+        }
+
+        if kind == PartKind::Content {
+            return match self.content.or(self.text) {
+                Some(text) => Some(Part::Content {
+                    id,
+                    title: self.title.unwrap_or_default(),
+                    text,
+                }),
+                None => {
+                    issues.push(AuthoringIssue::at_part(index, "Content part needs `content` text."));
+                    None
+                }
+            };
+        }
+
+        let text = match &self.text {
+            Some(it) => it.clone(),
+            None => {
+                issues.push(AuthoringIssue::at_part(index, "Question needs `text`."));
+                String::new()
+            }
+        };
+
+        let (ans, mut validation) = match kind {
+            PartKind::Content => unreachable!(),
+            PartKind::Bool => match (&self.answer, &self.external) {
+                (Some(AnswerSpec::Bool(expected)), _) => (AnswerType::Bool, Some(AnswerValidation::Bool { expected: *expected })),
+                (None, Some(_)) => (AnswerType::Bool, self.text_validation(index, issues)),
+                (None, None) if self.validation.is_some() => (AnswerType::Bool, None),
+                _ => {
+                    issues.push(AuthoringIssue::at_part(index, "True/false question needs `answer: true` or `answer: false`."));
+                    return None;
+                }
+            },
+            PartKind::Number => {
+                let validation = match (&self.answer, &self.range) {
+                    (_, Some(range)) => match range.bounds() {
+                        Some((min, max)) => Some(AnswerValidation::NumberRange { min, max }),
+                        None => {
+                            issues.push(AuthoringIssue::at_part(index, "`range` must be written as \"min..max\" or [min, max] with min <= max."));
+                            return None;
+                        }
+                    },
+                    (Some(AnswerSpec::Number(value)), None) => {
+                        let tolerance = self.tolerance.unwrap_or(0.0).abs();
+                        Some(AnswerValidation::NumberRange {
+                            min: value - tolerance,
+                            max: value + tolerance,
+                        })
+                    }
+                    (Some(_), None) => {
+                        issues.push(AuthoringIssue::at_part(index, "Numeric question `answer` must be a number."));
+                        return None;
+                    }
+                    (None, None) => self.text_validation(index, issues),
+                };
+                (AnswerType::Number, validation)
+            }
+            PartKind::Short => (AnswerType::Short, self.text_validation(index, issues)),
+            PartKind::Long => (AnswerType::Long, self.text_validation(index, issues)),
+            PartKind::FillIn => match &self.answer {
+                Some(AnswerSpec::List(_)) | None => (AnswerType::FillIn, self.text_validation(index, issues)),
+                Some(_) => {
+                    issues.push(AuthoringIssue::at_part(index, "Fill in question `answer` must list an answer for each blank."));
+                    return None;
+                }
+            },
+            PartKind::Match => {
+                let mut pairs = vec![];
+                for entry in self.pairs.clone().unwrap_or_default() {
+                    if entry.len() != 1 {
+                        issues.push(AuthoringIssue::at_part(index, "Each match pair must be a single `left: right` entry."));
+                        continue;
+                    }
+                    pairs.extend(entry.into_iter());
+                }
+                if pairs.is_empty() {
+                    issues.push(AuthoringIssue::at_part(index, "Match question needs `pairs`."));
+                    return None;
+                }
+                (AnswerType::Match(pairs), None)
+            }
+            PartKind::Single | PartKind::Multiple => {
+                let options = self.options.clone().unwrap_or_default();
+                if options.len() < 2 {
+                    issues.push(AuthoringIssue::at_part(index, "Choice question needs at least two `options`."));
+                }
+                let shuffle = self.shuffle.unwrap_or(true);
+                let case_sensitive = self.case_sensitive.unwrap_or(true);
+
+                let expected: Vec<String> = match &self.answer {
+                    Some(AnswerSpec::List(list)) => list.clone(),
+                    Some(answer) => answer.text().into_iter().collect(),
+                    None => vec![],
+                };
+                for answer in &expected {
+                    if !options.contains(answer) {
+                        issues.push(AuthoringIssue::at_part(index, format!("Answer '{}' isn't one of the options.", answer)));
+                    }
+                }
+
+                if kind == PartKind::Single {
+                    if expected.len() > 1 {
+                        issues.push(AuthoringIssue::at_part(index, "Single choice question can only have one answer."));
+                    }
+                    let validation = expected.into_iter().next().map(|expected| AnswerValidation::Exact {
+                        case_sensitive,
+                        expected,
+                        normalize: self.normalize.clone().unwrap_or_default(),
+                    });
+                    (AnswerType::Single { options, shuffle }, validation.or_else(|| self.text_validation(index, issues)))
+                } else {
+                    let validation = if expected.is_empty() {
+                        self.text_validation(index, issues)
+                    } else {
+                        Some(AnswerValidation::Multiple {
+                            case_sensitive,
+                            expected,
+                            normalize: self.normalize.clone().unwrap_or_default(),
+                        })
+                    };
+                    (AnswerType::Multiple { options, shuffle }, validation)
+                }
+            }
+        };
+
+        if let Some(explicit) = &self.validation {
+            validation = Some(explicit.clone());
+        }
+
+        let value = self.value.unwrap_or(1.0);
+        if value < 0.0 || !value.is_finite() {
+            issues.push(AuthoringIssue::at_part(index, "`value` must be a positive number."));
+        }
+
+        let partial = self.partial.unwrap_or(match kind {
+            PartKind::Multiple | PartKind::Match | PartKind::FillIn => true,
+            _ => false,
+        });
+
+        Some(Part::Interact {
+            id,
+            text,
+            ans,
+            time_limit: self.time_limit.map(|it| it.0),
+            value,
+            validation,
+            partial,
+        })
+    }
+
+    pub fn from_part(part: &Part) -> AuthoredPart {
+        let (id, text, ans, time_limit, value, validation, partial) = match part {
+            Part::Content { id, title, text } => return AuthoredPart {
+                id: Some(*id),
+                title: if title.is_empty() { None } else { Some(title.clone()) },
+                content: Some(text.clone()),
+                ..AuthoredPart::default()
+            },
+            Part::Interact { id, text, ans, time_limit, value, validation, partial } =>
+                (id, text, ans, time_limit, value, validation, partial),
+        };
+
+        let mut authored = AuthoredPart {
+            id: Some(*id),
+            text: Some(text.clone()),
+            value: if *value == 1.0 { None } else { Some(*value) },
+            time_limit: time_limit.map(HumanDuration),
+            ..AuthoredPart::default()
+        };
+
+        authored.kind = Some(match ans {
+            AnswerType::Bool => PartKind::Bool,
+            AnswerType::Number => PartKind::Number,
+            AnswerType::Short => PartKind::Short,
+            AnswerType::Long => PartKind::Long,
+            AnswerType::FillIn => PartKind::FillIn,
+            AnswerType::Match(pairs) => {
+                authored.pairs = Some(pairs.iter()
+                    .map(|(left, right)| {
+                        let mut pair = BTreeMap::new();
+                        pair.insert(left.clone(), right.clone());
+                        pair
+                    })
+                    .collect());
+                PartKind::Match
+            }
+            AnswerType::Single { options, shuffle } => {
+                authored.options = Some(options.clone());
+                authored.shuffle = if *shuffle { None } else { Some(false) };
+                PartKind::Single
+            }
+            AnswerType::Multiple { options, shuffle } => {
+                authored.options = Some(options.clone());
+                authored.shuffle = if *shuffle { None } else { Some(false) };
+                PartKind::Multiple
+            }
+        });
+
+        let default_partial = match authored.kind {
+            Some(PartKind::Multiple) | Some(PartKind::Match) | Some(PartKind::FillIn) => true,
+            _ => false,
+        };
+        authored.partial = if *partial == default_partial { None } else { Some(*partial) };
+
+        // Choice questions are case sensitive by default, text answers aren't.
+        let default_case_sensitive = authored.options.is_some();
+        let case_sensitive = |cs: &bool| if *cs == default_case_sensitive { None } else { Some(*cs) };
+        let normalizers = |n: &Vec<Normalizer>| if n.is_empty() { None } else { Some(n.clone()) };
+
+        match validation {
+            None => {}
+            Some(AnswerValidation::Bool { expected }) => authored.answer = Some(AnswerSpec::Bool(*expected)),
+            Some(AnswerValidation::Exact { case_sensitive: cs, expected, normalize }) => {
+                authored.answer = Some(AnswerSpec::Text(expected.clone()));
+                authored.case_sensitive = case_sensitive(cs);
+                authored.normalize = normalizers(normalize);
+            }
+            Some(AnswerValidation::Multiple { case_sensitive: cs, expected, normalize }) => {
+                authored.answer = Some(AnswerSpec::List(expected.clone()));
+                authored.case_sensitive = case_sensitive(cs);
+                authored.normalize = normalizers(normalize);
+            }
+            Some(AnswerValidation::NumberRange { min, max }) => {
+                authored.range = Some(RangeSpec::Bounds(vec![*min, *max]));
+            }
+            Some(AnswerValidation::Regex { case_sensitive: cs, expr, full_match, multiline, normalize_whitespace, alternatives, normalize }) => {
+                authored.regex = Some(expr.clone());
+                authored.case_sensitive = case_sensitive(cs);
+                authored.full_match = if *full_match { None } else { Some(false) };
+                authored.multiline = if *multiline { Some(true) } else { None };
+                authored.normalize_whitespace = if *normalize_whitespace { Some(true) } else { None };
+                authored.alternatives = if alternatives.is_empty() { None } else { Some(alternatives.clone()) };
+                authored.normalize = normalizers(normalize);
+            }
+            Some(AnswerValidation::Fuzzy { case_sensitive: cs, expected, metric, max_distance, min_similarity, similarity_credit, normalize }) => {
+                authored.answer = Some(match expected.as_slice() {
+                    [expected] => AnswerSpec::Text(expected.clone()),
+                    _ => AnswerSpec::List(expected.clone()),
+                });
+                authored.case_sensitive = case_sensitive(cs);
+                authored.metric = if *metric == FuzzyMetric::default() { None } else { Some(*metric) };
+                authored.max_distance = *max_distance;
+                authored.min_similarity = *min_similarity;
+                authored.similarity_credit = if *similarity_credit { Some(true) } else { None };
+                authored.normalize = normalizers(normalize);
+            }
+            Some(AnswerValidation::External { command }) => authored.external = Some(command.clone()),
+            Some(composite) => authored.validation = Some(composite.clone()),
+        }
+
+        authored
+    }
+}
+
+impl AuthoredQuiz {
+    pub fn into_quiz(self, author: UserId) -> Result<Quiz, Vec<AuthoringIssue>> {
+        let mut issues = vec![];
+
+        let parts: Vec<Part> = self.parts.into_iter()
+            .enumerate()
+            .filter_map(|(index, part)| part.into_part(index, &mut issues))
+            .collect();
+
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+
+        let quiz = Quiz {
+            id: QuizId::new(),
+            name: self.name,
+            desc: self.desc,
+            author,
+            created: Utc::now(),
+            parts,
+            time_limit: self.time_limit.map(|it| it.0),
+            expect_focus: self.expect_focus,
+            show_answer: self.show_answer,
+            show_results: self.show_results,
+            public: self.public,
+            open_on: self.open_on,
+            close_on: self.close_on,
+            begin_buffer: self.begin_buffer.map(|it| it.0),
+            course: self.course,
+            participants: vec![],
+            collaborators: vec![],
+        };
+
+        let issues: Vec<AuthoringIssue> = validation::quiz_issues(&quiz).into_iter()
+            .map(AuthoringIssue::from)
+            .collect();
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+
+        Ok(quiz)
+    }
+
+    pub fn from_quiz(quiz: &Quiz) -> AuthoredQuiz {
+        AuthoredQuiz {
+            name: quiz.name.clone(),
+            desc: quiz.desc.clone(),
+            time_limit: quiz.time_limit.map(HumanDuration),
+            expect_focus: quiz.expect_focus,
+            show_answer: quiz.show_answer,
+            show_results: quiz.show_results,
+            public: quiz.public,
+            open_on: quiz.open_on,
+            close_on: quiz.close_on,
+            begin_buffer: quiz.begin_buffer.map(HumanDuration),
+            course: quiz.course,
+            parts: quiz.parts.iter().map(AuthoredPart::from_part).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SOURCE: &'static str = r#"
+name: Networking basics
+desc: Covers the first two lectures.
+time_limit: 1h 30m
+show_results: false
+begin_buffer: 5m
+parts:
+  - content: Answer the following questions.
+    title: Intro
+  - text: Hubs forward frames to every port.
+    answer: true
+    value: 2
+  - text: Which OSI layer do switches operate on?
+    options: [Data link, Network, Physical]
+    answer: Data link
+    shuffle: false
+  - text: Which protocols are connection oriented?
+    options: [TCP, SCTP, UDP]
+    answer: [TCP, SCTP]
+  - text: What does the N in LAN stand for?
+    answer: network
+    max_distance: 1
+  - text: Name a private IPv4 prefix.
+    regex: '10\..*'
+    case_sensitive: true
+  - text: Which port does HTTP use by default?
+    answer: 80
+    tolerance: 0.5
+    time_limit: 30s
+  - text: Match protocols with ports.
+    pairs:
+      - HTTP: "80"
+      - SSH: "22"
+  - text: Describe how a switch differs from a hub.
+"#;
+
+    fn parts_json(quiz: &Quiz) -> serde_json::Value {
+        serde_json::to_value(&quiz.parts).unwrap()
+    }
+
+    fn part(yaml: &str) -> AuthoredPart {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn reads_quiz() {
+        let author = UserId::new();
+        let quiz = read_quiz(SOURCE, Syntax::Yaml, author).unwrap();
+        assert_eq!(quiz.author, author);
+        assert_eq!(quiz.time_limit, Some(Duration::from_secs(90 * 60)));
+        assert_eq!(quiz.begin_buffer, Some(Duration::from_secs(5 * 60)));
+        assert!(!quiz.show_results);
+        assert_eq!(quiz.parts.len(), 9);
+
+        match &quiz.parts[6] {
+            Part::Interact { ans: AnswerType::Number, time_limit, validation: Some(AnswerValidation::NumberRange { min, max }), .. } => {
+                assert_eq!((*min, *max), (79.5, 80.5));
+                assert_eq!(*time_limit, Some(Duration::from_secs(30)));
+            }
+            other => panic!("Expected numeric question, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_yaml_and_toml() {
+        let quiz = read_quiz(SOURCE, Syntax::Yaml, UserId::new()).unwrap();
+
+        for syntax in &[Syntax::Yaml, Syntax::Toml] {
+            let written = write(&AuthoredQuiz::from_quiz(&quiz), *syntax).unwrap();
+            let reread = read_quiz(&written, *syntax, quiz.author)
+                .unwrap_or_else(|issues| panic!("Unable to read {:?} quiz: {:?}\n{}", syntax, issues, written));
+
+            assert_eq!(reread.name, quiz.name);
+            assert_eq!(reread.desc, quiz.desc);
+            assert_eq!(reread.time_limit, quiz.time_limit);
+            assert_eq!(reread.begin_buffer, quiz.begin_buffer);
+            assert_eq!(reread.show_results, quiz.show_results);
+            assert_eq!(parts_json(&reread), parts_json(&quiz));
+        }
+    }
+
+    #[test]
+    fn writes_tables_after_values_in_toml() {
+        let source = "\
+name: Addressing
+parts:
+  - text: Match the layers.
+    pairs:
+      - {Ethernet: link}
+      - {IP: network}
+    value: 3
+  - type: short
+    text: Name the loopback address.
+    validation:
+      AnyOf:
+        validations:
+          - IpAddress: {expected: [127.0.0.1, '::1']}
+          - Exact: {expected: localhost}
+    value: 2
+    partial: true
+    time_limit: 30
+";
+        let quiz = read_quiz(source, Syntax::Yaml, UserId::new()).unwrap();
+
+        let written = write(&AuthoredQuiz::from_quiz(&quiz), Syntax::Toml).unwrap();
+        let reread = read_quiz(&written, Syntax::Toml, quiz.author)
+            .unwrap_or_else(|issues| panic!("Unable to read TOML quiz: {:?}\n{}", issues, written));
+
+        assert_eq!(parts_json(&reread), parts_json(&quiz));
+        match &reread.parts[1] {
+            Part::Interact { value, time_limit, validation: Some(AnswerValidation::AnyOf { validations }), .. } => {
+                assert_eq!(*value, 2.0);
+                assert_eq!(*time_limit, Some(Duration::from_secs(30)));
+                assert_eq!(validations.len(), 2);
+            }
+            other => panic!("Expected composite validation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn infers_part_kinds() {
+        let cases = [
+            ("content: Read this.", PartKind::Content),
+            ("{text: Pick one., options: [a, b], answer: a}", PartKind::Single),
+            ("{text: Pick one., options: [a, b]}", PartKind::Single),
+            ("{text: Pick some., options: [a, b], answer: [a, b]}", PartKind::Multiple),
+            ("{text: Match., pairs: [{a: b}]}", PartKind::Match),
+            ("{text: Guess., range: 1..2}", PartKind::Number),
+            ("{text: Guess., tolerance: 0.1}", PartKind::Number),
+            ("{text: Guess., answer: 3.14}", PartKind::Number),
+            ("{text: Is it?, answer: false}", PartKind::Bool),
+            ("{text: Name it., answer: Paris}", PartKind::Short),
+            ("{text: Name them., answer: [Paris, Lyon]}", PartKind::Short),
+            ("{text: Name it., regex: P.*}", PartKind::Short),
+            ("{text: Name it., external: ./check}", PartKind::Short),
+            ("{text: Explain.}", PartKind::Long),
+            ("{type: fill_in, text: Fill in., answer: [a]}", PartKind::FillIn),
+            ("{type: long, text: Explain., answer: anything}", PartKind::Long),
+        ];
+
+        for (yaml, kind) in cases.iter() {
+            assert_eq!(part(yaml).infer_kind(), *kind, "Wrong kind inferred for {}", yaml);
+        }
+    }
+
+    #[test]
+    fn parses_durations() {
+        let duration = |yaml: &str| serde_yaml::from_str::<HumanDuration>(yaml).map(|it| it.0);
+
+        assert_eq!(duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert_eq!(duration("'120'").unwrap(), Duration::from_secs(120));
+        assert_eq!(duration("1h 30m").unwrap(), Duration::from_secs(90 * 60));
+
+        assert!(duration("-1").is_err());
+        assert!(duration("1.0e30").is_err());
+        assert!(duration("forever").is_err());
+    }
+
+    #[test]
+    fn reports_issues() {
+        let issue = read_quiz("name: Quiz\nparts:\n  - text: Is it?\n    colour: red\n", Syntax::Yaml, UserId::new())
+            .unwrap_err()
+            .remove(0);
+        assert_eq!(issue.line, Some(4));
+        assert!(issue.message.contains("unknown field `colour`"), "Unexpected message: {}", issue.message);
+
+        let issues = read_quiz("name = \"Quiz\"\n[[parts]]\ntext = \"Pick one.\"\noptions = [\"a\", \"b\"]\nanswer = \"c\"\n", Syntax::Toml, UserId::new())
+            .unwrap_err();
+        assert_eq!(issues[0].pointer.as_deref(), Some("/parts/0"));
+        assert_eq!(issues[0].message, "Answer 'c' isn't one of the options.");
+    }
+}
@@ -0,0 +1,206 @@
+use std::ops::{Deref, DerefMut};
+
+use rocket::data::{self, Data, FromData, ToByteUnit};
+use rocket::http::{ContentType, Status};
+use rocket::outcome::Outcome::{Failure, Success};
+use rocket::request::Request;
+use serde::de::DeserializeOwned;
+use serde_path_to_error::Segment;
+
+use crate::error::Problem;
+
+pub static TRANSFER_ENCODING_HEADER: &'static str = "Content-Transfer-Encoding";
+
+/// Media type of base64 encoded JSON bodies. Alternatively `application/json` bodies can be sent
+/// with `Content-Transfer-Encoding: base64` header.
+pub fn base64_json_content_type() -> ContentType {
+    ContentType::new("application", "json+base64")
+}
+
+#[inline]
+fn body_problem<S: Into<String>>(status: Status, detail: S) -> Problem {
+    Problem::new_untyped(
+        status,
+        "There was a problem decoding request body.",
+    )
+        .detail(detail)
+        .clone()
+}
+
+/// JSON request body which is transparently decoded from base64 when request indicates so.
+///
+/// Should be used instead of `Json` by all routes which accept JSON data. Routes should accept
+/// `Result<JsonBody<T>, Problem>` and return the error, failed data guards are otherwise handled
+/// by catchers which don't know the problem.
+#[derive(Debug, Clone)]
+pub struct JsonBody<T>(pub T);
+
+impl<T> JsonBody<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for JsonBody<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Returns `Ok(true)` if body is base64 encoded JSON and `Ok(false)` if it's plain JSON.
+fn body_encoding(req: &Request<'_>) -> Result<bool, Problem> {
+    let base64_encoded = match req.headers().get_one(TRANSFER_ENCODING_HEADER) {
+        Some(encoding) if encoding.trim().eq_ignore_ascii_case("base64") => true,
+        Some(encoding) if encoding.trim().eq_ignore_ascii_case("8bit") ||
+            encoding.trim().eq_ignore_ascii_case("binary") => false,
+        Some(encoding) => return Err(
+            body_problem(
+                Status::BadRequest,
+                format!("Unsupported {}: '{}'.", TRANSFER_ENCODING_HEADER, encoding),
+            )
+        ),
+        None => false,
+    };
+
+    match req.content_type() {
+        Some(ct) if ct.top() == "application" && ct.sub() == "json+base64" => Ok(true),
+        Some(ct) if ct.is_json() => Ok(base64_encoded),
+        None => Ok(base64_encoded),
+        Some(ct) => Err(
+            body_problem(
+                Status::UnsupportedMediaType,
+                format!("Expected 'application/json' or '{}' body, got '{}'.", base64_json_content_type(), ct),
+            )
+        ),
+    }
+}
+
+/// Decodes base64 encoded body into a JSON string.
+pub fn decode_base64_json(body: &str) -> Result<String, Problem> {
+    // Encoders commonly wrap lines, so whitespace is ignored.
+    let compact: String = body.chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+
+    let bytes = match base64::decode(&compact) {
+        Ok(it) => it,
+        Err(base64::DecodeError::InvalidByte(offset, byte)) => return Err(
+            body_problem(
+                Status::BadRequest,
+                format!("Invalid base64 byte 0x{:02X} at offset {}.", byte, offset),
+            )
+        ),
+        Err(base64::DecodeError::InvalidLastSymbol(offset, byte)) => return Err(
+            body_problem(
+                Status::BadRequest,
+                format!("Invalid base64 trailing symbol 0x{:02X} at offset {}.", byte, offset),
+            )
+        ),
+        Err(base64::DecodeError::InvalidLength) => return Err(
+            body_problem(
+                Status::BadRequest,
+                format!("Invalid base64 length: {} symbols.", compact.len()),
+            )
+        ),
+    };
+
+    String::from_utf8(bytes).map_err(|e| body_problem(
+        Status::BadRequest,
+        format!("Decoded body isn't valid UTF-8 (invalid sequence at byte {}).", e.utf8_error().valid_up_to()),
+    ))
+}
+
+/// Formats deserialization path as a JSON pointer (RFC 6901). Segments which can't be named end
+/// the pointer at their parent.
+fn json_pointer(path: &serde_path_to_error::Path) -> String {
+    let mut pointer = String::new();
+    for segment in path.iter() {
+        let token = match segment {
+            Segment::Seq { index } => index.to_string(),
+            Segment::Map { key } => key.clone(),
+            Segment::Enum { variant } => variant.clone(),
+            Segment::Unknown => break,
+        };
+        pointer.push('/');
+        pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    }
+    pointer
+}
+
+#[inline]
+fn invalid_json_problem(e: &serde_json::Error, pointer: Option<String>) -> Problem {
+    let mut problem = body_problem(
+        Status::UnprocessableEntity,
+        format!("Invalid JSON: {}", e),
+    )
+        .insert_serialized("line", e.line())
+        .insert_serialized("column", e.column())
+        .clone();
+
+    if let Some(pointer) = pointer {
+        problem.insert_serialized("pointer", pointer);
+    }
+    problem
+}
+
+/// Parses JSON, errors point to the offending value with `pointer`, `line` and `column`.
+pub fn parse_json<T: DeserializeOwned>(json: &str) -> Result<T, Problem> {
+    let mut de = serde_json::Deserializer::from_str(json);
+
+    let value = serde_path_to_error::deserialize(&mut de).map_err(|e| {
+        invalid_json_problem(e.inner(), Some(json_pointer(e.path())))
+    })?;
+    de.end().map_err(|e| invalid_json_problem(&e, None))?;
+
+    Ok(value)
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for JsonBody<T> {
+    type Error = Problem;
+
+    async fn from_data(req: &'r Request<'_>, data: Data) -> data::Outcome<Self, Self::Error> {
+        let base64_encoded = match body_encoding(req) {
+            Ok(it) => it,
+            Err(e) => return Failure((e.status, e)),
+        };
+
+        let limit = req.limits().get("json").unwrap_or(1.mebibytes());
+        // Base64 encoding inflates data by a third.
+        let limit = if base64_encoded { limit * 4 / 3 + 4 } else { limit };
+
+        let body = match data.open(limit).into_string().await {
+            Ok(it) if it.is_complete() => it.into_inner(),
+            Ok(_) => {
+                let e = body_problem(Status::PayloadTooLarge, "Request body is too large.");
+                return Failure((e.status, e));
+            }
+            Err(e) => {
+                let e = body_problem(Status::BadRequest, format!("Unable to read request body: {}", e));
+                return Failure((e.status, e));
+            }
+        };
+
+        let json = if base64_encoded {
+            match decode_base64_json(&body) {
+                Ok(it) => it,
+                Err(e) => return Failure((e.status, e)),
+            }
+        } else {
+            body
+        };
+
+        match parse_json(&json) {
+            Ok(it) => Success(JsonBody(it)),
+            Err(e) => Failure((e.status, e)),
+        }
+    }
+}
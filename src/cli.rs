@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use std::net::IpAddr;
+
+use structopt::StructOpt;
+
+use crate::authoring::{self, Syntax};
+use crate::config::ConfigSource;
+use crate::id::{QuizId, UserId};
+use crate::role::Role;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "quizzer", about = "Versatile quiz server with automatic answer validation.")]
+pub struct Cli {
+    /// Directory which contains `settings.yml`.
+    #[structopt(long, global = true, env = "QUIZZER_CONFIG_DIR", default_value = "./config", parse(from_os_str))]
+    pub config_dir: PathBuf,
+    /// Address the server listens on.
+    #[structopt(long, global = true)]
+    pub address: Option<IpAddr>,
+    /// Port the server listens on.
+    #[structopt(short, long, global = true)]
+    pub port: Option<u16>,
+    /// Sets any configuration key, nested keys are separated by `.`. Can be repeated.
+    #[structopt(long = "set", global = true, number_of_values = 1, value_name = "KEY=VALUE", parse(try_from_str = parse_override))]
+    pub overrides: Vec<(String, String)>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+fn parse_override(arg: &str) -> Result<(String, String), String> {
+    match arg.find('=') {
+        Some(at) if at > 0 => Ok((arg[..at].trim().to_string(), arg[at + 1..].to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", arg)),
+    }
+}
+
+impl Cli {
+    /// Configuration directory and values set by flags, which override all other layers.
+    pub fn config_source(&self) -> ConfigSource {
+        let mut overrides = Vec::new();
+        if let Some(address) = self.address {
+            overrides.push(("address".to_string(), address.to_string()));
+        }
+        if let Some(port) = self.port {
+            overrides.push(("port".to_string(), port.to_string()));
+        }
+        overrides.extend(self.overrides.iter().cloned());
+
+        ConfigSource {
+            dir: self.config_dir.clone(),
+            overrides,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Starts the server. This is the default command.
+    Serve,
+    /// Checks an authored YAML or TOML quiz for errors before it's uploaded.
+    Validate {
+        /// Quiz file, `.toml` files are read as TOML and everything else as YAML.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Inspects configuration.
+    Config(ConfigCommand),
+    /// Manages user accounts.
+    User(UserCommand),
+    /// Imports and exports quizzes.
+    Quiz(QuizCommand),
+    /// Lists applied and pending database migrations and applies the pending ones.
+    Migrate {
+        /// Only lists migrations, without applying them.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Grades finished attempts at a quiz again. Manually awarded grades are kept.
+    Regrade {
+        quiz: QuizId,
+    },
+    /// Prints scores of all attempts at a quiz as CSV.
+    Report {
+        quiz: QuizId,
+    },
+}
+
+fn parse_role(role: &str) -> Result<Role, String> {
+    role.parse().map_err(|_| "role must be one of: normal, author, admin".to_string())
+}
+
+/// Commands which read passwords, read them from standard input so they can be piped in.
+#[derive(Debug, StructOpt)]
+pub enum UserCommand {
+    /// Creates a user.
+    Create {
+        username: String,
+        /// Role granted in addition to the normal one. Can be repeated.
+        #[structopt(long = "role", number_of_values = 1, parse(try_from_str = parse_role))]
+        roles: Vec<Role>,
+    },
+    /// Changes password of a user.
+    Passwd {
+        username: String,
+    },
+    /// Grants a role to a user.
+    Promote {
+        username: String,
+        #[structopt(default_value = "admin", parse(try_from_str = parse_role))]
+        role: Role,
+    },
+    /// Deletes a user along with their attempts, tokens and enrollments.
+    Delete {
+        username: String,
+        /// Deletes quizzes authored by the user as well.
+        #[structopt(long)]
+        cascade: bool,
+        /// Transfers quizzes authored by the user to another author.
+        #[structopt(long, value_name = "USERNAME")]
+        transfer_to: Option<String>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum QuizCommand {
+    /// Creates a quiz from a file in one of supported formats.
+    Import {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// One of: moodle, gift, qti, yaml, toml. Guessed from file extension by default.
+        #[structopt(long)]
+        format: Option<String>,
+        /// Username of the quiz author.
+        #[structopt(long)]
+        author: String,
+        /// Overrides quiz name.
+        #[structopt(long)]
+        name: Option<String>,
+    },
+    /// Writes a quiz in one of supported formats.
+    Export {
+        quiz: QuizId,
+        #[structopt(long, default_value = "yaml")]
+        format: String,
+        /// File quiz is written to, standard output by default.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCommand {
+    /// Prints effective configuration with secrets redacted.
+    Show,
+}
+
+/// Prints effective configuration and returns process exit code.
+pub fn show_config(source: &ConfigSource) -> i32 {
+    let config = match source.load() {
+        Ok(it) => it,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    match serde_yaml::to_string(&config.redacted()) {
+        Ok(yaml) => {
+            println!("{}", yaml);
+            0
+        }
+        Err(e) => {
+            eprintln!("unable to print configuration: {}", e);
+            2
+        }
+    }
+}
+
+/// Validates an authored quiz file and returns process exit code.
+pub fn validate(path: &Path) -> i32 {
+    let source = match fs::read_to_string(path) {
+        Ok(it) => it,
+        Err(e) => {
+            eprintln!("{}: unable to read file: {}", path.display(), e);
+            return 2;
+        }
+    };
+
+    match authoring::read_quiz(&source, Syntax::from_path(path), UserId::nil()) {
+        Ok(quiz) => {
+            println!("{}: OK, '{}' has {} part(s).", path.display(), quiz.name, quiz.parts.len());
+            0
+        }
+        Err(issues) => {
+            for issue in &issues {
+                eprintln!("{}:{}", path.display(), issue);
+            }
+            eprintln!("{}: {} issue(s) found.", path.display(), issues.len());
+            1
+        }
+    }
+}
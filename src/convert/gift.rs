@@ -0,0 +1,596 @@
+//! GIFT text format.
+//!
+//! See: https://docs.moodle.org/en/GIFT_format
+
+use crate::convert::{ConversionReport, ExportShape, ImportedQuiz, export_shape, format_fraction, format_problem, question_name, wildcard_validation};
+use crate::data::{AnswerType, AnswerValidation, Part};
+use crate::error::Problem;
+use crate::id::PartId;
+
+static SPECIAL_CHARACTERS: &'static [char] = &['~', '=', '#', '{', '}', ':'];
+
+/// Question as it appears in the source, along with its position for error reporting.
+struct Block {
+    line: usize,
+    text: String,
+}
+
+impl Block {
+    fn problem<S: Into<String>>(&self, offset: usize, detail: S) -> Problem {
+        let before = &self.text[..offset];
+        let line = self.line + before.matches('\n').count();
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        format_problem(line as u32, column as u32, detail)
+    }
+}
+
+/// Splits source into questions, which are separated by blank lines. Comment lines are dropped.
+fn blocks(source: &str) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut current: Option<Block> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        if line.trim_start().starts_with("//") {
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            blocks.extend(current.take());
+            continue;
+        }
+
+        match &mut current {
+            Some(block) => {
+                block.text.push('\n');
+                block.text.push_str(line);
+            }
+            None => current = Some(Block {
+                line: i + 1,
+                text: line.to_string(),
+            }),
+        }
+    }
+    blocks.extend(current.take());
+
+    blocks
+}
+
+/// Byte offset of first unescaped occurrence of `pattern` at or after `from`.
+fn find_unescaped(text: &str, pattern: &str, from: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text[from..].char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if c == '\\' {
+            escaped = true;
+            continue;
+        }
+        if text[from + i..].starts_with(pattern) {
+            return Some(from + i);
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some(next) => result.push(next),
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+    result.trim().to_string()
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if SPECIAL_CHARACTERS.contains(&c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Removes `[html]`, `[plain]`, `[markdown]` and `[moodle]` text format markers.
+fn strip_format(text: &str) -> &str {
+    let trimmed = text.trim_start();
+    for marker in &["[html]", "[plain]", "[markdown]", "[moodle]"] {
+        if trimmed.starts_with(marker) {
+            return &trimmed[marker.len()..];
+        }
+    }
+    trimmed
+}
+
+/// Single answer in an answer block, e.g. `~%50%Paris#feedback`.
+struct Entry {
+    correct: bool,
+    weight: Option<f64>,
+    text: String,
+}
+
+/// Splits answer block content at unescaped `=` and `~` markers. Feedback is dropped.
+fn entries(content: &str) -> Vec<Entry> {
+    let mut starts = vec![];
+    let mut escaped = false;
+    for (i, c) in content.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | '~' => starts.push(i),
+            _ => {}
+        }
+    }
+
+    let mut result = vec![];
+    for (n, start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(content.len());
+        let raw = &content[start + 1..end];
+        let raw = match find_unescaped(raw, "#", 0) {
+            Some(feedback) => &raw[..feedback],
+            None => raw,
+        };
+
+        let mut weight = None;
+        let mut text = raw.trim_start();
+        if text.starts_with('%') {
+            if let Some(close) = text[1..].find('%') {
+                weight = text[1..close + 1].trim().parse::<f64>().ok();
+                text = &text[close + 2..];
+            }
+        }
+
+        result.push(Entry {
+            correct: content[*start..].starts_with('='),
+            weight,
+            text: text.to_string(),
+        });
+    }
+
+    result
+}
+
+/// Parses `value:tolerance`, `min..max` or `value` numerical answer into a range.
+fn numeric_range(answer: &str) -> Option<(f64, f64)> {
+    let answer = answer.trim();
+    if let Some(separator) = answer.find("..") {
+        let min = answer[..separator].trim().parse::<f64>().ok()?;
+        let max = answer[separator + 2..].trim().parse::<f64>().ok()?;
+        return Some((min.min(max), min.max(max)));
+    }
+
+    let (value, tolerance) = match find_unescaped(answer, ":", 0) {
+        Some(separator) => (
+            answer[..separator].trim().parse::<f64>().ok()?,
+            answer[separator + 1..].trim().parse::<f64>().ok()?.abs(),
+        ),
+        None => (answer.parse::<f64>().ok()?, 0.0),
+    };
+    Some((value - tolerance, value + tolerance))
+}
+
+#[inline]
+fn interact(text: String, ans: AnswerType, validation: Option<AnswerValidation>, partial: bool) -> Part {
+    Part::Interact {
+        id: PartId::new(),
+        text,
+        ans,
+        time_limit: None,
+        value: 1.0,
+        validation,
+        partial,
+    }
+}
+
+fn parse_answers(content: &str, text: String, name: &str, report: &mut ConversionReport) -> Option<Part> {
+    let trimmed = content.trim();
+
+    if trimmed.is_empty() {
+        return Some(interact(text, AnswerType::Long, None, false));
+    }
+
+    let without_feedback = match find_unescaped(trimmed, "#", 1) {
+        Some(feedback) => &trimmed[..feedback],
+        None => trimmed,
+    };
+    match without_feedback.trim().to_uppercase().as_str() {
+        "T" | "TRUE" => return Some(interact(text, AnswerType::Bool, Some(AnswerValidation::Bool { expected: true }), false)),
+        "F" | "FALSE" => return Some(interact(text, AnswerType::Bool, Some(AnswerValidation::Bool { expected: false }), false)),
+        _ => {}
+    }
+
+    if trimmed.starts_with('#') {
+        let numeric = &trimmed[1..];
+        let range = if find_unescaped(numeric, "=", 0).is_some() {
+            let correct: Vec<Entry> = entries(numeric).into_iter()
+                .filter(|e| e.correct && e.weight.map_or(true, |w| w >= 100.0))
+                .collect();
+            if correct.len() > 1 {
+                report.issue(name, "Only the first correct numerical answer was imported.");
+            }
+            correct.first().and_then(|e| numeric_range(&e.text))
+        } else {
+            let answer = match find_unescaped(numeric, "#", 0) {
+                Some(feedback) => &numeric[..feedback],
+                None => numeric,
+            };
+            numeric_range(answer)
+        };
+
+        return match range {
+            Some((min, max)) => Some(interact(text, AnswerType::Number, Some(AnswerValidation::NumberRange { min, max }), false)),
+            None => {
+                report.skip(name, "Numerical answer couldn't be parsed.");
+                None
+            }
+        };
+    }
+
+    let entries = entries(trimmed);
+    if entries.is_empty() {
+        report.skip(name, "Answer block has no answers.");
+        return None;
+    }
+
+    if entries.iter().any(|e| find_unescaped(&e.text, "->", 0).is_some()) {
+        let mut pairs = vec![];
+        for entry in &entries {
+            match find_unescaped(&entry.text, "->", 0) {
+                Some(arrow) => {
+                    let left = unescape(&entry.text[..arrow]);
+                    let right = unescape(&entry.text[arrow + 2..]);
+                    if left.is_empty() {
+                        report.issue(name, format!("Distractor answer '{}' without a pair was dropped.", right));
+                    } else {
+                        pairs.push((left, right));
+                    }
+                }
+                None => report.issue(name, format!("Entry '{}' isn't a pair and was dropped.", unescape(&entry.text))),
+            }
+        }
+
+        return Some(interact(text, AnswerType::Match(pairs), None, true));
+    }
+
+    let has_wrong = entries.iter().any(|e| !e.correct);
+    if !has_wrong {
+        // Only correct answers means this is a short answer question.
+        if entries.iter().any(|e| e.weight.map_or(false, |w| w < 100.0)) {
+            report.issue(name, "Partially correct answers aren't supported and were dropped.");
+        }
+        let mut expected: Vec<String> = entries.iter()
+            .filter(|e| e.weight.map_or(true, |w| w >= 100.0))
+            .map(|e| unescape(&e.text))
+            .collect();
+
+        let validation = if expected.is_empty() {
+            report.skip(name, "Short answer question has no correct answer.");
+            return None;
+        } else if expected.iter().any(|e| e.contains('*')) {
+            wildcard_validation(&expected, false)
+        } else if expected.len() == 1 {
+            AnswerValidation::Exact {
+                case_sensitive: false,
+                expected: expected.remove(0),
+                normalize: vec![],
+            }
+        } else {
+            AnswerValidation::Multiple {
+                case_sensitive: false,
+                expected,
+                normalize: vec![],
+            }
+        };
+
+        return Some(interact(text, AnswerType::Short, Some(validation), false));
+    }
+
+    let options: Vec<String> = entries.iter().map(|e| unescape(&e.text)).collect();
+    let weighted = entries.iter().any(|e| !e.correct && e.weight.map_or(false, |w| w > 0.0));
+
+    if weighted {
+        let expected: Vec<String> = entries.iter()
+            .filter(|e| e.weight.map_or(e.correct, |w| w > 0.0))
+            .map(|e| unescape(&e.text))
+            .collect();
+
+        Some(interact(text, AnswerType::Multiple { options, shuffle: true }, Some(AnswerValidation::Multiple {
+            case_sensitive: true,
+            expected,
+            normalize: vec![],
+        }), true))
+    } else {
+        let correct: Vec<&Entry> = entries.iter().filter(|e| e.correct).collect();
+        if correct.is_empty() {
+            report.skip(name, "Multiple choice question has no correct option.");
+            return None;
+        }
+        if correct.len() > 1 {
+            report.issue(name, "Single choice question has multiple correct options, only the first one is accepted.");
+        }
+
+        Some(interact(text, AnswerType::Single { options, shuffle: true }, Some(AnswerValidation::Exact {
+            case_sensitive: true,
+            expected: unescape(&correct[0].text),
+            normalize: vec![],
+        }), false))
+    }
+}
+
+fn parse_question(block: &Block, index: usize, report: &mut ConversionReport) -> Result<Option<Part>, Problem> {
+    let source = block.text.trim_start();
+    let skipped = block.text.len() - source.len();
+
+    let (title, body_start) = if source.starts_with("::") {
+        match find_unescaped(source, "::", 2) {
+            Some(end) => (Some(unescape(&source[2..end])), end + 2),
+            None => return Err(block.problem(skipped, "Question title isn't terminated with '::'.")),
+        }
+    } else {
+        (None, 0)
+    };
+
+    let open = find_unescaped(source, "{", body_start);
+    let (text, answers) = match open {
+        Some(open) => {
+            let close = match find_unescaped(source, "}", open + 1) {
+                Some(it) => it,
+                None => return Err(block.problem(skipped + open, "Answer block isn't terminated with '}'.")),
+            };
+
+            let before = unescape(strip_format(&source[body_start..open]));
+            let after = unescape(&source[close + 1..]);
+            let text = if after.is_empty() {
+                before
+            } else {
+                // Missing word format; answer block is in the middle of the text.
+                format!("{} _____ {}", before, after)
+            };
+
+            (text, Some(&source[open + 1..close]))
+        }
+        None => (unescape(strip_format(&source[body_start..])), None),
+    };
+
+    let name = title.clone()
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| question_name(index, &text));
+
+    match answers {
+        Some(answers) => Ok(parse_answers(answers, text, &name, report)),
+        None => Ok(Some(Part::Content {
+            id: PartId::new(),
+            title: title.unwrap_or_default(),
+            text,
+        })),
+    }
+}
+
+pub fn import(source: &str) -> Result<ImportedQuiz, Problem> {
+    let mut name = None;
+    let mut parts = vec![];
+    let mut report = ConversionReport::default();
+
+    for (index, block) in blocks(source).iter().enumerate() {
+        let text = block.text.trim();
+        if text.starts_with("$CATEGORY:") {
+            if name.is_none() {
+                name = text["$CATEGORY:".len()..].trim()
+                    .rsplit('/')
+                    .next()
+                    .map(|n| n.to_string())
+                    .filter(|n| !n.is_empty() && !n.starts_with('$'));
+            }
+            continue;
+        }
+
+        if let Some(part) = parse_question(block, index, &mut report)? {
+            parts.push(part);
+            report.converted += 1;
+        }
+    }
+
+    Ok(ImportedQuiz::from_parts(name, parts, report))
+}
+
+pub fn export(name: &str, parts: &[Part]) -> (String, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let mut out = String::new();
+
+    out.push_str(&format!("$CATEGORY: $course$/{}\n\n", name.replace('/', "-").replace('\n', " ")));
+
+    for (index, part) in parts.iter().enumerate() {
+        let shape = match export_shape(index, part, &mut report) {
+            Some(it) => it,
+            None => continue,
+        };
+
+        let text = match part {
+            Part::Content { text, .. } => text,
+            Part::Interact { text, .. } => text,
+        };
+        let title = match &shape {
+            ExportShape::Description { title } if !title.is_empty() => title.to_string(),
+            _ => question_name(index, text),
+        };
+
+        out.push_str(&format!("::{}::{}", escape(&title), escape(text)));
+
+        match shape {
+            ExportShape::Description { .. } => {}
+            ExportShape::TrueFalse { expected } => {
+                out.push_str(if expected { " {TRUE}" } else { " {FALSE}" });
+            }
+            ExportShape::Choice { options, single, .. } => {
+                out.push_str(" {\n");
+                for (option, fraction) in options {
+                    if single {
+                        let marker = if fraction >= 100.0 { '=' } else { '~' };
+                        out.push_str(&format!("\t{}{}\n", marker, escape(option)));
+                    } else {
+                        out.push_str(&format!("\t~%{}%{}\n", format_fraction(fraction), escape(option)));
+                    }
+                }
+                out.push('}');
+            }
+            ExportShape::ShortAnswer { expected, case_sensitive } => {
+                if case_sensitive {
+                    report.issue(title.as_str(), "Case sensitivity can't be represented in GIFT, answers are case insensitive.");
+                }
+                out.push_str(" {\n");
+                for answer in expected {
+                    out.push_str(&format!("\t={}\n", escape(&answer)));
+                }
+                out.push('}');
+            }
+            ExportShape::Numerical { value, tolerance } => {
+                out.push_str(&format!(" {{#{}:{}}}", value, tolerance));
+            }
+            ExportShape::Matching { pairs } => {
+                out.push_str(" {\n");
+                for (left, right) in pairs {
+                    out.push_str(&format!("\t={} -> {}\n", escape(left), escape(right)));
+                }
+                out.push('}');
+            }
+            ExportShape::Essay => out.push_str(" {}"),
+        }
+
+        out.push_str("\n\n");
+        report.converted += 1;
+    }
+
+    (out, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::without_ids;
+
+    static FIXTURE: &'static str = include_str!("fixtures/networking.gift");
+
+    fn position(problem: &Problem) -> (Option<u64>, Option<u64>) {
+        (problem.body["line"].as_u64(), problem.body["column"].as_u64())
+    }
+
+    #[test]
+    fn imports_fixture() {
+        let imported = import(FIXTURE).unwrap();
+        let quiz = imported.quiz;
+        assert_eq!(quiz.name, "Basics");
+        assert_eq!(quiz.parts.len(), 9);
+
+        match &quiz.parts[0] {
+            Part::Content { title, text, .. } => {
+                assert_eq!(title, "Intro");
+                assert_eq!(text, "This quiz covers <b>LAN</b> basics.");
+            }
+            other => panic!("Expected content, got {:?}", other),
+        }
+
+        match &quiz.parts[2] {
+            Part::Interact { ans: AnswerType::Single { options, .. }, validation: Some(AnswerValidation::Exact { expected, .. }), .. } => {
+                assert_eq!(options, &vec!["Data link".to_string(), "Network".to_string(), "Physical".to_string()]);
+                assert_eq!(expected, "Data link");
+            }
+            other => panic!("Expected single choice, got {:?}", other),
+        }
+
+        match &quiz.parts[3] {
+            Part::Interact { ans: AnswerType::Multiple { options, .. }, validation: Some(AnswerValidation::Multiple { expected, .. }), partial, .. } => {
+                assert_eq!(options.len(), 3);
+                assert_eq!(expected, &vec!["TCP".to_string(), "SCTP".to_string()]);
+                assert!(partial);
+            }
+            other => panic!("Expected multiple choice, got {:?}", other),
+        }
+
+        match &quiz.parts[6] {
+            Part::Interact { validation: Some(AnswerValidation::NumberRange { min, max }), .. } => {
+                assert_eq!((*min, *max), (80.0, 80.0));
+            }
+            other => panic!("Expected numerical question, got {:?}", other),
+        }
+
+        match &quiz.parts[7] {
+            Part::Interact { ans: AnswerType::Match(pairs), .. } => {
+                assert_eq!(pairs, &vec![("HTTP".to_string(), "80".to_string()), ("SSH".to_string(), "22".to_string())]);
+            }
+            other => panic!("Expected matching question, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_skipped_questions() {
+        let report = import(FIXTURE).unwrap().report;
+        assert_eq!((report.converted, report.skipped), (9, 2));
+
+        let issues: Vec<(&str, &str)> = report.issues.iter()
+            .map(|issue| (issue.question.as_str(), issue.message.as_str()))
+            .collect();
+        assert_eq!(issues, vec![
+            ("Pairs", "Distractor answer '25' without a pair was dropped."),
+            ("Broken", "Numerical answer couldn't be parsed."),
+            ("Nothing right", "Multiple choice question has no correct option."),
+        ]);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let quiz = import(FIXTURE).unwrap().quiz;
+
+        let (exported, report) = export(&quiz.name, &quiz.parts);
+        assert_eq!(report.converted, quiz.parts.len());
+        assert!(report.issues.is_empty(), "Unexpected issues: {:?}", report.issues);
+
+        let reimported = import(&exported).unwrap();
+        assert_eq!(reimported.quiz.name, quiz.name);
+        assert_eq!(without_ids(&reimported.quiz.parts), without_ids(&quiz.parts));
+    }
+
+    #[test]
+    fn reports_unsupported_exports() {
+        let parts = vec![
+            interact("Fill in the blank.".to_string(), AnswerType::FillIn, None, false),
+            interact("Name the protocol.".to_string(), AnswerType::Short, Some(AnswerValidation::Exact {
+                case_sensitive: true,
+                expected: "TCP".to_string(),
+                normalize: vec![],
+            }), false),
+        ];
+
+        let (exported, report) = export("Unsupported", &parts);
+        assert_eq!((report.converted, report.skipped), (1, 1));
+        assert_eq!(report.issues[0].message, "Fill-in questions aren't supported by the format.");
+        assert_eq!(report.issues[1].message, "Case sensitivity can't be represented in GIFT, answers are case insensitive.");
+        assert!(!exported.contains("Fill in"));
+    }
+
+    #[test]
+    fn problems_point_at_source() {
+        let source = "::Ok::Fine {TRUE}\n\n// Comment\n   ::Open::Spans\nlines {=A\n";
+        let problem = import(source).unwrap_err();
+        assert_eq!(problem.detail.as_deref(), Some("Answer block isn't terminated with '}'."));
+        assert_eq!(position(&problem), (Some(5), Some(7)));
+
+        let problem = import("  ::Title isn't closed {TRUE}").unwrap_err();
+        assert_eq!(problem.detail.as_deref(), Some("Question title isn't terminated with '::'."));
+        assert_eq!(position(&problem), (Some(1), Some(3)));
+    }
+}
@@ -0,0 +1,411 @@
+//! Conversion of quizzes from and to formats used by other learning management systems.
+
+use chrono::Utc;
+use rocket::http::Status;
+
+use crate::authoring::{self, AuthoredQuiz, Syntax, authoring_problem};
+use crate::data::{AnswerType, AnswerValidation, Part, Quiz};
+use crate::error::Problem;
+use crate::id::{QuizId, UserId};
+
+pub mod moodle;
+pub mod gift;
+pub mod qti;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionIssue {
+    /// Name or position of the question the issue concerns.
+    pub question: String,
+    pub message: String,
+}
+
+/// Lists everything that couldn't be converted (fully) between formats.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConversionReport {
+    pub converted: usize,
+    pub skipped: usize,
+    pub issues: Vec<ConversionIssue>,
+}
+
+impl ConversionReport {
+    pub fn issue<Q: Into<String>, M: Into<String>>(&mut self, question: Q, message: M) {
+        self.issues.push(ConversionIssue {
+            question: question.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn skip<Q: Into<String>, M: Into<String>>(&mut self, question: Q, message: M) {
+        self.skipped += 1;
+        self.issue(question, message);
+    }
+}
+
+/// Quiz read from a foreign format. Its `author` is nil and must be set by the importer.
+#[derive(Debug, Clone)]
+pub struct ImportedQuiz {
+    pub quiz: Quiz,
+    pub report: ConversionReport,
+}
+
+impl ImportedQuiz {
+    /// Creates a quiz from formats which only carry questions. Such quizzes aren't public.
+    pub(crate) fn from_parts(name: Option<String>, parts: Vec<Part>, report: ConversionReport) -> ImportedQuiz {
+        ImportedQuiz {
+            quiz: Quiz {
+                id: QuizId::new(),
+                name: name.unwrap_or_else(|| "Imported quiz".to_string()),
+                desc: String::new(),
+                author: UserId::nil(),
+                created: Utc::now(),
+                parts,
+                time_limit: None,
+                expect_focus: false,
+                show_answer: false,
+                show_results: true,
+                public: false,
+                open_on: None,
+                close_on: None,
+                begin_buffer: None,
+                course: None,
+                participants: vec![],
+                collaborators: vec![],
+            },
+            report,
+        }
+    }
+}
+
+#[inline]
+pub fn format_problem<S: Into<String>>(line: u32, column: u32, detail: S) -> Problem {
+    Problem::new_untyped(
+        Status::UnprocessableEntity,
+        "Unable to parse imported quiz."
+    )
+        .detail(detail)
+        .insert_serialized("line", line)
+        .insert_serialized("column", column)
+        .clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    MoodleXml,
+    Gift,
+    Qti,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::MoodleXml => "application/xml",
+            Format::Gift => "text/plain",
+            Format::Qti => "application/zip",
+            Format::Yaml => "application/x-yaml",
+            Format::Toml => "application/toml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::MoodleXml => "xml",
+            Format::Gift => "gift",
+            Format::Qti => "zip",
+            Format::Yaml => "yml",
+            Format::Toml => "toml",
+        }
+    }
+
+    /// Binary formats can't be embedded in JSON responses as is.
+    pub fn is_binary(self) -> bool {
+        self == Format::Qti
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "moodle" | "moodle-xml" | "xml" => Ok(Format::MoodleXml),
+            "gift" => Ok(Format::Gift),
+            "qti" | "ims-qti" => Ok(Format::Qti),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "toml" => Ok(Format::Toml),
+            _ => Err(()),
+        }
+    }
+}
+
+fn source_text(source: &[u8]) -> Result<&str, Problem> {
+    std::str::from_utf8(source).map_err(|e| {
+        let before = &source[..e.valid_up_to()];
+        let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+        let column = before.iter().rev().take_while(|b| **b != b'\n').count() + 1;
+        format_problem(line as u32, column as u32, "Imported file isn't valid UTF-8.")
+    })
+}
+
+pub fn import(format: Format, source: &[u8]) -> Result<ImportedQuiz, Problem> {
+    match format {
+        Format::MoodleXml => moodle::import(source_text(source)?),
+        Format::Gift => gift::import(source_text(source)?),
+        Format::Qti => qti::import(source),
+        Format::Yaml => import_authored(source_text(source)?, Syntax::Yaml),
+        Format::Toml => import_authored(source_text(source)?, Syntax::Toml),
+    }
+}
+
+fn import_authored(source: &str, syntax: Syntax) -> Result<ImportedQuiz, Problem> {
+    let quiz = authoring::read_quiz(source, syntax, UserId::nil())
+        .map_err(authoring_problem)?;
+
+    Ok(ImportedQuiz {
+        report: ConversionReport {
+            converted: quiz.parts.len(),
+            ..ConversionReport::default()
+        },
+        quiz,
+    })
+}
+
+pub fn export(format: Format, quiz: &Quiz) -> Result<(Vec<u8>, ConversionReport), Problem> {
+    let exported = match format {
+        Format::MoodleXml => {
+            let (content, report) = moodle::export(&quiz.name, &quiz.parts);
+            (content.into_bytes(), report)
+        }
+        Format::Gift => {
+            let (content, report) = gift::export(&quiz.name, &quiz.parts);
+            (content.into_bytes(), report)
+        }
+        Format::Qti => qti::export(&quiz.name, &quiz.parts),
+        Format::Yaml | Format::Toml => {
+            let syntax = if format == Format::Yaml { Syntax::Yaml } else { Syntax::Toml };
+            let content = authoring::write(&AuthoredQuiz::from_quiz(quiz), syntax)?;
+            let report = ConversionReport {
+                converted: quiz.parts.len(),
+                ..ConversionReport::default()
+            };
+            (content.into_bytes(), report)
+        }
+    };
+
+    Ok(exported)
+}
+
+/// Question shape shared by exporters, derived from a quiz `Part`.
+pub(crate) enum ExportShape<'a> {
+    Description { title: &'a str },
+    TrueFalse { expected: bool },
+    /// Options with fractions of points (in percent) assigned to each of them.
+    Choice { options: Vec<(&'a str, f64)>, single: bool, shuffle: bool },
+    /// Accepted answers, `*` matches any sequence of characters.
+    ShortAnswer { expected: Vec<String>, case_sensitive: bool },
+    Numerical { value: f64, tolerance: f64 },
+    Matching { pairs: &'a Vec<(String, String)> },
+    Essay,
+}
+
+/// Formats a fraction the way Moodle expects it, e.g. `33.33333`.
+pub(crate) fn format_fraction(fraction: f64) -> String {
+    let formatted = format!("{:.5}", fraction);
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    if formatted == "-0" {
+        "0".to_string()
+    } else {
+        formatted.to_string()
+    }
+}
+
+/// Removes floating point noise introduced by converting between ranges and tolerances.
+#[inline]
+fn round_decimal(value: f64) -> f64 {
+    (value * 1e9).round() / 1e9
+}
+
+/// Removes HTML tags from text. Option texts are compared with answers as plain text.
+pub(crate) fn strip_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => result.push(c),
+            _ => {}
+        }
+    }
+
+    result.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Converts a Moodle short answer pattern where `*` matches anything into an anchored regex.
+fn wildcard_to_regex(pattern: &str) -> String {
+    let pieces: Vec<String> = pattern.split('*')
+        .map(|piece| regex::escape(piece))
+        .collect();
+    format!("^{}$", pieces.join(".*"))
+}
+
+/// Regex validation accepting any of the expected answers, which can contain `*` wildcards.
+pub(crate) fn wildcard_validation(expected: &[String], case_sensitive: bool) -> AnswerValidation {
+    let patterns: Vec<String> = expected.iter().map(|e| wildcard_to_regex(e)).collect();
+    AnswerValidation::Regex {
+        case_sensitive,
+        expr: if patterns.len() == 1 {
+            patterns[0].clone()
+        } else {
+            format!("(?:{})", patterns.join(")|(?:"))
+        },
+        full_match: true,
+        multiline: false,
+        normalize_whitespace: false,
+        alternatives: vec![],
+        normalize: vec![],
+    }
+}
+
+/// Reverses `wildcard_to_regex`. Returns `None` for expressions that use other regex features.
+pub(crate) fn regex_to_wildcard(expr: &str) -> Option<String> {
+    if !expr.starts_with('^') || !expr.ends_with('$') || expr.len() < 2 {
+        return None;
+    }
+
+    let mut pieces = vec![];
+    for escaped in expr[1..expr.len() - 1].split(".*") {
+        let mut piece = String::with_capacity(escaped.len());
+        let mut chars = escaped.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => piece.push(chars.next()?),
+                _ => piece.push(c),
+            }
+        }
+
+        if piece.contains('*') || regex::escape(&piece) != escaped {
+            return None;
+        }
+        pieces.push(piece);
+    }
+
+    Some(pieces.join("*"))
+}
+
+/// Short name used to identify a question in reports and exported files.
+pub(crate) fn question_name(index: usize, text: &str) -> String {
+    let text = strip_html(text);
+    let text = text.trim();
+    if text.is_empty() {
+        return format!("Question {}", index + 1);
+    }
+
+    let mut name: String = text.chars().take(40).collect();
+    if name.len() < text.len() {
+        name.push_str("...");
+    }
+    name
+}
+
+/// Determines how a part is exported. Parts whose validation can't be represented are exported
+/// as essays (graded manually) and reported.
+pub(crate) fn export_shape<'a>(index: usize, part: &'a Part, report: &mut ConversionReport) -> Option<ExportShape<'a>> {
+    let (text, ans, validation) = match part {
+        Part::Content { title, .. } => return Some(ExportShape::Description { title }),
+        Part::Interact { text, ans, validation, .. } => (text, ans, validation),
+    };
+    let name = question_name(index, text);
+
+    let shape = match (ans, validation) {
+        (AnswerType::Bool, Some(AnswerValidation::Bool { expected })) => ExportShape::TrueFalse { expected: *expected },
+        (AnswerType::Single { options, shuffle }, Some(AnswerValidation::Exact { expected, .. })) => {
+            if !options.contains(expected) {
+                report.issue(name.as_str(), "Expected answer isn't one of the options.");
+            }
+            ExportShape::Choice {
+                options: options.iter()
+                    .map(|o| (o.as_str(), if o == expected { 100.0 } else { 0.0 }))
+                    .collect(),
+                single: true,
+                shuffle: *shuffle,
+            }
+        }
+        (AnswerType::Multiple { options, shuffle }, Some(AnswerValidation::Multiple { expected, .. })) => {
+            let correct = expected.len().max(1) as f64;
+            ExportShape::Choice {
+                options: options.iter()
+                    .map(|o| (o.as_str(), if expected.contains(o) { 100.0 / correct } else { -100.0 / correct }))
+                    .collect(),
+                single: false,
+                shuffle: *shuffle,
+            }
+        }
+        (AnswerType::Short, Some(AnswerValidation::Exact { case_sensitive, expected, .. })) => ExportShape::ShortAnswer {
+            expected: vec![expected.clone()],
+            case_sensitive: *case_sensitive,
+        },
+        (AnswerType::Short, Some(AnswerValidation::Multiple { case_sensitive, expected, .. })) => ExportShape::ShortAnswer {
+            expected: expected.clone(),
+            case_sensitive: *case_sensitive,
+        },
+        (AnswerType::Short, Some(AnswerValidation::Regex { case_sensitive, expr, multiline: false, normalize_whitespace: false, alternatives, .. }))
+        if alternatives.is_empty() && regex_to_wildcard(expr).is_some() => {
+            ExportShape::ShortAnswer {
+                expected: regex_to_wildcard(expr).into_iter().collect(),
+                case_sensitive: *case_sensitive,
+            }
+        }
+        (AnswerType::Short, Some(AnswerValidation::Fuzzy { case_sensitive, expected, .. })) => {
+            report.issue(name.as_str(), "Fuzzy matching isn't supported by the format, only expected answers are accepted.");
+            ExportShape::ShortAnswer {
+                expected: expected.clone(),
+                case_sensitive: *case_sensitive,
+            }
+        }
+        (AnswerType::Number, Some(AnswerValidation::NumberRange { min, max })) => ExportShape::Numerical {
+            value: round_decimal((min + max) / 2.0),
+            tolerance: round_decimal((max - min).abs() / 2.0),
+        },
+        (AnswerType::Match(pairs), None) => ExportShape::Matching { pairs },
+        (AnswerType::Long, None) => ExportShape::Essay,
+        (AnswerType::FillIn, _) => {
+            report.skip(name, "Fill-in questions aren't supported by the format.");
+            return None;
+        }
+        (_, None) => {
+            report.issue(name, "Question has no validation, exported as essay.");
+            ExportShape::Essay
+        }
+        (_, Some(_)) => {
+            report.issue(name, "Validation can't be represented in the format, exported as essay.");
+            ExportShape::Essay
+        }
+    };
+
+    Some(shape)
+}
+
+/// Serialized parts without their IDs, which aren't kept by conversions.
+#[cfg(test)]
+pub(crate) fn without_ids(parts: &[Part]) -> Vec<serde_json::Value> {
+    parts.iter()
+        .map(|part| {
+            let mut value = serde_json::to_value(part).expect("Part must be JSON serializable.");
+            for (_, fields) in value.as_object_mut().expect("Part must serialize as an object.") {
+                if let Some(fields) = fields.as_object_mut() {
+                    fields.remove("id");
+                }
+            }
+            value
+        })
+        .collect()
+}
@@ -0,0 +1,503 @@
+//! Moodle XML question bank format.
+//!
+//! See: https://docs.moodle.org/en/Moodle_XML_format
+
+use roxmltree::{Document, Node};
+
+use crate::convert::{ConversionReport, ExportShape, ImportedQuiz, export_shape, format_fraction, format_problem, question_name, strip_html, wildcard_validation};
+use crate::data::{AnswerType, AnswerValidation, Part};
+use crate::error::Problem;
+use crate::id::PartId;
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn node_text(node: Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+/// Text of `<text>` element nested inside `name` child element.
+fn child_text(node: Node, name: &str) -> Option<String> {
+    let element = child(node, name)?;
+    match child(element, "text") {
+        Some(text) => Some(node_text(text)),
+        None => Some(node_text(element)),
+    }
+}
+
+fn child_flag(node: Node, name: &str) -> Option<bool> {
+    child_text(node, name).map(|value| {
+        let value = value.trim();
+        value == "1" || value.eq_ignore_ascii_case("true")
+    })
+}
+
+/// Answer text with its fraction of points in percent.
+struct MoodleAnswer {
+    text: String,
+    fraction: f64,
+    tolerance: f64,
+}
+
+fn answers(question: Node) -> Vec<MoodleAnswer> {
+    question.children()
+        .filter(|n| n.has_tag_name("answer"))
+        .map(|answer| {
+            let text = child(answer, "text").map(node_text).unwrap_or_default();
+            let text = match answer.attribute("format") {
+                Some("html") => strip_html(&text),
+                _ => text.trim().to_string(),
+            };
+            MoodleAnswer {
+                text,
+                fraction: answer.attribute("fraction")
+                    .and_then(|f| f.trim().parse().ok())
+                    .unwrap_or(0.0),
+                tolerance: child_text(answer, "tolerance")
+                    .and_then(|t| t.trim().parse().ok())
+                    .unwrap_or(0.0),
+            }
+        })
+        .collect()
+}
+
+#[inline]
+fn interact(text: String, value: f32, ans: AnswerType, validation: Option<AnswerValidation>, partial: bool) -> Part {
+    Part::Interact {
+        id: PartId::new(),
+        text,
+        ans,
+        time_limit: None,
+        value,
+        validation,
+        partial,
+    }
+}
+
+fn import_question(question: Node, name: &str, report: &mut ConversionReport) -> Option<Part> {
+    let text = child_text(question, "questiontext").unwrap_or_default();
+    let value = child_text(question, "defaultgrade")
+        .and_then(|grade| grade.trim().parse::<f32>().ok())
+        .unwrap_or(1.0);
+    let kind = question.attribute("type").unwrap_or("");
+
+    let part = match kind {
+        "multichoice" => {
+            let answers = answers(question);
+            if answers.is_empty() {
+                report.skip(name, "Multiple choice question has no options.");
+                return None;
+            }
+
+            let options: Vec<String> = answers.iter().map(|a| a.text.clone()).collect();
+            let shuffle = child_flag(question, "shuffleanswers").unwrap_or(true);
+
+            if child_flag(question, "single").unwrap_or(true) {
+                if answers.iter().any(|a| a.fraction > 0.0 && a.fraction < 100.0) {
+                    report.issue(name, "Partial credit for single choice options isn't supported, only the best option is accepted.");
+                }
+                let best = answers.iter()
+                    .max_by(|a, b| a.fraction.partial_cmp(&b.fraction).unwrap_or(std::cmp::Ordering::Equal))?;
+
+                interact(text, value, AnswerType::Single { options, shuffle }, Some(AnswerValidation::Exact {
+                    case_sensitive: true,
+                    expected: best.text.clone(),
+                    normalize: vec![],
+                }), false)
+            } else {
+                let expected: Vec<String> = answers.iter()
+                    .filter(|a| a.fraction > 0.0)
+                    .map(|a| a.text.clone())
+                    .collect();
+                if expected.iter().any(|e| answers.iter().any(|a| &a.text == e && (a.fraction - 100.0 / expected.len() as f64).abs() > 0.01)) {
+                    report.issue(name, "Uneven option fractions aren't supported, correct options are worth equal parts.");
+                }
+
+                interact(text, value, AnswerType::Multiple { options, shuffle }, Some(AnswerValidation::Multiple {
+                    case_sensitive: true,
+                    expected,
+                    normalize: vec![],
+                }), true)
+            }
+        }
+        "truefalse" => {
+            let expected = answers(question).into_iter()
+                .find(|a| a.fraction >= 100.0)
+                .map(|a| a.text.eq_ignore_ascii_case("true"));
+            match expected {
+                Some(expected) => interact(text, value, AnswerType::Bool, Some(AnswerValidation::Bool { expected }), false),
+                None => {
+                    report.skip(name, "True/false question has no correct answer.");
+                    return None;
+                }
+            }
+        }
+        "shortanswer" => {
+            let answers = answers(question);
+            let case_sensitive = child_flag(question, "usecase").unwrap_or(false);
+            if answers.iter().any(|a| a.fraction > 0.0 && a.fraction < 100.0) {
+                report.issue(name, "Partially correct answers aren't supported and were dropped.");
+            }
+
+            let mut expected: Vec<String> = answers.into_iter()
+                .filter(|a| a.fraction >= 100.0)
+                .map(|a| a.text)
+                .collect();
+
+            let validation = if expected.is_empty() {
+                report.skip(name, "Short answer question has no correct answer.");
+                return None;
+            } else if expected.iter().any(|e| e.contains('*')) {
+                wildcard_validation(&expected, case_sensitive)
+            } else if expected.len() == 1 {
+                AnswerValidation::Exact {
+                    case_sensitive,
+                    expected: expected.remove(0),
+                    normalize: vec![],
+                }
+            } else {
+                AnswerValidation::Multiple {
+                    case_sensitive,
+                    expected,
+                    normalize: vec![],
+                }
+            };
+
+            interact(text, value, AnswerType::Short, Some(validation), false)
+        }
+        "numerical" => {
+            let correct: Vec<MoodleAnswer> = answers(question).into_iter()
+                .filter(|a| a.fraction >= 100.0)
+                .collect();
+            if correct.len() > 1 {
+                report.issue(name, "Only the first correct numerical answer was imported.");
+            }
+
+            let answer = match correct.first().and_then(|a| a.text.trim().parse::<f64>().ok().map(|v| (v, a.tolerance.abs()))) {
+                Some(it) => it,
+                None => {
+                    report.skip(name, "Numerical question has no numeric correct answer.");
+                    return None;
+                }
+            };
+            if child(question, "units").is_some() {
+                report.issue(name, "Units aren't supported and were dropped.");
+            }
+
+            interact(text, value, AnswerType::Number, Some(AnswerValidation::NumberRange {
+                min: answer.0 - answer.1,
+                max: answer.0 + answer.1,
+            }), false)
+        }
+        "matching" => {
+            let mut pairs = vec![];
+            let mut distractors = 0;
+            for sub in question.children().filter(|n| n.has_tag_name("subquestion")) {
+                let left = child(sub, "text").map(node_text).unwrap_or_default();
+                let left = match sub.attribute("format") {
+                    Some("html") => strip_html(&left),
+                    _ => left.trim().to_string(),
+                };
+                let right = child_text(sub, "answer").unwrap_or_default().trim().to_string();
+
+                if left.is_empty() {
+                    distractors += 1;
+                } else {
+                    pairs.push((left, right));
+                }
+            }
+
+            if pairs.is_empty() {
+                report.skip(name, "Matching question has no pairs.");
+                return None;
+            }
+            if distractors > 0 {
+                report.issue(name, format!("{} distractor answer(s) without a pair were dropped.", distractors));
+            }
+
+            interact(text, value, AnswerType::Match(pairs), None, true)
+        }
+        "essay" => interact(text, value, AnswerType::Long, None, false),
+        "description" => Part::Content {
+            id: PartId::new(),
+            title: name.to_string(),
+            text,
+        },
+        other => {
+            report.skip(name, format!("Question type '{}' isn't supported.", other));
+            return None;
+        }
+    };
+
+    Some(part)
+}
+
+pub fn import(xml: &str) -> Result<ImportedQuiz, Problem> {
+    let document = Document::parse(xml).map_err(|e| {
+        let pos = e.pos();
+        format_problem(pos.row, pos.col, e.to_string())
+    })?;
+
+    let root = document.root_element();
+    if !root.has_tag_name("quiz") {
+        let pos = document.text_pos_at(root.range().start);
+        return Err(format_problem(pos.row, pos.col, "Root element must be <quiz>."));
+    }
+
+    let mut name = None;
+    let mut parts = vec![];
+    let mut report = ConversionReport::default();
+
+    for (index, question) in root.children().filter(|n| n.has_tag_name("question")).enumerate() {
+        if question.attribute("type") == Some("category") {
+            // Last category path segment is the best name we can get for a quiz.
+            if name.is_none() {
+                name = child_text(question, "category")
+                    .and_then(|path| path.trim().rsplit('/').next().map(|n| n.to_string()))
+                    .filter(|n| !n.is_empty() && !n.starts_with('$'));
+            }
+            continue;
+        }
+
+        let question_name = child_text(question, "name")
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| question_name(index, ""));
+
+        if let Some(part) = import_question(question, &question_name, &mut report) {
+            parts.push(part);
+            report.converted += 1;
+        }
+    }
+
+    Ok(ImportedQuiz::from_parts(name, parts, report))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn write_answer(out: &mut String, fraction: f64, text: &str, extra: &str) {
+    out.push_str(&format!(
+        "    <answer fraction=\"{}\" format=\"plain_text\">\n      <text>{}</text>\n{}    </answer>\n",
+        format_fraction(fraction),
+        escape(text),
+        extra,
+    ));
+}
+
+pub fn export(name: &str, parts: &[Part]) -> (String, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n");
+    out.push_str(&format!(
+        "  <question type=\"category\">\n    <category>\n      <text>$course$/{}</text>\n    </category>\n  </question>\n",
+        escape(&name.replace('/', "-")),
+    ));
+
+    for (index, part) in parts.iter().enumerate() {
+        let shape = match export_shape(index, part, &mut report) {
+            Some(it) => it,
+            None => continue,
+        };
+
+        let (text, value) = match part {
+            Part::Content { text, .. } => (text, 0.0),
+            Part::Interact { text, value, .. } => (text, *value),
+        };
+
+        let kind = match &shape {
+            ExportShape::Description { .. } => "description",
+            ExportShape::TrueFalse { .. } => "truefalse",
+            ExportShape::Choice { .. } => "multichoice",
+            ExportShape::ShortAnswer { .. } => "shortanswer",
+            ExportShape::Numerical { .. } => "numerical",
+            ExportShape::Matching { .. } => "matching",
+            ExportShape::Essay => "essay",
+        };
+        let title = match &shape {
+            ExportShape::Description { title } if !title.is_empty() => title.to_string(),
+            _ => question_name(index, text),
+        };
+
+        out.push_str(&format!("  <question type=\"{}\">\n", kind));
+        out.push_str(&format!("    <name>\n      <text>{}</text>\n    </name>\n", escape(&title)));
+        out.push_str(&format!(
+            "    <questiontext format=\"html\">\n      <text>{}</text>\n    </questiontext>\n",
+            cdata(text),
+        ));
+        out.push_str(&format!("    <defaultgrade>{}</defaultgrade>\n", value));
+
+        match shape {
+            ExportShape::Description { .. } => {}
+            ExportShape::TrueFalse { expected } => {
+                write_answer(&mut out, if expected { 100.0 } else { 0.0 }, "true", "");
+                write_answer(&mut out, if expected { 0.0 } else { 100.0 }, "false", "");
+            }
+            ExportShape::Choice { options, single, shuffle } => {
+                out.push_str(&format!("    <single>{}</single>\n", single));
+                out.push_str(&format!("    <shuffleanswers>{}</shuffleanswers>\n", shuffle));
+                out.push_str("    <answernumbering>abc</answernumbering>\n");
+                for (option, fraction) in options {
+                    write_answer(&mut out, fraction, option, "");
+                }
+            }
+            ExportShape::ShortAnswer { expected, case_sensitive } => {
+                out.push_str(&format!("    <usecase>{}</usecase>\n", if case_sensitive { 1 } else { 0 }));
+                for answer in expected {
+                    write_answer(&mut out, 100.0, &answer, "");
+                }
+            }
+            ExportShape::Numerical { value, tolerance } => {
+                write_answer(&mut out, 100.0, &value.to_string(), &format!(
+                    "      <tolerance>{}</tolerance>\n", tolerance,
+                ));
+            }
+            ExportShape::Matching { pairs } => {
+                out.push_str("    <shuffleanswers>true</shuffleanswers>\n");
+                for (left, right) in pairs {
+                    out.push_str(&format!(
+                        "    <subquestion format=\"plain_text\">\n      <text>{}</text>\n      <answer>\n        <text>{}</text>\n      </answer>\n    </subquestion>\n",
+                        escape(left),
+                        escape(right),
+                    ));
+                }
+            }
+            ExportShape::Essay => {
+                out.push_str("    <responseformat>editor</responseformat>\n");
+            }
+        }
+
+        out.push_str("  </question>\n");
+        report.converted += 1;
+    }
+
+    out.push_str("</quiz>\n");
+
+    (out, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::without_ids;
+
+    static FIXTURE: &'static str = include_str!("fixtures/networking.xml");
+
+    fn position(problem: &Problem) -> (Option<u64>, Option<u64>) {
+        (problem.body["line"].as_u64(), problem.body["column"].as_u64())
+    }
+
+    #[test]
+    fn imports_fixture() {
+        let quiz = import(FIXTURE).unwrap().quiz;
+        assert_eq!(quiz.name, "Basics");
+        assert_eq!(quiz.parts.len(), 8);
+
+        match &quiz.parts[0] {
+            Part::Content { title, text, .. } => {
+                assert_eq!(title, "Intro");
+                assert_eq!(text, "This quiz covers <b>LAN</b> basics.");
+            }
+            other => panic!("Expected content, got {:?}", other),
+        }
+
+        match &quiz.parts[1] {
+            Part::Interact { value, validation: Some(AnswerValidation::Bool { expected }), .. } => {
+                assert_eq!(*value, 2.0);
+                assert!(expected);
+            }
+            other => panic!("Expected true/false question, got {:?}", other),
+        }
+
+        match &quiz.parts[2] {
+            Part::Interact { ans: AnswerType::Single { options, shuffle }, validation: Some(AnswerValidation::Exact { expected, .. }), .. } => {
+                assert_eq!(options, &vec!["Data link".to_string(), "Network".to_string(), "Physical".to_string()]);
+                assert!(!shuffle);
+                assert_eq!(expected, "Data link");
+            }
+            other => panic!("Expected single choice, got {:?}", other),
+        }
+
+        match &quiz.parts[4] {
+            Part::Interact { validation: Some(AnswerValidation::Exact { case_sensitive, expected, .. }), .. } => {
+                assert!(case_sensitive);
+                assert_eq!(expected, "Network");
+            }
+            other => panic!("Expected short answer, got {:?}", other),
+        }
+
+        match &quiz.parts[6] {
+            Part::Interact { ans: AnswerType::Match(pairs), .. } => {
+                assert_eq!(pairs, &vec![("HTTP".to_string(), "80".to_string()), ("SSH".to_string(), "22".to_string())]);
+            }
+            other => panic!("Expected matching question, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_skipped_questions() {
+        let report = import(FIXTURE).unwrap().report;
+        assert_eq!((report.converted, report.skipped), (8, 2));
+
+        let issues: Vec<(&str, &str)> = report.issues.iter()
+            .map(|issue| (issue.question.as_str(), issue.message.as_str()))
+            .collect();
+        assert_eq!(issues, vec![
+            ("LAN", "Partially correct answers aren't supported and were dropped."),
+            ("Pairs", "1 distractor answer(s) without a pair were dropped."),
+            ("Subnets", "Question type 'calculated' isn't supported."),
+            ("Unanswered", "True/false question has no correct answer."),
+        ]);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let quiz = import(FIXTURE).unwrap().quiz;
+
+        let (exported, report) = export(&quiz.name, &quiz.parts);
+        assert_eq!(report.converted, quiz.parts.len());
+        assert!(report.issues.is_empty(), "Unexpected issues: {:?}", report.issues);
+
+        let reimported = import(&exported).unwrap();
+        assert_eq!(reimported.quiz.name, quiz.name);
+        assert!(reimported.report.issues.is_empty(), "Unexpected issues: {:?}", reimported.report.issues);
+        assert_eq!(without_ids(&reimported.quiz.parts), without_ids(&quiz.parts));
+    }
+
+    #[test]
+    fn reports_unsupported_exports() {
+        let parts = vec![
+            interact("Fill in the blank.".to_string(), 1.0, AnswerType::FillIn, None, false),
+            interact("Explain routing.".to_string(), 1.0, AnswerType::Short, None, false),
+        ];
+
+        let (exported, report) = export("Unsupported", &parts);
+        assert_eq!((report.converted, report.skipped), (1, 1));
+        assert_eq!(report.issues[0].message, "Fill-in questions aren't supported by the format.");
+        assert_eq!(report.issues[1].message, "Question has no validation, exported as essay.");
+        assert!(exported.contains("<question type=\"essay\">"));
+    }
+
+    #[test]
+    fn problems_point_at_source() {
+        let problem = import("<quiz>\n  <question type=\"essay\">\n    <name></question>\n</quiz>").unwrap_err();
+        assert_eq!(position(&problem), (Some(3), Some(11)));
+
+        let problem = import("<?xml version=\"1.0\"?>\n\n  <questions/>\n").unwrap_err();
+        assert_eq!(problem.detail.as_deref(), Some("Root element must be <quiz>."));
+        assert_eq!(position(&problem), (Some(3), Some(3)));
+    }
+}
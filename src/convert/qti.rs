@@ -0,0 +1,873 @@
+//! IMS QTI content packages.
+//!
+//! Packages are exported as QTI 2.1 and both QTI 2.x and 3.0 packages can be imported. QTI 3.0
+//! uses `qti-` prefixed kebab-case names for the same elements, so names are compared in both
+//! forms.
+//!
+//! See: https://www.imsglobal.org/question/qtiv2p1/imsqti_v2p1.html
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+
+use roxmltree::{Document, Node};
+use uuid::Uuid;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::FileOptions;
+
+use crate::convert::{ConversionReport, ExportShape, ImportedQuiz, export_shape, format_fraction, format_problem, question_name};
+use crate::data::{AnswerType, AnswerValidation, Part};
+use crate::error::Problem;
+use crate::id::PartId;
+
+static MANIFEST_FILE: &'static str = "imsmanifest.xml";
+static QTI_NAMESPACE: &'static str = "http://www.imsglobal.org/xsd/imsqti_v2p1";
+static CP_NAMESPACE: &'static str = "http://www.imsglobal.org/xsd/imscp_v1p1";
+static MATCH_CORRECT: &'static str = "http://www.imsglobal.org/question/qti_v2p1/rptemplates/match_correct";
+static MAP_RESPONSE: &'static str = "http://www.imsglobal.org/question/qti_v2p1/rptemplates/map_response";
+
+/// Largest uncompressed package entry that will be read, guards against zip bombs.
+const MAX_ENTRY_SIZE: u64 = 4 * 1024 * 1024;
+/// Largest number of uncompressed bytes read from all package entries together.
+const MAX_PACKAGE_SIZE: u64 = 16 * 1024 * 1024;
+/// Largest number of items a package can contain.
+const MAX_ITEMS: usize = 500;
+
+/// Converts QTI 2 camelCase name into QTI 3 kebab-case, e.g. `maxChoices` -> `max-choices`.
+fn kebab(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            result.push('-');
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn is(node: Node, name: &str) -> bool {
+    if !node.is_element() {
+        return false;
+    }
+    let tag = node.tag_name().name();
+    tag == name || (tag.starts_with("qti-") && tag[4..] == kebab(name))
+}
+
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute(name).or_else(|| node.attribute(kebab(name).as_str()))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| is(*n, name))
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn node_text(node: Node) -> String {
+    let text: String = node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect();
+    normalize_whitespace(&text)
+}
+
+fn is_interaction(node: Node) -> bool {
+    node.is_element() && {
+        let tag = node.tag_name().name();
+        tag.ends_with("Interaction") || tag.ends_with("-interaction")
+    }
+}
+
+/// Collects item body text. Only prompts are taken from interactions, their choices are options.
+fn body_text(node: Node, out: &mut String) {
+    for n in node.children() {
+        if n.is_text() {
+            out.push_str(n.text().unwrap_or(""));
+        } else if is_interaction(n) {
+            if let Some(prompt) = child(n, "prompt") {
+                out.push(' ');
+                out.push_str(&node_text(prompt));
+            }
+        } else if n.is_element() {
+            out.push(' ');
+            body_text(n, out);
+        }
+    }
+}
+
+struct ResponseDeclaration {
+    correct: Vec<String>,
+    /// Mapped response values with their scores and case sensitivity.
+    mapping: Vec<(String, f64, bool)>,
+}
+
+fn response_declarations(item: Node) -> HashMap<String, ResponseDeclaration> {
+    item.children()
+        .filter(|n| is(*n, "responseDeclaration"))
+        .filter_map(|declaration| {
+            let identifier = attr(declaration, "identifier")?.to_string();
+            let correct = child(declaration, "correctResponse")
+                .map(|c| c.children()
+                    .filter(|v| is(*v, "value"))
+                    .map(|v| node_text(v))
+                    .collect())
+                .unwrap_or_default();
+            let mapping = child(declaration, "mapping")
+                .map(|m| m.children()
+                    .filter(|e| is(*e, "mapEntry"))
+                    .filter_map(|e| Some((
+                        attr(e, "mapKey")?.to_string(),
+                        attr(e, "mappedValue")?.trim().parse::<f64>().ok()?,
+                        attr(e, "caseSensitive").map_or(true, |c| c == "true"),
+                    )))
+                    .collect())
+                .unwrap_or_default();
+
+            Some((identifier, ResponseDeclaration { correct, mapping }))
+        })
+        .collect()
+}
+
+/// Item value taken from `MAXSCORE` outcome or `SCORE` normal maximum.
+fn item_value(item: Node) -> f32 {
+    let outcomes: Vec<Node> = item.children()
+        .filter(|n| is(*n, "outcomeDeclaration"))
+        .collect();
+
+    let max_score = outcomes.iter()
+        .find(|o| attr(**o, "identifier") == Some("MAXSCORE"))
+        .and_then(|o| child(*o, "defaultValue"))
+        .and_then(|d| child(d, "value"))
+        .and_then(|v| node_text(v).parse::<f32>().ok());
+    let normal_maximum = outcomes.iter()
+        .find(|o| attr(**o, "identifier") == Some("SCORE"))
+        .and_then(|o| attr(*o, "normalMaximum"))
+        .and_then(|v| v.trim().parse::<f32>().ok());
+
+    max_score.or(normal_maximum).unwrap_or(1.0)
+}
+
+/// Absolute tolerance of `equal` numeric comparison in custom response processing.
+fn numeric_tolerance(item: Node) -> f64 {
+    item.descendants()
+        .find(|n| is(*n, "equal") && attr(*n, "toleranceMode") == Some("absolute"))
+        .and_then(|n| attr(n, "tolerance"))
+        .and_then(|t| t.split_whitespace().next())
+        .and_then(|t| t.parse::<f64>().ok())
+        .unwrap_or(0.0)
+        .abs()
+}
+
+#[inline]
+fn interact(text: String, value: f32, ans: AnswerType, validation: Option<AnswerValidation>, partial: bool) -> Part {
+    Part::Interact {
+        id: PartId::new(),
+        text,
+        ans,
+        time_limit: None,
+        value,
+        validation,
+        partial,
+    }
+}
+
+fn import_item(item: Node, name: &str, report: &mut ConversionReport) -> Option<Part> {
+    let body = match child(item, "itemBody") {
+        Some(it) => it,
+        None => {
+            report.skip(name, "Item has no body.");
+            return None;
+        }
+    };
+
+    let mut text = String::new();
+    body_text(body, &mut text);
+    let text = normalize_whitespace(&text);
+    let value = item_value(item);
+
+    let interactions: Vec<Node> = body.descendants().filter(|n| is_interaction(*n)).collect();
+    let interaction = match interactions.first() {
+        Some(it) => *it,
+        None => return Some(Part::Content {
+            id: PartId::new(),
+            title: name.to_string(),
+            text,
+        }),
+    };
+    if interactions.len() > 1 {
+        report.issue(name, "Item has multiple interactions, only the first one was imported.");
+    }
+
+    let declarations = response_declarations(item);
+    let declaration = attr(interaction, "responseIdentifier").and_then(|id| declarations.get(id));
+    let correct: Vec<String> = declaration.map(|d| d.correct.clone()).unwrap_or_default();
+
+    let part = if is(interaction, "choiceInteraction") {
+        let choices: Vec<(String, String)> = interaction.children()
+            .filter(|n| is(*n, "simpleChoice"))
+            .filter_map(|c| Some((attr(c, "identifier")?.to_string(), node_text(c))))
+            .collect();
+        if choices.is_empty() {
+            report.skip(name, "Choice interaction has no choices.");
+            return None;
+        }
+
+        let choice_text = |id: &String| choices.iter().find(|(i, _)| i == id).map(|(_, t)| t.clone());
+        let options: Vec<String> = choices.iter().map(|(_, t)| t.clone()).collect();
+        let shuffle = attr(interaction, "shuffle").map_or(false, |s| s == "true");
+        let max_choices = attr(interaction, "maxChoices")
+            .and_then(|m| m.trim().parse::<usize>().ok())
+            .unwrap_or(1);
+
+        if correct.is_empty() {
+            report.issue(name, "Item has no correct response and must be graded manually.");
+        }
+
+        if max_choices == 1 {
+            let is_bool = options.len() == 2 &&
+                options.iter().all(|o| o.eq_ignore_ascii_case("true") || o.eq_ignore_ascii_case("false"));
+            let expected = correct.first().and_then(|c| choice_text(c));
+
+            if is_bool {
+                interact(text, value, AnswerType::Bool, expected.map(|e| AnswerValidation::Bool {
+                    expected: e.eq_ignore_ascii_case("true"),
+                }), false)
+            } else {
+                interact(text, value, AnswerType::Single { options, shuffle }, expected.map(|e| AnswerValidation::Exact {
+                    case_sensitive: true,
+                    expected: e,
+                    normalize: vec![],
+                }), false)
+            }
+        } else {
+            let expected: Vec<String> = correct.iter().filter_map(|c| choice_text(c)).collect();
+            interact(text, value, AnswerType::Multiple { options, shuffle }, if expected.is_empty() {
+                None
+            } else {
+                Some(AnswerValidation::Multiple {
+                    case_sensitive: true,
+                    expected,
+                    normalize: vec![],
+                })
+            }, true)
+        }
+    } else if is(interaction, "textEntryInteraction") {
+        let base_type = item.children()
+            .filter(|n| is(*n, "responseDeclaration"))
+            .find(|n| attr(*n, "identifier") == attr(interaction, "responseIdentifier"))
+            .and_then(|n| attr(n, "baseType"))
+            .unwrap_or("string");
+
+        if base_type == "float" || base_type == "integer" {
+            let value_range = correct.first()
+                .and_then(|c| c.trim().parse::<f64>().ok())
+                .map(|v| (v, numeric_tolerance(item)));
+
+            match value_range {
+                Some((v, tolerance)) => interact(text, value, AnswerType::Number, Some(AnswerValidation::NumberRange {
+                    min: v - tolerance,
+                    max: v + tolerance,
+                }), false),
+                None => {
+                    report.issue(name, "Item has no numeric correct response and must be graded manually.");
+                    interact(text, value, AnswerType::Number, None, false)
+                }
+            }
+        } else {
+            let mapping = declaration.map(|d| d.mapping.clone()).unwrap_or_default();
+            let best = mapping.iter().map(|(_, v, _)| *v).fold(0.0, f64::max);
+            if mapping.iter().any(|(_, v, _)| *v > 0.0 && *v < best) {
+                report.issue(name, "Partially correct answers aren't supported and were dropped.");
+            }
+
+            let mut expected = correct.clone();
+            for (key, mapped, _) in &mapping {
+                if *mapped >= best && *mapped > 0.0 && !expected.contains(key) {
+                    expected.push(key.clone());
+                }
+            }
+            let case_sensitive = mapping.iter().all(|(_, _, cs)| *cs);
+
+            let validation = match expected.len() {
+                0 => {
+                    report.issue(name, "Item has no correct response and must be graded manually.");
+                    None
+                }
+                1 => Some(AnswerValidation::Exact {
+                    case_sensitive,
+                    expected: expected.remove(0),
+                    normalize: vec![],
+                }),
+                _ => Some(AnswerValidation::Multiple {
+                    case_sensitive,
+                    expected,
+                    normalize: vec![],
+                }),
+            };
+
+            interact(text, value, AnswerType::Short, validation, false)
+        }
+    } else if is(interaction, "matchInteraction") {
+        let sets: Vec<Vec<(String, String)>> = interaction.children()
+            .filter(|n| is(*n, "simpleMatchSet"))
+            .map(|set| set.children()
+                .filter(|c| is(*c, "simpleAssociableChoice"))
+                .filter_map(|c| Some((attr(c, "identifier")?.to_string(), node_text(c))))
+                .collect())
+            .collect();
+        if sets.len() != 2 {
+            report.skip(name, "Match interaction must have exactly two match sets.");
+            return None;
+        }
+
+        let lookup = |set: &Vec<(String, String)>, id: &str| set.iter().find(|(i, _)| i == id).map(|(_, t)| t.clone());
+        let mut pairs = vec![];
+        for value in &correct {
+            let mut ids = value.split_whitespace();
+            match (ids.next().and_then(|l| lookup(&sets[0], l)), ids.next().and_then(|r| lookup(&sets[1], r))) {
+                (Some(left), Some(right)) => pairs.push((left, right)),
+                _ => report.issue(name, format!("Correct pair '{}' doesn't refer to match choices and was dropped.", value)),
+            }
+        }
+
+        if pairs.is_empty() {
+            report.skip(name, "Match interaction has no correct pairs.");
+            return None;
+        }
+        if sets[1].len() > pairs.len() {
+            report.issue(name, "Distractor choices without a pair were dropped.");
+        }
+
+        interact(text, value, AnswerType::Match(pairs), None, true)
+    } else if is(interaction, "extendedTextInteraction") {
+        interact(text, value, AnswerType::Long, None, false)
+    } else {
+        report.skip(name, format!("Interaction '{}' isn't supported.", interaction.tag_name().name()));
+        return None;
+    };
+
+    Some(part)
+}
+
+/// Reads an entry, subtracting its size from `remaining` bytes allowed to be read from the package.
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str, remaining: &mut u64) -> Result<String, Problem> {
+    let mut file = archive.by_name(path)
+        .map_err(|e| format_problem(0, 0, format!("Unable to read '{}' from package: {}", path, e)))?;
+    if file.size() > MAX_ENTRY_SIZE {
+        return Err(format_problem(0, 0, format!("Package entry '{}' is too large.", path)));
+    }
+    if file.size() > *remaining {
+        return Err(format_problem(0, 0, "Content package is too large."));
+    }
+
+    let mut content = String::new();
+    file.by_ref()
+        .take(MAX_ENTRY_SIZE.min(*remaining))
+        .read_to_string(&mut content)
+        .map_err(|e| format_problem(0, 0, format!("Unable to read '{}' from package: {}", path, e)))?;
+    *remaining -= content.len() as u64;
+    Ok(content)
+}
+
+fn parse_xml<'input>(path: &str, xml: &'input str) -> Result<Document<'input>, Problem> {
+    Document::parse(xml).map_err(|e| {
+        let pos = e.pos();
+        format_problem(pos.row, pos.col, format!("{}: {}", path, e))
+            .insert_serialized("file", path)
+            .clone()
+    })
+}
+
+fn package_path(href: &str) -> String {
+    href.trim_start_matches("./").replace('\\', "/")
+}
+
+pub fn import(package: &[u8]) -> Result<ImportedQuiz, Problem> {
+    let mut archive = ZipArchive::new(Cursor::new(package))
+        .map_err(|e| format_problem(0, 0, format!("Invalid content package: {}", e)))?;
+
+    let mut remaining = MAX_PACKAGE_SIZE;
+    let manifest_xml = read_entry(&mut archive, MANIFEST_FILE, &mut remaining)?;
+    let manifest = parse_xml(MANIFEST_FILE, &manifest_xml)?;
+
+    let resources: Vec<(String, String)> = manifest.descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == "resource")
+        .filter_map(|r| Some((r.attribute("type")?.to_string(), package_path(r.attribute("href")?))))
+        .collect();
+
+    let items: HashSet<&str> = resources.iter()
+        .filter(|(kind, _)| kind.starts_with("imsqti_item_"))
+        .map(|(_, path)| path.as_str())
+        .collect();
+    if items.len() > MAX_ITEMS {
+        return Err(format_problem(0, 0, format!("Content package contains more than {} items.", MAX_ITEMS)));
+    }
+
+    let mut name = None;
+    let mut parts = vec![];
+    let mut report = ConversionReport::default();
+    let mut imported = HashSet::new();
+
+    for (kind, path) in &resources {
+        if kind.starts_with("imsqti_test_") {
+            if name.is_none() {
+                let test_xml = read_entry(&mut archive, path, &mut remaining)?;
+                let test = parse_xml(path, &test_xml)?;
+                name = test.root_element().attribute("title").map(|t| t.to_string());
+            }
+            continue;
+        }
+        if !kind.starts_with("imsqti_item_") {
+            continue;
+        }
+
+        // Several resources can reference the same file, it's imported only once.
+        if !imported.insert(path.as_str()) {
+            continue;
+        }
+
+        let item_xml = read_entry(&mut archive, path, &mut remaining)?;
+        let item = parse_xml(path, &item_xml)?;
+        let root = item.root_element();
+        if !is(root, "assessmentItem") {
+            report.skip(path.as_str(), "Resource isn't an assessment item.");
+            continue;
+        }
+
+        let item_name = attr(root, "title")
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| path.clone());
+
+        if let Some(part) = import_item(root, &item_name, &mut report) {
+            parts.push(part);
+            report.converted += 1;
+        }
+    }
+
+    if resources.iter().all(|(kind, _)| !kind.starts_with("imsqti_item_")) {
+        return Err(format_problem(0, 0, "Content package doesn't contain any QTI items."));
+    }
+
+    Ok(ImportedQuiz::from_parts(name, parts, report))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn response_declaration(cardinality: &str, base_type: &str, correct: &[String], mapping: &[(String, f64, bool)]) -> String {
+    let mut out = format!(
+        "  <responseDeclaration identifier=\"RESPONSE\" cardinality=\"{}\" baseType=\"{}\">\n",
+        cardinality, base_type,
+    );
+    if !correct.is_empty() {
+        out.push_str("    <correctResponse>\n");
+        for value in correct {
+            out.push_str(&format!("      <value>{}</value>\n", escape(value)));
+        }
+        out.push_str("    </correctResponse>\n");
+    }
+    if !mapping.is_empty() {
+        out.push_str("    <mapping defaultValue=\"0\">\n");
+        for (key, value, case_sensitive) in mapping {
+            out.push_str(&format!(
+                "      <mapEntry mapKey=\"{}\" mappedValue=\"{}\" caseSensitive=\"{}\"/>\n",
+                escape(key), format_fraction(*value), case_sensitive,
+            ));
+        }
+        out.push_str("    </mapping>\n");
+    }
+    out.push_str("  </responseDeclaration>\n");
+    out
+}
+
+fn export_item(identifier: &str, title: &str, text: &str, value: f32, shape: ExportShape, report: &mut ConversionReport) -> String {
+    let value = value as f64;
+    let mut declarations = String::new();
+    let mut interaction = String::new();
+    let mut processing = String::new();
+
+    match shape {
+        ExportShape::Description { .. } => {}
+        ExportShape::TrueFalse { expected } => {
+            declarations = response_declaration("single", "identifier", &[expected.to_string()], &[]);
+            interaction = "    <choiceInteraction responseIdentifier=\"RESPONSE\" shuffle=\"false\" maxChoices=\"1\">\n      \
+                <simpleChoice identifier=\"true\">true</simpleChoice>\n      \
+                <simpleChoice identifier=\"false\">false</simpleChoice>\n    \
+                </choiceInteraction>\n".to_string();
+            processing = format!("  <responseProcessing template=\"{}\"/>\n", MATCH_CORRECT);
+        }
+        ExportShape::Choice { options, single, shuffle } => {
+            let correct: Vec<String> = options.iter().enumerate()
+                .filter(|(_, (_, fraction))| *fraction > 0.0)
+                .map(|(i, _)| format!("C{}", i))
+                .collect();
+
+            if single {
+                declarations = response_declaration("single", "identifier", &correct, &[]);
+                processing = format!("  <responseProcessing template=\"{}\"/>\n", MATCH_CORRECT);
+            } else {
+                let mapping: Vec<(String, f64, bool)> = options.iter().enumerate()
+                    .map(|(i, (_, fraction))| (format!("C{}", i), value * fraction / 100.0, true))
+                    .collect();
+                declarations = response_declaration("multiple", "identifier", &correct, &mapping);
+                processing = format!("  <responseProcessing template=\"{}\"/>\n", MAP_RESPONSE);
+            }
+
+            interaction.push_str(&format!(
+                "    <choiceInteraction responseIdentifier=\"RESPONSE\" shuffle=\"{}\" maxChoices=\"{}\">\n",
+                shuffle, if single { 1 } else { 0 },
+            ));
+            for (i, (option, _)) in options.iter().enumerate() {
+                interaction.push_str(&format!("      <simpleChoice identifier=\"C{}\">{}</simpleChoice>\n", i, escape(option)));
+            }
+            interaction.push_str("    </choiceInteraction>\n");
+        }
+        ExportShape::ShortAnswer { expected, case_sensitive } => {
+            if expected.iter().any(|e| e.contains('*')) {
+                report.issue(title, "Wildcard answers can't be represented in QTI, item must be graded manually.");
+                declarations = response_declaration("single", "string", &[], &[]);
+            } else {
+                let mapping: Vec<(String, f64, bool)> = expected.iter()
+                    .map(|e| (e.clone(), value, case_sensitive))
+                    .collect();
+                declarations = response_declaration("single", "string", &expected[..expected.len().min(1)], &mapping);
+                processing = format!("  <responseProcessing template=\"{}\"/>\n", MAP_RESPONSE);
+            }
+            interaction = "    <p><textEntryInteraction responseIdentifier=\"RESPONSE\"/></p>\n".to_string();
+        }
+        ExportShape::Numerical { value: expected, tolerance } => {
+            declarations = response_declaration("single", "float", &[expected.to_string()], &[]);
+            interaction = "    <p><textEntryInteraction responseIdentifier=\"RESPONSE\"/></p>\n".to_string();
+            processing = format!(
+                "  <responseProcessing>\n    <responseCondition>\n      <responseIf>\n        \
+                <equal toleranceMode=\"absolute\" tolerance=\"{0} {0}\">\n          \
+                <variable identifier=\"RESPONSE\"/>\n          <correct identifier=\"RESPONSE\"/>\n        </equal>\n        \
+                <setOutcomeValue identifier=\"SCORE\">\n          <baseValue baseType=\"float\">{1}</baseValue>\n        </setOutcomeValue>\n      \
+                </responseIf>\n    </responseCondition>\n  </responseProcessing>\n",
+                tolerance, value,
+            );
+        }
+        ExportShape::Matching { pairs } => {
+            let correct: Vec<String> = (0..pairs.len()).map(|i| format!("L{} R{}", i, i)).collect();
+            let mapping: Vec<(String, f64, bool)> = correct.iter()
+                .map(|c| (c.clone(), value / pairs.len() as f64, true))
+                .collect();
+            declarations = response_declaration("multiple", "directedPair", &correct, &mapping);
+            processing = format!("  <responseProcessing template=\"{}\"/>\n", MAP_RESPONSE);
+
+            interaction.push_str(&format!(
+                "    <matchInteraction responseIdentifier=\"RESPONSE\" shuffle=\"true\" maxAssociations=\"{}\">\n      <simpleMatchSet>\n",
+                pairs.len(),
+            ));
+            for (i, (left, _)) in pairs.iter().enumerate() {
+                interaction.push_str(&format!(
+                    "        <simpleAssociableChoice identifier=\"L{}\" matchMax=\"1\">{}</simpleAssociableChoice>\n", i, escape(left),
+                ));
+            }
+            interaction.push_str("      </simpleMatchSet>\n      <simpleMatchSet>\n");
+            for (i, (_, right)) in pairs.iter().enumerate() {
+                interaction.push_str(&format!(
+                    "        <simpleAssociableChoice identifier=\"R{}\" matchMax=\"1\">{}</simpleAssociableChoice>\n", i, escape(right),
+                ));
+            }
+            interaction.push_str("      </simpleMatchSet>\n    </matchInteraction>\n");
+        }
+        ExportShape::Essay => {
+            declarations = response_declaration("single", "string", &[], &[]);
+            interaction = "    <extendedTextInteraction responseIdentifier=\"RESPONSE\"/>\n".to_string();
+        }
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <assessmentItem xmlns=\"{ns}\" identifier=\"{id}\" title=\"{title}\" adaptive=\"false\" timeDependent=\"false\">\n\
+        {declarations}\
+        \x20 <outcomeDeclaration identifier=\"SCORE\" cardinality=\"single\" baseType=\"float\" normalMaximum=\"{value}\">\n    \
+        <defaultValue>\n      <value>0</value>\n    </defaultValue>\n  </outcomeDeclaration>\n\
+        \x20 <itemBody>\n    <div>{text}</div>\n{interaction}  </itemBody>\n\
+        {processing}\
+        </assessmentItem>\n",
+        ns = QTI_NAMESPACE,
+        id = identifier,
+        title = escape(title),
+        declarations = declarations,
+        value = value,
+        text = escape(text),
+        interaction = interaction,
+        processing = processing,
+    )
+}
+
+fn write_entry(zip: &mut ZipWriter<Cursor<Vec<u8>>>, path: &str, content: &str) {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(path, options)
+        .and_then(|_| zip.write_all(content.as_bytes()).map_err(|e| e.into()))
+        .expect("Unable to write QTI package entry into memory.");
+}
+
+pub fn export(name: &str, parts: &[Part]) -> (Vec<u8>, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let mut items = vec![];
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for (index, part) in parts.iter().enumerate() {
+        let shape = match export_shape(index, part, &mut report) {
+            Some(it) => it,
+            None => continue,
+        };
+
+        let (text, value) = match part {
+            Part::Content { text, .. } => (text, 0.0),
+            Part::Interact { text, value, .. } => (text, *value),
+        };
+        let title = match &shape {
+            ExportShape::Description { title } if !title.is_empty() => title.to_string(),
+            _ => question_name(index, text),
+        };
+
+        let identifier = format!("ITEM-{}", part.id());
+        let path = format!("items/{}.xml", identifier);
+        write_entry(&mut zip, &path, &export_item(&identifier, &title, text, value, shape, &mut report));
+
+        items.push((identifier, path));
+        report.converted += 1;
+    }
+
+    let test_id = format!("TEST-{}", Uuid::new_v4());
+    let mut test = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <assessmentTest xmlns=\"{}\" identifier=\"{}\" title=\"{}\">\n  \
+        <testPart identifier=\"PART-1\" navigationMode=\"linear\" submissionMode=\"individual\">\n    \
+        <assessmentSection identifier=\"SECTION-1\" title=\"{}\" visible=\"true\">\n",
+        QTI_NAMESPACE, test_id, escape(name), escape(name),
+    );
+    for (identifier, path) in &items {
+        test.push_str(&format!("      <assessmentItemRef identifier=\"{}\" href=\"{}\"/>\n", identifier, path));
+    }
+    test.push_str("    </assessmentSection>\n  </testPart>\n</assessmentTest>\n");
+    write_entry(&mut zip, "test.xml", &test);
+
+    let mut manifest = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <manifest xmlns=\"{}\" identifier=\"MANIFEST-{}\">\n  \
+        <metadata>\n    <schema>QTIv2.1 Package</schema>\n    <schemaversion>1.0.0</schemaversion>\n  </metadata>\n  \
+        <organizations/>\n  <resources>\n    \
+        <resource identifier=\"{}\" type=\"imsqti_test_xmlv2p1\" href=\"test.xml\">\n      <file href=\"test.xml\"/>\n",
+        CP_NAMESPACE, Uuid::new_v4(), test_id,
+    );
+    for (identifier, _) in &items {
+        manifest.push_str(&format!("      <dependency identifierref=\"{}\"/>\n", identifier));
+    }
+    manifest.push_str("    </resource>\n");
+    for (identifier, path) in &items {
+        manifest.push_str(&format!(
+            "    <resource identifier=\"{0}\" type=\"imsqti_item_xmlv2p1\" href=\"{1}\">\n      <file href=\"{1}\"/>\n    </resource>\n",
+            identifier, path,
+        ));
+    }
+    manifest.push_str("  </resources>\n</manifest>\n");
+    write_entry(&mut zip, MANIFEST_FILE, &manifest);
+
+    let package = zip.finish()
+        .expect("Unable to finish QTI package.")
+        .into_inner();
+
+    (package, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::without_ids;
+
+    fn package(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            write_entry(&mut zip, path, content);
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn fixture() -> Vec<u8> {
+        package(&[
+            (MANIFEST_FILE, include_str!("fixtures/qti/imsmanifest.xml")),
+            ("test.xml", include_str!("fixtures/qti/test.xml")),
+            ("items/intro.xml", include_str!("fixtures/qti/items/intro.xml")),
+            ("items/hubs.xml", include_str!("fixtures/qti/items/hubs.xml")),
+            ("items/layer.xml", include_str!("fixtures/qti/items/layer.xml")),
+            ("items/protocols.xml", include_str!("fixtures/qti/items/protocols.xml")),
+            ("items/lan.xml", include_str!("fixtures/qti/items/lan.xml")),
+            ("items/port.xml", include_str!("fixtures/qti/items/port.xml")),
+            ("items/pairs.xml", include_str!("fixtures/qti/items/pairs.xml")),
+            ("items/essay.xml", include_str!("fixtures/qti/items/essay.xml")),
+            ("items/topology.xml", include_str!("fixtures/qti/items/topology.xml")),
+            ("items/empty.xml", include_str!("fixtures/qti/items/empty.xml")),
+        ])
+    }
+
+    #[test]
+    fn imports_fixture() {
+        let quiz = import(&fixture()).unwrap().quiz;
+        assert_eq!(quiz.name, "Networking basics");
+        assert_eq!(quiz.parts.len(), 8);
+
+        match &quiz.parts[0] {
+            Part::Content { title, text, .. } => {
+                assert_eq!(title, "Intro");
+                assert_eq!(text, "This quiz covers LAN basics.");
+            }
+            other => panic!("Expected content, got {:?}", other),
+        }
+
+        // QTI 3.0 item.
+        match &quiz.parts[1] {
+            Part::Interact { text, value, ans: AnswerType::Bool, validation: Some(AnswerValidation::Bool { expected }), .. } => {
+                assert_eq!(text, "Hubs forward frames to every port.");
+                assert_eq!(*value, 2.0);
+                assert!(expected);
+            }
+            other => panic!("Expected true/false question, got {:?}", other),
+        }
+
+        match &quiz.parts[3] {
+            Part::Interact { text, value, ans: AnswerType::Multiple { options, .. }, validation: Some(AnswerValidation::Multiple { expected, .. }), .. } => {
+                assert_eq!(text, "Which protocols are connection oriented?");
+                assert_eq!(*value, 3.0);
+                assert_eq!(options.len(), 3);
+                assert_eq!(expected, &vec!["TCP".to_string(), "SCTP".to_string()]);
+            }
+            other => panic!("Expected multiple choice, got {:?}", other),
+        }
+
+        match &quiz.parts[4] {
+            Part::Interact { validation: Some(AnswerValidation::Exact { case_sensitive, expected, .. }), .. } => {
+                assert!(!case_sensitive);
+                assert_eq!(expected, "Network");
+            }
+            other => panic!("Expected short answer, got {:?}", other),
+        }
+
+        match &quiz.parts[5] {
+            Part::Interact { validation: Some(AnswerValidation::NumberRange { min, max }), .. } => {
+                assert_eq!((*min, *max), (79.5, 80.5));
+            }
+            other => panic!("Expected numerical question, got {:?}", other),
+        }
+
+        match &quiz.parts[6] {
+            Part::Interact { ans: AnswerType::Match(pairs), .. } => {
+                assert_eq!(pairs, &vec![("HTTP".to_string(), "80".to_string()), ("SSH".to_string(), "22".to_string())]);
+            }
+            other => panic!("Expected matching question, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_skipped_items() {
+        let report = import(&fixture()).unwrap().report;
+        assert_eq!((report.converted, report.skipped), (8, 2));
+
+        let issues: Vec<(&str, &str)> = report.issues.iter()
+            .map(|issue| (issue.question.as_str(), issue.message.as_str()))
+            .collect();
+        assert_eq!(issues, vec![
+            ("LAN", "Partially correct answers aren't supported and were dropped."),
+            ("Pairs", "Distractor choices without a pair were dropped."),
+            ("Topology", "Interaction 'hotspotInteraction' isn't supported."),
+            ("Empty", "Item has no body."),
+        ]);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let quiz = import(&fixture()).unwrap().quiz;
+
+        let (exported, report) = export(&quiz.name, &quiz.parts);
+        assert_eq!(report.converted, quiz.parts.len());
+        assert!(report.issues.is_empty(), "Unexpected issues: {:?}", report.issues);
+
+        let reimported = import(&exported).unwrap();
+        assert_eq!(reimported.quiz.name, quiz.name);
+        assert!(reimported.report.issues.is_empty(), "Unexpected issues: {:?}", reimported.report.issues);
+        assert_eq!(without_ids(&reimported.quiz.parts), without_ids(&quiz.parts));
+    }
+
+    #[test]
+    fn reports_unsupported_exports() {
+        let parts = vec![
+            interact("Fill in the blank.".to_string(), 1.0, AnswerType::FillIn, None, false),
+            interact("Name a LAN technology.".to_string(), 1.0, AnswerType::Short, Some(AnswerValidation::Multiple {
+                case_sensitive: false,
+                expected: vec!["Ethernet".to_string(), "Wi*".to_string()],
+                normalize: vec![],
+            }), false),
+        ];
+
+        let (_, report) = export("Unsupported", &parts);
+        assert_eq!((report.converted, report.skipped), (1, 1));
+        assert_eq!(report.issues[0].message, "Fill-in questions aren't supported by the format.");
+        assert_eq!(report.issues[1].message, "Wildcard answers can't be represented in QTI, item must be graded manually.");
+    }
+
+    #[test]
+    fn problems_point_at_source() {
+        let manifest = include_str!("fixtures/qti/imsmanifest.xml");
+        let problem = import(&package(&[
+            (MANIFEST_FILE, manifest),
+            ("test.xml", include_str!("fixtures/qti/test.xml")),
+            ("items/intro.xml", "<assessmentItem>\n  <itemBody>\n    <p>Unclosed</div>\n"),
+        ])).unwrap_err();
+        assert_eq!(problem.body["file"], "items/intro.xml");
+        assert_eq!((problem.body["line"].as_u64(), problem.body["column"].as_u64()), (Some(3), Some(16)));
+
+        let problem = import(&package(&[("test.xml", "")])).unwrap_err();
+        assert!(problem.detail.unwrap().starts_with("Unable to read 'imsmanifest.xml' from package"));
+
+        let problem = import(&package(&[(MANIFEST_FILE, "<manifest><resources/></manifest>")])).unwrap_err();
+        assert_eq!(problem.detail.as_deref(), Some("Content package doesn't contain any QTI items."));
+    }
+
+    fn item_manifest(hrefs: &[String]) -> String {
+        let resources: String = hrefs.iter()
+            .map(|href| format!("<resource type=\"imsqti_item_xmlv2p1\" href=\"{}\"/>", href))
+            .collect();
+        format!("<manifest><resources>{}</resources></manifest>", resources)
+    }
+
+    #[test]
+    fn imports_repeated_items_once() {
+        let hrefs = vec!["items/lan.xml".to_string(), "./items/lan.xml".to_string(), "items/lan.xml".to_string()];
+        let quiz = import(&package(&[
+            (MANIFEST_FILE, &item_manifest(&hrefs)),
+            ("items/lan.xml", include_str!("fixtures/qti/items/lan.xml")),
+        ])).unwrap().quiz;
+
+        assert_eq!(quiz.parts.len(), 1);
+    }
+
+    #[test]
+    fn limits_package_size() {
+        let hrefs: Vec<String> = (0..=MAX_ITEMS).map(|i| format!("items/{}.xml", i)).collect();
+        let problem = import(&package(&[(MANIFEST_FILE, &item_manifest(&hrefs))])).unwrap_err();
+        assert_eq!(problem.detail, Some(format!("Content package contains more than {} items.", MAX_ITEMS)));
+
+        let padding = " ".repeat(MAX_ENTRY_SIZE as usize / 2);
+        let item = format!("{}{}", include_str!("fixtures/qti/items/lan.xml"), padding);
+        let hrefs: Vec<String> = (0..(MAX_PACKAGE_SIZE / MAX_ENTRY_SIZE * 2)).map(|i| format!("items/{}.xml", i)).collect();
+        let mut files = vec![(MANIFEST_FILE, item_manifest(&hrefs))];
+        files.extend(hrefs.iter().map(|href| (href.as_str(), item.clone())));
+        let files: Vec<(&str, &str)> = files.iter().map(|(path, content)| (*path, content.as_str())).collect();
+
+        let problem = import(&package(&files)).unwrap_err();
+        assert_eq!(problem.detail.as_deref(), Some("Content package is too large."));
+    }
+}
@@ -0,0 +1,267 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::data::Capability;
+use crate::error::Problem;
+use crate::id::UserId;
+use crate::store::{CourseStore, Storage, StoreResult, UserStore};
+
+pub static COURSE_COLLECTION_NAME: &'static str = "courses";
+pub static ENROLLMENT_COLLECTION_NAME: &'static str = "enrollments";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+pub enum CourseRole {
+    Student,
+    TeachingAssistant,
+    Teacher,
+}
+
+impl CourseRole {
+    /// Capabilities a course member has on every quiz that belongs to the course.
+    pub fn quiz_capabilities(self) -> Vec<Capability> {
+        match self {
+            CourseRole::Student => vec![],
+            CourseRole::TeachingAssistant => vec![
+                Capability::Grade,
+                Capability::ViewReports,
+            ],
+            CourseRole::Teacher => vec![
+                Capability::EditContent,
+                Capability::Grade,
+                Capability::ViewReports,
+                Capability::ManageParticipants,
+            ],
+        }
+    }
+}
+
+impl std::fmt::Display for CourseRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CourseRole::Student => write!(f, "student"),
+            CourseRole::TeachingAssistant => write!(f, "ta"),
+            CourseRole::Teacher => write!(f, "teacher"),
+        }
+    }
+}
+
+impl std::str::FromStr for CourseRole {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "student" => Ok(CourseRole::Student),
+            "ta" | "teaching_assistant" | "assistant" => Ok(CourseRole::TeachingAssistant),
+            "teacher" => Ok(CourseRole::Teacher),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Course {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    #[serde(default = "Utc::now")]
+    pub created: DateTime<Utc>,
+}
+
+/// Membership of user with `user` ID in course with `course` ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Enrollment {
+    pub course: Uuid,
+    pub user: UserId,
+    pub role: CourseRole,
+    #[serde(default = "Utc::now")]
+    pub enrolled: DateTime<Utc>,
+}
+
+impl Enrollment {
+    pub fn new(course: Uuid, user: UserId, role: CourseRole) -> Enrollment {
+        Enrollment {
+            course,
+            user,
+            role,
+            enrolled: Utc::now(),
+        }
+    }
+}
+
+/// Finds enrollment of user in a course. Resources which don't belong to a course don't have any
+/// enrollments.
+pub async fn find_enrollment(store: &Storage, course: Option<Uuid>, user: UserId) -> Result<Option<Enrollment>, Problem> {
+    match course {
+        Some(course) => Ok(store.find_enrollment(course, user).await?),
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterEntry {
+    pub line: u64,
+    pub username: String,
+    pub role: CourseRole,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterIssue {
+    pub line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    pub reason: String,
+}
+
+/// Parses a CSV roster with `username[,role]` rows. Role defaults to student and an optional
+/// header row is skipped. Rows which repeat a username are reported, the first one is kept.
+pub fn parse_roster(roster: &str) -> (Vec<RosterEntry>, Vec<RosterIssue>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(roster.as_bytes());
+
+    let mut entries = vec![];
+    let mut issues = vec![];
+
+    for (i, record) in reader.records().enumerate() {
+        let line = i as u64 + 1;
+        let record = match record {
+            Ok(it) => it,
+            Err(e) => {
+                issues.push(RosterIssue {
+                    line: e.position().map(|p| p.line()).unwrap_or(line),
+                    username: None,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(line);
+
+        let username = record.get(0).unwrap_or("");
+        if username.is_empty() || (i == 0 && username.eq_ignore_ascii_case("username")) {
+            continue;
+        }
+
+        let role = match record.get(1) {
+            Some(role) if !role.is_empty() => match role.parse::<CourseRole>() {
+                Ok(it) => it,
+                Err(_) => {
+                    issues.push(RosterIssue {
+                        line,
+                        username: Some(username.to_string()),
+                        reason: format!("Unknown course role '{}'.", role),
+                    });
+                    continue;
+                }
+            },
+            _ => CourseRole::Student,
+        };
+
+        if let Some(first) = entries.iter().find(|entry| entry.username == username) {
+            issues.push(RosterIssue {
+                line,
+                username: Some(username.to_string()),
+                reason: format!("User is already listed on line {}.", first.line),
+            });
+            continue;
+        }
+
+        entries.push(RosterEntry {
+            line,
+            username: username.to_string(),
+            role,
+        });
+    }
+
+    (entries, issues)
+}
+
+/// Enrolls users listed in a CSV roster. Returns enrolled rows and rows which couldn't be enrolled.
+pub async fn enroll_roster(store: &Storage, course: Uuid, roster: &str) -> StoreResult<(Vec<RosterEntry>, Vec<RosterIssue>)> {
+    let (entries, mut failed) = parse_roster(roster);
+
+    let mut enrolled = vec![];
+    for entry in entries {
+        let user = match store.find_user_by_username(&entry.username).await? {
+            Some(it) => it,
+            None => {
+                failed.push(RosterIssue {
+                    line: entry.line,
+                    username: Some(entry.username),
+                    reason: "User doesn't exist.".to_string(),
+                });
+                continue;
+            }
+        };
+
+        store.save_enrollment(&Enrollment::new(course, user.id, entry.role)).await?;
+        enrolled.push(entry);
+    }
+
+    failed.sort_by_key(|issue| issue.line);
+    Ok((enrolled, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::store::MemoryStore;
+    use crate::user::User;
+
+    fn lines(issues: &[RosterIssue]) -> Vec<u64> {
+        issues.iter().map(|issue| issue.line).collect()
+    }
+
+    #[test]
+    fn header_is_skipped() {
+        let (entries, issues) = parse_roster("Username,Role\nalice,teacher\nbob\n");
+        assert!(issues.is_empty());
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].line, entries[0].username.as_str(), entries[0].role), (2, "alice", CourseRole::Teacher));
+        assert_eq!((entries[1].line, entries[1].username.as_str(), entries[1].role), (3, "bob", CourseRole::Student));
+
+        // Only the first row can be a header.
+        let (entries, _) = parse_roster("alice\nusername\n");
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn bad_roles_are_reported() {
+        let (entries, issues) = parse_roster("alice, TA \nbob,dean\n\ncarol,\n");
+        assert_eq!(entries.iter().map(|e| e.role).collect::<Vec<_>>(), vec![CourseRole::TeachingAssistant, CourseRole::Student]);
+        assert_eq!(lines(&issues), vec![2]);
+        assert_eq!(issues[0].username.as_deref(), Some("bob"));
+        assert!(issues[0].reason.contains("dean"));
+    }
+
+    #[test]
+    fn duplicate_rows_are_reported() {
+        let (entries, issues) = parse_roster("alice,student\nbob\nalice,teacher\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].role, CourseRole::Student);
+        assert_eq!(lines(&issues), vec![3]);
+        assert!(issues[0].reason.contains("line 1"));
+    }
+
+    #[rocket::async_test]
+    async fn unknown_users_are_reported() {
+        let store: Storage = Arc::new(MemoryStore::new());
+        let alice = User::new("alice".to_string(), "correct horse".to_string());
+        store.insert_user(&alice).await.unwrap();
+        let course = Uuid::new_v4();
+
+        let (enrolled, failed) = enroll_roster(&store, course, "nobody\nalice,ta\ndean,dean\n").await.unwrap();
+        assert_eq!(enrolled.len(), 1);
+        assert_eq!(lines(&failed), vec![1, 3]);
+        assert_eq!(failed[0].reason, "User doesn't exist.");
+
+        let enrollment = store.find_enrollment(course, alice.id).await.unwrap().unwrap();
+        assert_eq!(enrollment.role, CourseRole::TeachingAssistant);
+    }
+}
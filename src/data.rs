@@ -5,6 +5,8 @@ use chrono::{DateTime, Utc};
 use uuid::Uuid;
 use serde::Deserialize;
 
+use crate::id::{PartId, QuizId, UserId};
+
 fn true_bool() -> bool {
     true
 }
@@ -33,6 +35,38 @@ pub enum AnswerType {
     },
 }
 
+/// Transformation applied to text answers (and expected values) before they're validated, in
+/// order they're listed in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Normalizer {
+    Trim,
+    /// Replaces runs of whitespace with a single space.
+    CollapseWhitespace,
+    /// Unicode canonical composition.
+    Nfc,
+    /// Unicode compatibility composition, e.g. `ﬁ` becomes `fi` and `²` becomes `2`.
+    Nfkc,
+    /// Removes diacritics, e.g. `čćđšž` becomes `ccdsz`.
+    FoldAccents,
+    StripPunctuation,
+    /// Rewrites numbers to use a decimal dot and no digit grouping, e.g. `1.000,5` becomes `1000.5`.
+    CanonicalNumbers,
+}
+
+/// Edit distance used by fuzzy validation.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum FuzzyMetric {
+    Levenshtein,
+    /// Like Levenshtein, but swapping two adjacent characters counts as a single edit.
+    DamerauLevenshtein,
+}
+
+impl Default for FuzzyMetric {
+    fn default() -> Self {
+        FuzzyMetric::DamerauLevenshtein
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AnswerValidation {
     Bool {
@@ -42,6 +76,8 @@ pub enum AnswerValidation {
         #[serde(default)]
         case_sensitive: bool,
         expected: String,
+        #[serde(default)]
+        normalize: Vec<Normalizer>,
     },
     NumberRange {
         min: f64,
@@ -51,11 +87,91 @@ pub enum AnswerValidation {
         #[serde(default)]
         case_sensitive: bool,
         expr: String,
+        /// Requires the whole answer to match instead of any part of it.
+        #[serde(default = "true_bool")]
+        full_match: bool,
+        /// Makes `^` and `$` match at the beginning and end of each line.
+        #[serde(default)]
+        multiline: bool,
+        /// Trims the answer and collapses all whitespace into single spaces before matching.
+        #[serde(default)]
+        normalize_whitespace: bool,
+        /// Patterns tried when `expr` doesn't match. The best matching one awards its score.
+        #[serde(default)]
+        alternatives: Vec<RegexAlternative>,
+        /// Normalizers applied to the answer, patterns are used as written.
+        #[serde(default)]
+        normalize: Vec<Normalizer>,
     },
     Multiple {
         #[serde(default)]
         case_sensitive: bool,
         expected: Vec<String>,
+        #[serde(default)]
+        normalize: Vec<Normalizer>,
+    },
+    /// Accepts answers close to any of the expected ones. Answers have to satisfy every
+    /// threshold which is set.
+    Fuzzy {
+        #[serde(default)]
+        case_sensitive: bool,
+        expected: Vec<String>,
+        #[serde(default)]
+        metric: FuzzyMetric,
+        /// Largest accepted number of edits.
+        #[serde(default)]
+        max_distance: Option<usize>,
+        /// Smallest accepted similarity, from 0 (completely different) to 1 (equal).
+        #[serde(default)]
+        min_similarity: Option<f64>,
+        /// Awards similarity of accepted answers as a fraction of points instead of full points.
+        #[serde(default)]
+        similarity_credit: bool,
+        #[serde(default)]
+        normalize: Vec<Normalizer>,
+    },
+    /// Accepts IPv4 or IPv6 addresses equal to any of the expected ones, e.g. `2001:db8::1` is
+    /// equal to `2001:0db8:0:0:0:0:0:1`.
+    IpAddress {
+        expected: Vec<String>,
+    },
+    /// Accepts networks equal to any of the expected ones, with prefix written as length or a
+    /// dotted mask.
+    Cidr {
+        expected: Vec<String>,
+        /// Requires host bits to match too, instead of only comparing network addresses.
+        #[serde(default)]
+        match_host: bool,
+    },
+    /// Accepts IPv4 subnet masks equal to any of the expected ones, e.g. `255.255.255.0` is
+    /// equal to `/24`.
+    SubnetMask {
+        expected: Vec<String>,
+    },
+    /// Accepts MAC addresses equal to any of the expected ones in any common notation.
+    MacAddress {
+        expected: Vec<String>,
+    },
+    /// Accepts lists of ports and port ranges (e.g. `80, 443, 8000-8080`) which cover the same
+    /// ports as any of the expected ones.
+    Ports {
+        expected: Vec<String>,
+    },
+    /// Accepts answers accepted by every validation, awarding the lowest score.
+    AllOf {
+        validations: Vec<AnswerValidation>,
+    },
+    /// Accepts answers accepted by any validation, awarding the highest score.
+    AnyOf {
+        validations: Vec<AnswerValidation>,
+    },
+    /// Awards score of the first branch whose validation fully accepts the answer.
+    FirstMatch {
+        branches: Vec<ScoredValidation>,
+    },
+    /// Awards weighted average of scores of all validations.
+    Weighted {
+        branches: Vec<WeightedValidation>,
     },
     External {
         // for running external, locally installed validation programs/scripts.
@@ -64,17 +180,38 @@ pub enum AnswerValidation {
     },
 }
 
+/// Alternative regex validation pattern which awards a fraction (`[0, 1]`) of points.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexAlternative {
+    pub expr: String,
+    pub score: f32,
+}
+
+/// Branch of `FirstMatch` validation which awards a fraction (`[0, 1]`) of points.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredValidation {
+    pub validation: AnswerValidation,
+    pub score: f32,
+}
+
+/// Branch of `Weighted` validation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedValidation {
+    pub validation: AnswerValidation,
+    pub weight: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Part {
     Content {
-        #[serde(default = "Uuid::new_v4")]
-        id: Uuid,
+        #[serde(default = "PartId::new")]
+        id: PartId,
         title: String,
         text: String,
     },
     Interact {
-        #[serde(default = "Uuid::new_v4")]
-        id: Uuid,
+        #[serde(default = "PartId::new")]
+        id: PartId,
         text: String,
         ans: AnswerType,
 
@@ -97,23 +234,75 @@ pub enum Answer {
     Multiple(Vec<i32>),
 }
 
+/// Score awarded for an answer. Grades without `graded_by` were awarded automatically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Grade {
+    pub score: f32,
+    #[serde(default)]
+    pub graded_by: Option<UserId>,
+    #[serde(default = "Utc::now")]
+    pub graded_on: DateTime<Utc>,
+}
+
+/// Attempt of a single participant (user with `id`) at solving quiz with `quiz` ID.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParticipantInfo {
-    pub id: Uuid,
+    pub id: UserId,
+    pub quiz: QuizId,
     #[serde(default = "Utc::now")]
     pub started_on: DateTime<Utc>,
     #[serde(default)]
-    pub answers: HashMap<Uuid, Answer>,
+    pub finished_on: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub answers: HashMap<PartId, Answer>,
+    #[serde(default)]
+    pub grades: HashMap<PartId, Grade>,
+    /// Incremented by every change so that updates based on a stale copy can be detected.
+    #[serde(default)]
+    pub version: i64,
+}
+
+impl ParticipantInfo {
+    pub fn new(user: UserId, quiz: QuizId) -> ParticipantInfo {
+        ParticipantInfo {
+            id: user,
+            quiz,
+            started_on: Utc::now(),
+            finished_on: None,
+            answers: HashMap::new(),
+            grades: HashMap::new(),
+            version: 0,
+        }
+    }
+
+    pub fn score(&self) -> f32 {
+        self.grades.values().map(|grade| grade.score).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Capability {
+    EditContent,
+    Grade,
+    ViewReports,
+    ManageParticipants,
+}
+
+/// User other than the author who helps with running a quiz, e.g. co-author or teaching assistant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collaborator {
+    pub user: UserId,
+    pub capabilities: Vec<Capability>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Quiz {
-    #[serde(default = "Uuid::new_v4")]
-    pub id: Uuid,
+    #[serde(default = "QuizId::new")]
+    pub id: QuizId,
     pub name: String,
     #[serde(default)]
     pub desc: String,
-    pub author: Uuid,
+    pub author: UserId,
     #[serde(default = "Utc::now")]
     pub created: DateTime<Utc>,
     #[serde(default)]
@@ -136,6 +325,68 @@ pub struct Quiz {
     pub close_on: Option<DateTime<Utc>>,
     #[serde(default)]
     pub begin_buffer: Option<Duration>,
+    /// Course the quiz belongs to. Students enrolled in the course are eligible to participate
+    /// and course staff gets capabilities based on their course role.
+    #[serde(default)]
+    pub course: Option<Uuid>,
+    /// Users invited to participate in a non-public quiz in addition to course students.
+    #[serde(default)]
+    pub participants: Vec<UserId>,
     #[serde(default)]
-    pub participants: Vec<String>,
+    pub collaborators: Vec<Collaborator>,
+}
+
+impl Quiz {
+    /// Indicates whether current time is within quiz `open_on` and `close_on` window.
+    pub fn is_open(&self) -> bool {
+        let now = Utc::now();
+        self.open_on.map_or(true, |open| open <= now) &&
+            self.close_on.map_or(true, |close| now <= close)
+    }
+
+    pub fn part(&self, id: PartId) -> Option<&Part> {
+        self.parts.iter().find(|part| part.id() == id)
+    }
+
+    pub fn collaborator(&self, user: UserId) -> Option<&Collaborator> {
+        self.collaborators.iter().find(|c| c.user == user)
+    }
+
+    /// Adds a collaborator, replacing capabilities they had before.
+    pub fn set_collaborator(&mut self, collaborator: Collaborator) {
+        self.collaborators.retain(|c| c.user != collaborator.user);
+        self.collaborators.push(collaborator);
+    }
+
+    /// Removes validations from all parts so quiz can be shown without its expected answers.
+    pub fn hide_answers(&mut self) {
+        for part in &mut self.parts {
+            if let Part::Interact { validation, .. } = part {
+                *validation = None;
+            }
+        }
+    }
+
+    /// Replaces content with that of `update`. Ownership, course, participants and collaborators
+    /// are kept.
+    pub fn replace_content(&mut self, update: &Quiz) {
+        *self = Quiz {
+            id: self.id,
+            author: self.author,
+            created: self.created,
+            course: self.course,
+            participants: std::mem::take(&mut self.participants),
+            collaborators: std::mem::take(&mut self.collaborators),
+            ..update.clone()
+        };
+    }
+}
+
+impl Part {
+    pub fn id(&self) -> PartId {
+        match self {
+            Part::Content { id, .. } => *id,
+            Part::Interact { id, .. } => *id,
+        }
+    }
 }
@@ -10,6 +10,8 @@ use serde::export::Formatter;
 use serde::Serialize;
 use serde_json::{Map, Value};
 
+use crate::logging::RequestId;
+
 // https://tools.ietf.org/html/rfc7807
 
 #[derive(Debug, Clone, PartialEq)]
@@ -104,8 +106,9 @@ impl std::fmt::Display for Problem {
 impl std::error::Error for Problem {}
 
 impl<'r> Responder<'r, 'static> for Problem {
-    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
         let mut body = self.body.clone();
+        let instance_uri = self.instance_uri.unwrap_or_else(|| RequestId::of(req).urn());
 
         // Following are required by rfc7807
         body.insert(String::from("type"), serde_json::Value::from(self.type_uri));
@@ -116,9 +119,7 @@ impl<'r> Responder<'r, 'static> for Problem {
             body.insert(String::from("detail"), serde_json::Value::from(self.detail.unwrap()));
         }
         body.insert(String::from("status"), serde_json::Value::from(self.status.code));
-        if self.instance_uri.is_some() {
-            body.insert(String::from("instance"), serde_json::Value::from(self.instance_uri.unwrap()));
-        }
+        body.insert(String::from("instance"), serde_json::Value::from(instance_uri));
 
         let body_string = serde_json::to_string(&body)
             .expect("Problem body must be convertible to a String.");
@@ -237,6 +238,40 @@ impl std::convert::From<serde_json::Error> for Problem {
     }
 }
 
+impl std::convert::From<crate::store::StoreError> for Problem {
+    fn from(e: crate::store::StoreError) -> Self {
+        use crate::store::StoreError;
+
+        match e {
+            StoreError::Duplicate(key) => Problem::new_untyped(
+                Status::Conflict,
+                "Resource already exists.",
+            )
+                .detail("Another resource has the same unique key.")
+                .insert_serialized("key", key)
+                .clone(),
+            StoreError::LastAdmin => Problem::new_untyped(
+                Status::Conflict,
+                "Last admin can't be demoted.",
+            ),
+            StoreError::Mongo(e) => Problem::from(e),
+            StoreError::Embedded(e) => {
+                error!("Embedded database error: {}", e);
+                Problem::new_untyped(
+                    Status::InternalServerError,
+                    "Embedded database failed while processing request.",
+                )
+            }
+            StoreError::BsonDecode(e) => Problem::from(e),
+            StoreError::BsonEncode(_) => Problem::new_untyped(
+                Status::InternalServerError,
+                "An error occurred while processing BSON data.",
+            ),
+            StoreError::Json(e) => Problem::from(e),
+        }
+    }
+}
+
 impl std::convert::From<jsonwebtoken::errors::Error> for Problem {
     fn from(e: jsonwebtoken::errors::Error) -> Self {
         use jsonwebtoken::errors::ErrorKind;
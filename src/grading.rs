@@ -0,0 +1,336 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::Utc;
+
+use crate::data::{Answer, AnswerType, AnswerValidation, FuzzyMetric, Grade, Normalizer, Part, ParticipantInfo, Quiz};
+use crate::id::PartId;
+use crate::metrics;
+use crate::network;
+use crate::normalize;
+use crate::pattern;
+use crate::validation::validation_name;
+
+/// Returns textual representation of an answer which is used by text based validations.
+fn answer_text(ans: &AnswerType, answer: &Answer) -> Option<String> {
+    match (ans, answer) {
+        (_, Answer::Bool(value)) => Some(value.to_string()),
+        (_, Answer::Number(value)) => Some(value.to_string()),
+        (_, Answer::Short(value)) => Some(value.clone()),
+        (_, Answer::Long(value)) => Some(value.clone()),
+        (AnswerType::Single { options, .. }, Answer::Single(i)) => options.get(*i as usize).cloned(),
+        _ => None,
+    }
+}
+
+/// Returns texts of all options chosen by a multiple choice answer. Options chosen more than once
+/// are only counted once.
+fn chosen_options(ans: &AnswerType, answer: &Answer) -> Option<Vec<String>> {
+    match (ans, answer) {
+        (AnswerType::Multiple { options, .. }, Answer::Multiple(chosen)) => Some(
+            chosen.iter()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter_map(|i| options.get(*i as usize).cloned())
+                .collect()
+        ),
+        _ => None,
+    }
+}
+
+#[inline]
+fn text_eq(a: &str, b: &str, case_sensitive: bool, normalizers: &[Normalizer]) -> bool {
+    let (a, b) = (normalize::apply(normalizers, a), normalize::apply(normalizers, b));
+    if case_sensitive {
+        a == b
+    } else {
+        a.to_lowercase() == b.to_lowercase()
+    }
+}
+
+/// Longer answers are too expensive to compare fuzzily and have to match exactly.
+const MAX_FUZZY_LENGTH: usize = 256;
+
+/// Scores a text answer by its similarity to the closest expected answer.
+fn fuzzy_score(validation: &AnswerValidation, text: &str) -> f32 {
+    let (case_sensitive, expected, metric, max_distance, min_similarity, similarity_credit, normalizers) = match validation {
+        AnswerValidation::Fuzzy { case_sensitive, expected, metric, max_distance, min_similarity, similarity_credit, normalize } =>
+            (*case_sensitive, expected, *metric, *max_distance, *min_similarity, *similarity_credit, normalize),
+        _ => return 0.0,
+    };
+
+    let prepare = |text: &str| {
+        let text = normalize::apply(normalizers, text.trim());
+        if case_sensitive { text.into_owned() } else { text.to_lowercase() }
+    };
+    let answer = prepare(text);
+
+    expected.iter()
+        .map(|expected| {
+            let expected = prepare(expected);
+            let length = answer.chars().count().max(expected.chars().count());
+            if answer == expected {
+                return 1.0;
+            }
+            if length > MAX_FUZZY_LENGTH {
+                return 0.0;
+            }
+
+            let distance = match metric {
+                FuzzyMetric::Levenshtein => strsim::levenshtein(&answer, &expected),
+                FuzzyMetric::DamerauLevenshtein => strsim::damerau_levenshtein(&answer, &expected),
+            };
+            let similarity = 1.0 - distance as f64 / length as f64;
+
+            let accepted = max_distance.map_or(true, |max| distance <= max) &&
+                min_similarity.map_or(true, |min| similarity >= min);
+            match (accepted, similarity_credit) {
+                (false, _) => 0.0,
+                (true, false) => 1.0,
+                (true, true) => similarity as f32,
+            }
+        })
+        .fold(0.0, f32::max)
+}
+
+#[inline]
+fn fraction(correct: bool) -> f32 {
+    if correct { 1.0 } else { 0.0 }
+}
+
+/// Validates an answer and returns fraction of points (in range `[0, 1]`) it deserves.
+///
+/// `None` is returned for answers which can't be validated automatically and require manual
+/// grading.
+pub fn validate(ans: &AnswerType, validation: &AnswerValidation, answer: &Answer) -> Option<f32> {
+    match validation {
+        AnswerValidation::Bool { expected } => match answer {
+            Answer::Bool(value) => Some(fraction(value == expected)),
+            _ => Some(0.0),
+        },
+        AnswerValidation::Exact { case_sensitive, expected, normalize } => match answer_text(ans, answer) {
+            Some(text) => Some(fraction(text_eq(text.trim(), expected.trim(), *case_sensitive, normalize))),
+            None => Some(0.0),
+        },
+        AnswerValidation::NumberRange { min, max } => {
+            let value = match answer {
+                Answer::Number(value) => Some(*value),
+                _ => answer_text(ans, answer).and_then(|text| {
+                    normalize::apply(&[Normalizer::Trim, Normalizer::CanonicalNumbers], &text).parse::<f64>().ok()
+                }),
+            };
+            match value {
+                Some(value) => Some(fraction(*min <= value && value <= *max)),
+                None => Some(0.0),
+            }
+        }
+        AnswerValidation::Regex { .. } => match answer_text(ans, answer) {
+            Some(text) => pattern::score(validation, &text),
+            None => Some(0.0),
+        },
+        AnswerValidation::Multiple { case_sensitive, expected, normalize } => {
+            if expected.is_empty() {
+                return failed(validation);
+            }
+
+            if let Some(chosen) = chosen_options(ans, answer) {
+                // Each correctly chosen option is worth a part of the points, while each wrongly
+                // chosen one takes the same amount away.
+                let correct = chosen.iter()
+                    .filter(|c| expected.iter().any(|e| text_eq(c, e, *case_sensitive, normalize)))
+                    .count() as f32;
+                let wrong = chosen.len() as f32 - correct;
+                return Some(((correct - wrong) / expected.len() as f32).max(0.0));
+            }
+
+            if let Answer::FillIn(blanks) = answer {
+                let correct = blanks.iter()
+                    .zip(expected.iter())
+                    .filter(|(b, e)| text_eq(b.trim(), e.trim(), *case_sensitive, normalize))
+                    .count() as f32;
+                return Some(correct / expected.len() as f32);
+            }
+
+            match answer_text(ans, answer) {
+                Some(text) => Some(fraction(
+                    expected.iter().any(|e| text_eq(text.trim(), e.trim(), *case_sensitive, normalize))
+                )),
+                None => Some(0.0),
+            }
+        }
+        AnswerValidation::Fuzzy { .. } => match answer_text(ans, answer) {
+            Some(text) => Some(fuzzy_score(validation, &text)),
+            None => Some(0.0),
+        },
+        AnswerValidation::IpAddress { .. } |
+        AnswerValidation::Cidr { .. } |
+        AnswerValidation::SubnetMask { .. } |
+        AnswerValidation::MacAddress { .. } |
+        AnswerValidation::Ports { .. } => match answer_text(ans, answer) {
+            Some(text) => match network::matches(validation, &text) {
+                Some(matched) => Some(fraction(matched)),
+                None => failed(validation),
+            },
+            None => Some(0.0),
+        },
+        AnswerValidation::AllOf { validations } => {
+            let scores: Option<Vec<f32>> = validations.iter()
+                .map(|validation| validate(ans, validation, answer))
+                .collect();
+            Some(scores?.into_iter().fold(1.0, f32::min))
+        }
+        AnswerValidation::AnyOf { validations } => {
+            let scores: Vec<Option<f32>> = validations.iter()
+                .map(|validation| validate(ans, validation, answer))
+                .collect();
+            // A single fully accepting validation is enough, even if others need manual grading.
+            if scores.iter().any(|score| score.map_or(false, |score| score >= 1.0)) {
+                return Some(1.0);
+            }
+            let scores: Option<Vec<f32>> = scores.into_iter().collect();
+            Some(scores?.into_iter().fold(0.0, f32::max))
+        }
+        AnswerValidation::FirstMatch { branches } => {
+            for branch in branches {
+                if validate(ans, &branch.validation, answer)? >= 1.0 {
+                    return Some(branch.score);
+                }
+            }
+            Some(0.0)
+        }
+        AnswerValidation::Weighted { branches } => {
+            let total: f32 = branches.iter().map(|branch| branch.weight).sum();
+            if total <= 0.0 {
+                return failed(validation);
+            }
+
+            let mut score = 0.0;
+            for branch in branches {
+                score += branch.weight * validate(ans, &branch.validation, answer)?;
+            }
+            Some(score / total)
+        }
+        // External validators aren't run yet.
+        AnswerValidation::External { .. } => failed(validation),
+    }
+}
+
+/// Validates an answer to a match question against pairs it was created with.
+fn validate_match(pairs: &Vec<(String, String)>, answer: &Answer) -> Option<f32> {
+    if pairs.is_empty() {
+        return None;
+    }
+
+    match answer {
+        Answer::FillIn(matched) => {
+            let correct = pairs.iter()
+                .zip(matched.iter())
+                .filter(|((_, expected), given)| text_eq(expected.trim(), given.trim(), true, &[]))
+                .count() as f32;
+            Some(correct / pairs.len() as f32)
+        }
+        _ => Some(0.0),
+    }
+}
+
+/// Records a validation which couldn't be evaluated, its answer has to be graded manually.
+fn failed(validation: &AnswerValidation) -> Option<f32> {
+    metrics::VALIDATION_FAILURES.with_label_values(&[validation_name(validation)]).inc();
+    None
+}
+
+/// Computes score for an answer to a quiz part. `None` means part requires manual grading.
+pub fn grade_part(part: &Part, answer: &Answer) -> Option<f32> {
+    let (ans, value, validation, partial) = match part {
+        Part::Interact { ans, value, validation, partial, .. } => (ans, value, validation, partial),
+        Part::Content { .. } => return None,
+    };
+
+    let result = match (validation, ans) {
+        (Some(validation), _) => {
+            let _timer = metrics::VALIDATION_DURATION
+                .with_label_values(&[validation_name(validation)])
+                .start_timer();
+            validate(ans, validation, answer)
+        }
+        (None, AnswerType::Match(pairs)) => validate_match(pairs, answer),
+        (None, _) => None,
+    }?;
+
+    let result = if *partial || result >= 1.0 { result } else { 0.0 };
+
+    Some(value * result.max(0.0).min(1.0))
+}
+
+/// Automatically grades all answers of an attempt which can be validated.
+///
+/// Grades previously awarded manually are kept.
+pub fn grade_attempt(quiz: &Quiz, attempt: &ParticipantInfo) -> HashMap<PartId, Grade> {
+    let mut grades: HashMap<PartId, Grade> = attempt.grades.iter()
+        .filter(|(_, grade)| grade.graded_by.is_some())
+        .map(|(id, grade)| (*id, grade.clone()))
+        .collect();
+
+    for (part_id, answer) in &attempt.answers {
+        if grades.contains_key(part_id) {
+            continue;
+        }
+
+        let part = match quiz.part(*part_id) {
+            Some(it) => it,
+            None => continue,
+        };
+
+        if let Some(score) = grade_part(part, answer) {
+            grades.insert(*part_id, Grade {
+                score,
+                graded_by: None,
+                graded_on: Utc::now(),
+            });
+        }
+    }
+
+    grades
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multiple_choice(partial: bool) -> Part {
+        Part::Interact {
+            id: PartId::new(),
+            text: "Which devices forward frames by MAC address?".to_string(),
+            ans: AnswerType::Multiple {
+                options: vec!["Switch".to_string(), "Bridge".to_string(), "Hub".to_string()],
+                shuffle: false,
+            },
+            time_limit: None,
+            value: 2.0,
+            validation: Some(AnswerValidation::Multiple {
+                case_sensitive: false,
+                expected: vec!["Switch".to_string(), "Bridge".to_string()],
+                normalize: vec![],
+            }),
+            partial,
+        }
+    }
+
+    #[test]
+    fn grades_multiple_choice() {
+        let part = multiple_choice(true);
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![0, 1])), Some(2.0));
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![0])), Some(1.0));
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![0, 2])), Some(0.0));
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![0, 7])), Some(1.0));
+    }
+
+    #[test]
+    fn repeated_options_count_once() {
+        let part = multiple_choice(true);
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![0, 0])), Some(1.0));
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![1, 0, 1])), Some(2.0));
+
+        let part = multiple_choice(false);
+        assert_eq!(grade_part(&part, &Answer::Multiple(vec![0, 0])), Some(0.0));
+    }
+}
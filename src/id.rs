@@ -0,0 +1,120 @@
+//! Typed identifiers of users, quizzes and quiz parts.
+//!
+//! IDs are stored as hyphenated UUID strings everywhere, regardless of whether the serializer is
+//! human readable. Filters must use `to_bson` to match the same representation.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use bson::Bson;
+use rocket::http::RawStr;
+use rocket::request::FromParam;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use uuid::Uuid;
+
+/// Canonical BSON representation of a UUID.
+#[inline]
+pub fn uuid_bson(id: Uuid) -> Bson {
+    Bson::String(id.to_hyphenated().to_string())
+}
+
+macro_rules! typed_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(pub Uuid);
+
+        impl $name {
+            /// Generates a new random ID.
+            pub fn new() -> $name {
+                $name(Uuid::new_v4())
+            }
+
+            pub fn nil() -> $name {
+                $name(Uuid::nil())
+            }
+
+            pub fn as_bytes(&self) -> &[u8; 16] {
+                self.0.as_bytes()
+            }
+
+            pub fn to_bson(&self) -> Bson {
+                uuid_bson(self.0)
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::new()
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                self.0.to_hyphenated().fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = uuid::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Uuid::parse_str(s).map($name)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                value.parse().map_err(D::Error::custom)
+            }
+        }
+
+        impl From<Uuid> for $name {
+            fn from(id: Uuid) -> Self {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for Uuid {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        /// Paths with malformed IDs are forwarded, so they end up as 404 unless another route
+        /// matches them.
+        impl<'a> FromParam<'a> for $name {
+            type Error = &'a RawStr;
+
+            fn from_param(param: &'a RawStr) -> Result<Self, Self::Error> {
+                param.percent_decode()
+                    .ok()
+                    .and_then(|decoded| decoded.parse().ok())
+                    .ok_or(param)
+            }
+        }
+    };
+}
+
+typed_id!(
+    /// ID of a `User`.
+    UserId
+);
+
+typed_id!(
+    /// ID of a `Quiz`.
+    QuizId
+);
+
+typed_id!(
+    /// ID of a quiz `Part`, unique within the quiz.
+    PartId
+);
@@ -7,13 +7,24 @@ use rocket::request::{self, Request, FromRequest};
 use crate::role::Role;
 use crate::user::User;
 use crate::error::Problem;
+use crate::token::{ApiToken, Scope};
+use crate::store::{Storage, TokenStore, UserStore};
+use crate::id::UserId;
 use uuid::Uuid;
 use rocket::outcome::Outcome::{Failure, Success};
+use once_cell::sync::OnceCell;
+use std::fs;
+use std::path::PathBuf;
+use crate::config::{Config, ConfigError, ConfigResult};
 
 pub static USER_AUTH_KEY: &'static [u8] = include_bytes!("../jwt-keys/user_auth");
 pub static USER_AUTH_PUB_KEY: &'static [u8] = include_bytes!("../jwt-keys/user_auth.pub");
 
+/// Keys read from paths set in configuration, bundled keys are used until they're loaded.
+static CONFIGURED_KEYS: OnceCell<(Vec<u8>, Vec<u8>)> = OnceCell::new();
+
 pub static AUTH_COOKIE_NAME: &'static str = "jwt_auth";
+pub static BEARER_PREFIX: &'static str = "Bearer ";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserRolesToken {
@@ -21,8 +32,15 @@ pub struct UserRolesToken {
     iat: DateTime<Utc>,
     #[serde(with = "jwt_numeric_date")]
     exp: DateTime<Utc>,
-    pub user: Uuid,
+    pub user: UserId,
     roles: Vec<Role>,
+
+    /// ID of the API token this JWT was issued for. Cookie sessions don't have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tid: Option<Uuid>,
+    /// Scopes granted to API token. Cookie sessions aren't limited by scopes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scopes: Option<Vec<Scope>>,
 }
 
 impl UserRolesToken {
@@ -33,6 +51,56 @@ impl UserRolesToken {
             exp: now + Duration::weeks(1),
             user: user.id.clone(),
             roles: user.user_roles.clone(),
+            tid: None,
+            scopes: None,
+        }
+    }
+
+    pub fn for_api_token(session: &UserRolesToken, token: &ApiToken) -> UserRolesToken {
+        UserRolesToken {
+            iat: token.created,
+            exp: token.expires,
+            user: session.user.clone(),
+            roles: session.roles.clone(),
+            tid: Some(token.id),
+            scopes: Some(token.scopes.clone()),
+        }
+    }
+
+    /// Replaces roles carried by the token with current roles of the user.
+    /// Returns `true` if roles have changed since the token was issued.
+    pub fn refresh_roles(&mut self, user: &User) -> bool {
+        if self.roles == user.user_roles {
+            return false;
+        }
+        self.roles = user.user_roles.clone();
+        true
+    }
+
+    /// Indicates whether the token was issued for an API client rather than a browser session.
+    pub fn is_api_token(&self) -> bool {
+        self.tid.is_some()
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.contains(&scope),
+            None => true,
+        }
+    }
+
+    pub fn require_scope(&self, scope: Scope) -> Result<(), Problem> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(
+                Problem::new_untyped(
+                    Status::Forbidden,
+                    "API token is missing required scope.",
+                )
+                    .insert_serialized("scope", scope)
+                    .clone()
+            )
         }
     }
 
@@ -51,7 +119,7 @@ impl UserRolesToken {
 
     pub fn encode_jwt(&self) -> Result<String, jsonwebtoken::errors::Error> {
         let header = Header::new(Algorithm::PS256);
-        let key = EncodingKey::from_rsa_pem(USER_AUTH_PUB_KEY)?;
+        let key = EncodingKey::from_rsa_pem(private_key())?;
 
         Ok(encode(&header, &self, &key)?)
     }
@@ -69,6 +137,37 @@ impl UserRolesToken {
     }
 }
 
+fn private_key() -> &'static [u8] {
+    CONFIGURED_KEYS.get().map(|(private, _)| private.as_slice()).unwrap_or(USER_AUTH_KEY)
+}
+
+fn public_key() -> &'static [u8] {
+    CONFIGURED_KEYS.get().map(|(_, public)| public.as_slice()).unwrap_or(USER_AUTH_PUB_KEY)
+}
+
+/// Reads JWT keys from paths set in configuration. Keys can only be loaded once.
+pub fn load_keys(config: &Config) -> ConfigResult<()> {
+    let (private_path, public_path) = match (&config.jwt_private_key, &config.jwt_public_key) {
+        (Some(private), Some(public)) => (private, public),
+        _ => return Ok(()),
+    };
+
+    let read = |path: &PathBuf| fs::read(path)
+        .map_err(|source| ConfigError::Io { path: path.clone(), source });
+    let private = read(private_path)?;
+    let public = read(public_path)?;
+
+    if EncodingKey::from_rsa_pem(&private).is_err() || DecodingKey::from_rsa_pem(&public).is_err() {
+        return Err(ConfigError::Invalid("JWT keys must be PEM encoded RSA keys".to_string()));
+    }
+
+    if CONFIGURED_KEYS.set((private, public)).is_err() {
+        warn!("JWT keys were already loaded, keeping the old ones.");
+    }
+
+    Ok(())
+}
+
 pub fn auth_problem<S: Into<String>>(detail: S) -> Problem {
     Problem::new_untyped(
         Status::Unauthorized,
@@ -78,6 +177,16 @@ pub fn auth_problem<S: Into<String>>(detail: S) -> Problem {
         .clone()
 }
 
+pub fn decode_jwt(token: &str) -> Result<UserRolesToken, jsonwebtoken::errors::Error> {
+    let key = DecodingKey::from_rsa_pem(public_key())?;
+
+    decode::<UserRolesToken>(
+        token,
+        &key,
+        &Validation::new(Algorithm::PS256),
+    ).map(|data| data.claims)
+}
+
 pub fn extract_claims(cookies: &CookieJar) -> Result<UserRolesToken, Problem> {
     let auth_cookie = cookies.get_private(AUTH_COOKIE_NAME);
     let token = match auth_cookie {
@@ -87,26 +196,97 @@ pub fn extract_claims(cookies: &CookieJar) -> Result<UserRolesToken, Problem> {
         }
     };
 
-    match decode::<UserRolesToken>(
-        &token,
-        &DecodingKey::from_secret(USER_AUTH_KEY),
-        &Validation::new(Algorithm::PS256),
-    ).map(|data| data.claims) {
+    match decode_jwt(&token) {
         Ok(it) => Ok(it),
         Err(_) => Err(auth_problem("JWT cookie was malformed."))
     }
 }
 
+/// Returns the token of an `Authorization` header which uses `Bearer` scheme. Headers with other
+/// schemes, like `Basic` added by proxies, aren't meant for this server.
+fn bearer_token(header: &str) -> Option<&str> {
+    let scheme = header.get(..BEARER_PREFIX.len())?;
+    if scheme.eq_ignore_ascii_case(BEARER_PREFIX) {
+        Some(header[BEARER_PREFIX.len()..].trim())
+    } else {
+        None
+    }
+}
+
+pub fn extract_bearer_claims(token: &str) -> Result<UserRolesToken, Problem> {
+    match decode_jwt(token) {
+        Ok(it) => Ok(it),
+        Err(_) => Err(auth_problem("Bearer token was malformed."))
+    }
+}
+
+/// Checks that API token a JWT was issued for still exists and wasn't revoked.
+async fn check_api_token(store: &Storage, claims: &UserRolesToken) -> Result<(), Problem> {
+    let tid = match claims.tid {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+
+    let token = match store.find_token(tid).await? {
+        Some(it) => it,
+        None => return Err(auth_problem("API token doesn't exist.")),
+    };
+
+    if token.user != claims.user || !token.is_active() {
+        return Err(auth_problem("API token was revoked or has expired."));
+    }
+
+    Ok(())
+}
+
 #[rocket::async_trait]
 impl<'a, 'r> FromRequest<'a, 'r> for UserRolesToken {
     type Error = Problem;
 
     async fn from_request(req: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
-        let claims = match extract_claims(req.cookies()) {
+        let bearer = req.headers().get("Authorization").find_map(bearer_token);
+        let claims = match bearer {
+            Some(token) => extract_bearer_claims(token),
+            None => extract_claims(req.cookies()),
+        };
+
+        let mut claims = match claims {
             Ok(it) => it,
             Err(e) => return Failure((Status::Unauthorized, e))
         };
 
+        let store = match req.managed_state::<Storage>() {
+            Some(it) => it,
+            None => return Failure((
+                Status::InternalServerError,
+                Problem::new_untyped(
+                    Status::InternalServerError,
+                    "Database isn't available for token validation.",
+                )
+            )),
+        };
+
+        if let Err(e) = check_api_token(store, &claims).await {
+            return Failure((e.status, e));
+        }
+
+        // Roles are refreshed on every request so that role changes take effect immediately.
+        let user = match store.find_user(claims.user).await {
+            Ok(Some(it)) => it,
+            Ok(None) => return Failure((Status::Unauthorized, auth_problem("User no longer exists."))),
+            Err(e) => {
+                let e = Problem::from(e);
+                return Failure((e.status, e));
+            }
+        };
+
+        if claims.refresh_roles(&user) && !claims.is_api_token() {
+            match claims.clone().cookie() {
+                Ok(cookie) => req.cookies().add_private(cookie),
+                Err(_) => warn!("Unable to refresh session cookie for user: {}", claims.user),
+            }
+        }
+
         return Success(claims);
     }
 }
@@ -0,0 +1,310 @@
+//! Logger and request tracing.
+//!
+//! Records are written to standard error either as human readable lines or as JSON objects, one
+//! per line. Security relevant events are logged with `SECURITY_TARGET` and can be written to a
+//! separate file. Every request is assigned an ID which is returned in `X-Request-Id` header and
+//! used as `instance` of `Problem` responses, so reported errors can be found in logs. Records
+//! logged while a request is handled carry its ID as well.
+
+use std::cell::Cell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::Utc;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rocket::{Data, Request, Response, Route};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::handler::{self, Handler};
+use rocket::http::Header;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::config::{Config, ConfigError, ConfigResult};
+use crate::metrics;
+
+/// Target of logins, role changes, grade edits and other security relevant events.
+pub static SECURITY_TARGET: &'static str = "security";
+pub static REQUEST_ID_HEADER: &'static str = "X-Request-Id";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+struct Logger {
+    format: LogFormat,
+    security: Option<Mutex<File>>,
+}
+
+/// Dependencies are only allowed to log warnings, their debug output drowns everything else.
+fn is_own_target(target: &str) -> bool {
+    target == SECURITY_TARGET
+        || target.starts_with("quizzer_server")
+        || target.starts_with("rocket")
+        || target.starts_with('_')
+}
+
+impl Logger {
+    fn format(&self, record: &Record) -> String {
+        let time = Utc::now();
+        let request_id = current_request_id().map(|id| id.0.to_hyphenated().to_string());
+
+        match self.format {
+            LogFormat::Pretty => {
+                let mut line = format!(
+                    "{} {:<5} {}: {}",
+                    time.format("%Y-%m-%d %H:%M:%S%.3f"),
+                    record.level(),
+                    record.target(),
+                    record.args(),
+                );
+                if let Some(id) = request_id {
+                    line.push_str(" request_id=");
+                    line.push_str(&id);
+                }
+                line
+            }
+            LogFormat::Json => {
+                let mut object = json!({
+                    "time": time.to_rfc3339(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                if let Some(id) = request_id {
+                    object["request_id"] = id.into();
+                }
+                object.to_string()
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && (metadata.level() <= Level::Warn || is_own_target(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = self.format(record);
+
+        if record.target() == SECURITY_TARGET {
+            if let Some(Ok(mut file)) = self.security.as_ref().map(|it| it.lock()) {
+                let _ = writeln!(file, "{}", line);
+                return;
+            }
+        }
+
+        let _ = writeln!(io::stderr(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+        if let Some(Ok(mut file)) = self.security.as_ref().map(|it| it.lock()) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Installs the logger. Level can be changed later with `set_level`.
+pub fn init(config: &Config) -> ConfigResult<()> {
+    let security = match &config.security_log {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|source| ConfigError::Io { path: path.clone(), source })?;
+            Some(Mutex::new(file))
+        }
+        None => None,
+    };
+
+    let logger = Logger {
+        format: config.log_format,
+        security,
+    };
+
+    if log::set_boxed_logger(Box::new(logger)).is_err() {
+        warn!("Logger was already initialized.");
+    }
+    set_level(config.log_level);
+
+    Ok(())
+}
+
+pub fn set_level(level: LogLevel) {
+    log::set_max_level(level.into());
+}
+
+/// ID of a request. IDs sent by clients in `X-Request-Id` header are kept if they're UUIDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestId(pub Uuid);
+
+impl RequestId {
+    pub fn of(req: &Request<'_>) -> RequestId {
+        *req.local_cache(|| {
+            let sent = req.headers()
+                .get_one(REQUEST_ID_HEADER)
+                .and_then(|id| Uuid::parse_str(id).ok());
+
+            RequestId(sent.unwrap_or_else(Uuid::new_v4))
+        })
+    }
+
+    /// URI which identifies the request in `Problem` responses.
+    pub fn urn(&self) -> String {
+        format!("urn:uuid:{}", self.0.to_hyphenated())
+    }
+}
+
+tokio::task_local! {
+    /// ID of the request handled by the current task, see `traced`.
+    static TASK_REQUEST_ID: RequestId;
+}
+
+thread_local! {
+    /// ID of the request logged by code which doesn't run in the request task, like fairings.
+    static THREAD_REQUEST_ID: Cell<Option<RequestId>> = Cell::new(None);
+}
+
+/// ID of the request which is being handled, if any.
+pub fn current_request_id() -> Option<RequestId> {
+    TASK_REQUEST_ID.try_with(|id| *id).ok()
+        .or_else(|| THREAD_REQUEST_ID.with(|id| id.get()))
+}
+
+/// Records logged by `f` carry `id` as their request ID.
+fn with_request_id<R, F: FnOnce() -> R>(id: RequestId, f: F) -> R {
+    THREAD_REQUEST_ID.with(|current| {
+        let previous = current.replace(Some(id));
+        let result = f();
+        current.set(previous);
+        result
+    })
+}
+
+/// Handler which runs the wrapped one with the request ID set for the task.
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r, 's: 'r>(&'s self, req: &'r Request<'_>, data: Data) -> handler::Outcome<'r> {
+        TASK_REQUEST_ID.scope(RequestId::of(req), self.0.handle(req, data)).await
+    }
+}
+
+/// Wraps handlers of `routes` so that records logged by them, their guards and everything they
+/// call carry the request ID. Fairings only see the request before and after the handler, so
+/// they can't do this themselves.
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes.into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
+
+struct RequestStart(Instant);
+
+/// Assigns IDs to requests, logs every response and records request metrics.
+pub struct RequestLogger;
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data) {
+        req.local_cache(|| RequestStart(Instant::now()));
+        RequestId::of(req);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let id = RequestId::of(req);
+        let elapsed = req.local_cache(|| RequestStart(Instant::now())).0.elapsed();
+
+        res.set_header(Header::new(REQUEST_ID_HEADER, id.0.to_hyphenated().to_string()));
+
+        let route = req.route()
+            .map(|route| route.uri.to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics::observe_request(req.method().as_str(), &route, res.status().code, elapsed);
+
+        with_request_id(id, || info!(
+            "{} {} {} {}ms",
+            req.method(),
+            req.uri(),
+            res.status().code,
+            elapsed.as_millis(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_json(logger: &Logger) -> serde_json::Value {
+        let line = logger.format(&Record::builder()
+            .args(format_args!("handled"))
+            .level(Level::Info)
+            .target("quizzer_server")
+            .build());
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn records_carry_request_id() {
+        let logger = Logger { format: LogFormat::Json, security: None };
+        let id = RequestId(Uuid::new_v4());
+
+        assert!(format_json(&logger).get("request_id").is_none());
+
+        let record = TASK_REQUEST_ID.scope(id, async { format_json(&logger) }).await;
+        assert_eq!(record["request_id"], id.0.to_hyphenated().to_string());
+        assert_eq!(record["message"], "handled");
+
+        let record = with_request_id(id, || format_json(&logger));
+        assert_eq!(record["request_id"], id.0.to_hyphenated().to_string());
+        assert!(current_request_id().is_none());
+    }
+}
@@ -7,49 +7,91 @@ extern crate rocket;
 #[macro_use]
 extern crate serde;
 
-use mongodb::Client;
 use rocket_contrib::helmet::SpaceHelmet;
+use structopt::StructOpt;
 
-use crate::config::Config;
+use crate::cli::{Cli, Command, ConfigCommand};
+use crate::config::{ConfigSource, LiveConfig};
+use crate::logging::RequestLogger;
 use crate::route::mount_routes;
 
 mod data;
+mod id;
 mod jwt;
 mod user;
 mod route;
 mod config;
 mod role;
 mod error;
+mod token;
+mod audit;
+mod policy;
+mod grading;
+mod course;
+mod body;
+mod convert;
+mod authoring;
+mod cli;
+mod admin;
+mod logging;
+mod metrics;
+mod validation;
+mod pattern;
+mod normalize;
+mod network;
+mod store;
+
+#[cfg(test)]
+mod tests;
 
 #[rocket::main]
 async fn main() {
-    // TODO: Deal with logging later.
-    //log4rs::init_file("log4rs.yml", Default::default()).unwrap();
+    let args = Cli::from_args();
 
-    info!("Reading .env file...");
-    if dotenv::dotenv().is_err() {
-        error!("Unable to load .env file.")
+    // Variables from .env are one of configuration layers so they're loaded before anything else,
+    // failure is logged once the logger is configured.
+    let env_loaded = dotenv::dotenv().is_ok();
+    let source = args.config_source();
+
+    match args.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(source, env_loaded).await,
+        Command::Validate { path } => std::process::exit(cli::validate(&path)),
+        Command::Config(ConfigCommand::Show) => std::process::exit(cli::show_config(&source)),
+        command => std::process::exit(admin::run(&source, command).await),
     }
+}
 
-    info!("Initializing configuration...");
-    let c = Config::init();
+async fn serve(source: ConfigSource, env_loaded: bool) {
+    let c = match source.load()
+        .and_then(|c| logging::init(&c).map(|_| c))
+        .and_then(|c| jwt::load_keys(&c).map(|_| c))
+    {
+        Ok(it) => it,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if !env_loaded {
+        error!("Unable to load .env file.")
+    }
 
-    info!("Connecting to MongoDB: {}", c.mongodb_uri);
-    let client = Client::with_uri_str(c.mongodb_uri.as_str()).await
-        .expect("Unable to init MongoDB client! Is URI valid?");
+    let storage = store::open(&c).await
+        .expect("Unable to open storage! Is configuration valid?");
 
-    info!("Using MongoDB database: {}", c.mongodb_db);
-    let db = client.database(c.mongodb_db.as_str());
+    let figment = c.rocket_figment();
+    let live = LiveConfig::new(c);
+    live.reload_on_hangup(source);
 
     info!("Igniting Rocket...");
-    let mut r = rocket::ignite()
-        .manage(c)
-        .manage(db);
+    let mut r = rocket::custom(figment)
+        .manage(live)
+        .manage(storage);
 
     r = mount_routes(r);
 
     let helmet = SpaceHelmet::default();
-    r = r.attach(helmet);
+    r = r.attach(helmet).attach(RequestLogger);
 
     match r.launch().await {
         Ok(_) => {},
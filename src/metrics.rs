@@ -0,0 +1,94 @@
+//! Prometheus metrics, exposed in text format by `/metrics`.
+//!
+//! Metrics are global so that code which doesn't have access to Rocket state, like grading, can
+//! record them. Rates such as submissions per minute are derived from counters by Prometheus.
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use prometheus::core::Collector;
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+fn register<C: Collector + Clone + 'static>(collector: C) -> C {
+    REGISTRY.register(Box::new(collector.clone()))
+        .expect("Metric names must be unique.");
+    collector
+}
+
+pub static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| register(
+    IntCounterVec::new(
+        Opts::new("http_requests_total", "Handled HTTP requests."),
+        &["method", "route", "status"],
+    ).expect("Invalid metric.")
+));
+
+pub static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| register(
+    HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "Time it took to handle HTTP requests."),
+        &["method", "route"],
+    ).expect("Invalid metric.")
+));
+
+/// Updated from storage whenever metrics are scraped.
+pub static ACTIVE_ATTEMPTS: Lazy<IntGauge> = Lazy::new(|| register(
+    IntGauge::new("quizzer_active_attempts", "Attempts which haven't been finished yet.")
+        .expect("Invalid metric.")
+));
+
+pub static ANSWER_SUBMISSIONS: Lazy<IntCounter> = Lazy::new(|| register(
+    IntCounter::new("quizzer_answer_submissions_total", "Answers submitted to attempts.")
+        .expect("Invalid metric.")
+));
+
+pub static VALIDATION_DURATION: Lazy<HistogramVec> = Lazy::new(|| register(
+    HistogramVec::new(
+        HistogramOpts::new("quizzer_validation_duration_seconds", "Time it took to validate an answer.")
+            .buckets(prometheus::exponential_buckets(0.00001, 4.0, 10).expect("Invalid buckets.")),
+        &["validation"],
+    ).expect("Invalid metric.")
+));
+
+pub static VALIDATION_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| register(
+    IntCounterVec::new(
+        Opts::new("quizzer_validation_failures_total", "Validations which couldn't be evaluated."),
+        &["validation"],
+    ).expect("Invalid metric.")
+));
+
+pub static LOGIN_FAILURES: Lazy<IntCounter> = Lazy::new(|| register(
+    IntCounter::new("quizzer_login_failures_total", "Login attempts with bad username or password.")
+        .expect("Invalid metric.")
+));
+
+/// Records a handled request. `route` is the matched route URI so that IDs don't create a series
+/// per path.
+pub fn observe_request(method: &str, route: &str, status: u16, elapsed: Duration) {
+    HTTP_REQUESTS.with_label_values(&[method, route, &status.to_string()]).inc();
+    HTTP_REQUEST_DURATION.with_label_values(&[method, route]).observe(elapsed.as_secs_f64());
+}
+
+/// Content type of `encode` output.
+pub fn content_type() -> String {
+    TextEncoder::new().format_type().to_string()
+}
+
+/// Encodes all metrics in Prometheus text format.
+pub fn encode() -> String {
+    // Metrics are registered when first used, forcing them lists ones that weren't used yet too.
+    Lazy::force(&HTTP_REQUESTS);
+    Lazy::force(&HTTP_REQUEST_DURATION);
+    Lazy::force(&ACTIVE_ATTEMPTS);
+    Lazy::force(&ANSWER_SUBMISSIONS);
+    Lazy::force(&VALIDATION_DURATION);
+    Lazy::force(&VALIDATION_FAILURES);
+    Lazy::force(&LOGIN_FAILURES);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("Unable to encode metrics.");
+
+    String::from_utf8(buffer).expect("Metrics text format is UTF-8.")
+}
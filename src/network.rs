@@ -0,0 +1,271 @@
+//! Parsing and semantic comparison of networking answers (addresses, prefixes, masks and ports).
+
+use std::net::IpAddr;
+
+use crate::data::AnswerValidation;
+
+/// Parses an IPv4 or IPv6 address. IPv6 addresses can be enclosed in brackets.
+pub fn parse_ip(text: &str) -> Option<IpAddr> {
+    let text = text.trim();
+    let text = text.strip_prefix('[')
+        .and_then(|it| it.strip_suffix(']'))
+        .unwrap_or(text);
+    text.parse().ok()
+}
+
+/// Parses a dotted IPv4 subnet mask (e.g. `255.255.255.0`) into its prefix length.
+fn dotted_mask_prefix(text: &str) -> Option<u8> {
+    let mask = match text.parse::<IpAddr>().ok()? {
+        IpAddr::V4(mask) => u32::from(mask),
+        IpAddr::V6(_) => return None,
+    };
+
+    let prefix = mask.leading_ones();
+    // Mask bits have to be contiguous.
+    if mask.count_ones() != prefix {
+        return None;
+    }
+    Some(prefix as u8)
+}
+
+/// Parses prefix length written as `/24`, `24` or, for IPv4, a dotted mask. Prefix can't be
+/// longer than addresses of the family.
+fn parse_prefix(text: &str, ipv4: bool) -> Option<u8> {
+    let text = text.trim();
+    let text = text.strip_prefix('/').unwrap_or(text).trim();
+
+    let prefix = if text.contains('.') && ipv4 {
+        dotted_mask_prefix(text)?
+    } else {
+        text.parse::<u8>().ok()?
+    };
+
+    let max = if ipv4 { 32 } else { 128 };
+    if prefix <= max { Some(prefix) } else { None }
+}
+
+/// Parses an IPv4 subnet mask in dotted or prefix form into its prefix length. IPv6 networks
+/// don't use masks and are compared as CIDR instead.
+pub fn parse_mask(text: &str) -> Option<u8> {
+    parse_prefix(text, true)
+}
+
+/// Returns network address of `address` with `prefix` bits.
+fn network_address(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(address) & mask).into())
+        }
+        IpAddr::V6(address) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(address) & mask).into())
+        }
+    }
+}
+
+/// Parses an address with a prefix, written as `192.168.0.0/24`, `192.168.0.0/255.255.255.0`
+/// or `192.168.0.0 255.255.255.0`.
+pub fn parse_cidr(text: &str) -> Option<(IpAddr, u8)> {
+    let text = text.trim();
+    let (address, prefix) = match text.find(|c: char| c == '/' || c.is_whitespace()) {
+        Some(index) => (&text[..index], &text[index..]),
+        None => return None,
+    };
+
+    let address = parse_ip(address)?;
+    let prefix = parse_prefix(prefix, address.is_ipv4())?;
+    Some((address, prefix))
+}
+
+/// Parses a MAC address written with `:` or `-` separated bytes, `.` separated groups of four
+/// digits (Cisco notation) or without separators.
+pub fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let text = text.trim();
+    let groups: Vec<&str> = text.split(|c| c == ':' || c == '-' || c == '.').collect();
+    let group_length = match groups.len() {
+        6 if !text.contains('.') => 2,
+        3 if !text.contains(':') && !text.contains('-') => 4,
+        1 => 12,
+        _ => return None,
+    };
+    if groups.iter().any(|group| group.len() != group_length || !group.chars().all(|c| c.is_ascii_hexdigit())) {
+        return None;
+    }
+
+    let digits: String = groups.concat();
+    let mut mac = [0u8; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(mac)
+}
+
+/// Parses a list of ports and port ranges (e.g. `80, 443, 8000-8080`) into sorted, merged
+/// ranges so equal sets of ports compare equal regardless of notation.
+pub fn parse_ports(text: &str) -> Option<Vec<(u16, u16)>> {
+    let mut ranges = vec![];
+    for item in text.split(|c: char| c == ',' || c == ';' || c.is_whitespace()).filter(|it| !it.is_empty()) {
+        let mut bounds = item.splitn(2, |c| c == '-' || c == '–' || c == ':');
+        let start: u16 = bounds.next()?.parse().ok()?;
+        let end: u16 = match bounds.next() {
+            Some(end) => end.parse().ok()?,
+            None => start,
+        };
+        if start > end {
+            return None;
+        }
+        ranges.push((start, end));
+    }
+    if ranges.is_empty() {
+        return None;
+    }
+
+    ranges.sort();
+    let mut merged: Vec<(u16, u16)> = vec![];
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    Some(merged)
+}
+
+/// Checks whether an answer is semantically equal to one of expected values. `None` is returned
+/// for validations which aren't networking ones and when an expected value can't be parsed, so
+/// that answers aren't graded against a broken validation.
+pub fn matches(validation: &AnswerValidation, text: &str) -> Option<bool> {
+    fn any<T: PartialEq, F: Fn(&str) -> Option<T>>(expected: &[String], text: &str, parse: F) -> Option<bool> {
+        let expected = expected.iter()
+            .map(|e| parse(e.as_str()))
+            .collect::<Option<Vec<T>>>()?;
+
+        match parse(text) {
+            Some(answer) => Some(expected.contains(&answer)),
+            None => Some(false),
+        }
+    }
+
+    match validation {
+        AnswerValidation::IpAddress { expected } => any(expected, text, parse_ip),
+        AnswerValidation::Cidr { expected, match_host } => any(expected, text, |text| {
+            let (address, prefix) = parse_cidr(text)?;
+            if *match_host {
+                Some((address, prefix))
+            } else {
+                Some((network_address(address, prefix), prefix))
+            }
+        }),
+        AnswerValidation::SubnetMask { expected } => any(expected, text, parse_mask),
+        AnswerValidation::MacAddress { expected } => any(expected, text, parse_mac),
+        AnswerValidation::Ports { expected } => any(expected, text, parse_ports),
+        _ => None,
+    }
+}
+
+/// Checks whether an expected value of a networking validation can be parsed.
+pub fn is_valid_expected(validation: &AnswerValidation, expected: &str) -> bool {
+    match validation {
+        AnswerValidation::IpAddress { .. } => parse_ip(expected).is_some(),
+        AnswerValidation::Cidr { .. } => parse_cidr(expected).is_some(),
+        AnswerValidation::SubnetMask { .. } => parse_mask(expected).is_some(),
+        AnswerValidation::MacAddress { .. } => parse_mac(expected).is_some(),
+        AnswerValidation::Ports { .. } => parse_ports(expected).is_some(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(expected: &[&str], match_host: bool) -> AnswerValidation {
+        AnswerValidation::Cidr {
+            expected: expected.iter().map(|it| it.to_string()).collect(),
+            match_host,
+        }
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(parse_ip(" 192.168.1.1 "), "192.168.1.1".parse().ok());
+        assert_eq!(parse_ip("[2001:db8::1]"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_ip("2001:0db8:0000::0001"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_ip("192.168.1.256"), None);
+        assert_eq!(parse_ip("[192.168.1.1"), None);
+    }
+
+    #[test]
+    fn parses_cidr_per_family() {
+        assert_eq!(parse_cidr("10.0.0.0/8"), Some(("10.0.0.0".parse().unwrap(), 8)));
+        assert_eq!(parse_cidr("10.0.0.0 255.255.0.0"), Some(("10.0.0.0".parse().unwrap(), 16)));
+        assert_eq!(parse_cidr("10.0.0.0/32").map(|it| it.1), Some(32));
+        assert_eq!(parse_cidr("10.0.0.0/33"), None);
+        assert_eq!(parse_cidr("2001:db8::/128").map(|it| it.1), Some(128));
+        assert_eq!(parse_cidr("[2001:db8::]/48"), Some(("2001:db8::".parse().unwrap(), 48)));
+        assert_eq!(parse_cidr("2001:db8::/129"), None);
+        // Dotted masks are only used by IPv4.
+        assert_eq!(parse_cidr("2001:db8::/255.255.0.0"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_cidr() {
+        for text in &["10.0.0.0", "10.0.0/24", "10.0.0.0/", "10.0.0.0/abc", "10.0.0.0/-1", "10.0.0.0/255.0.255.0", "/24", "gateway/24"] {
+            assert_eq!(parse_cidr(text), None, "{} was parsed", text);
+        }
+    }
+
+    #[test]
+    fn parses_ipv4_masks() {
+        assert_eq!(parse_mask("255.255.255.0"), Some(24));
+        assert_eq!(parse_mask("/24"), Some(24));
+        assert_eq!(parse_mask("0.0.0.0"), Some(0));
+        assert_eq!(parse_mask("255.255.255.255"), Some(32));
+        assert_eq!(parse_mask("/33"), None);
+        assert_eq!(parse_mask("/64"), None);
+        assert_eq!(parse_mask("255.255.0.255"), None);
+        assert_eq!(parse_mask("ffff:ffff::"), None);
+    }
+
+    #[test]
+    fn matches_networks() {
+        let v4 = cidr(&["192.168.1.0/24"], false);
+        assert_eq!(matches(&v4, "192.168.1.77/24"), Some(true));
+        assert_eq!(matches(&v4, "192.168.1.0 255.255.255.0"), Some(true));
+        assert_eq!(matches(&v4, "192.168.1.0/25"), Some(false));
+        assert_eq!(matches(&v4, "192.168.2.0/24"), Some(false));
+        assert_eq!(matches(&v4, "192.168.1.0/40"), Some(false));
+
+        let host = cidr(&["192.168.1.10/24"], true);
+        assert_eq!(matches(&host, "192.168.1.10/255.255.255.0"), Some(true));
+        assert_eq!(matches(&host, "192.168.1.0/24"), Some(false));
+
+        let v6 = cidr(&["2001:db8:abcd::/48"], false);
+        assert_eq!(matches(&v6, "2001:db8:abcd:12::1/48"), Some(true));
+        assert_eq!(matches(&v6, "2001:db8:abce::/48"), Some(false));
+        assert_eq!(matches(&v6, "2001:db8:abcd::/64"), Some(false));
+
+        // Addresses of different families never match.
+        assert_eq!(matches(&cidr(&["0.0.0.0/0"], false), "::/0"), Some(false));
+
+        // Broken validations can't be evaluated.
+        assert_eq!(matches(&cidr(&["192.168.1.0/24", "192.168.1.0/33"], false), "192.168.1.0/24"), None);
+    }
+
+    #[test]
+    fn parses_macs_and_ports() {
+        let mac = Some([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        assert_eq!(parse_mac("00:1A:2B:3C:4D:5E"), mac);
+        assert_eq!(parse_mac("00-1a-2b-3c-4d-5e"), mac);
+        assert_eq!(parse_mac("001a.2b3c.4d5e"), mac);
+        assert_eq!(parse_mac("001a2b3c4d5e"), mac);
+        assert_eq!(parse_mac("00:1a:2b:3c:4d"), None);
+        assert_eq!(parse_mac("00:1a:2b-3c:4d:5e:6f"), None);
+
+        assert_eq!(parse_ports("443, 80 8000-8080;8081"), Some(vec![(80, 80), (443, 443), (8000, 8081)]));
+        assert_eq!(parse_ports("80-22"), None);
+        assert_eq!(parse_ports("65536"), None);
+        assert_eq!(parse_ports(" "), None);
+    }
+}
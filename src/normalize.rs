@@ -0,0 +1,177 @@
+//! Normalization of text answers before they're validated.
+
+use std::borrow::Cow;
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::data::Normalizer;
+
+static NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+(?:[.,']\d+)*").unwrap());
+
+/// Applies normalizers to text in order.
+pub fn apply<'a>(normalizers: &[Normalizer], text: &'a str) -> Cow<'a, str> {
+    let mut result = Cow::Borrowed(text);
+    for normalizer in normalizers {
+        result = Cow::Owned(match normalizer {
+            Normalizer::Trim => result.trim().to_string(),
+            Normalizer::CollapseWhitespace => collapse_whitespace(&result),
+            Normalizer::Nfc => result.nfc().collect(),
+            Normalizer::Nfkc => result.nfkc().collect(),
+            Normalizer::FoldAccents => fold_accents(&result),
+            Normalizer::StripPunctuation => result.chars().filter(|c| !is_punctuation(*c)).collect(),
+            Normalizer::CanonicalNumbers => canonical_numbers(&result),
+        });
+    }
+    result
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                result.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            result.push(c);
+            in_whitespace = false;
+        }
+    }
+    result
+}
+
+fn fold_accents(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        // Letters with strokes don't decompose.
+        match c {
+            'đ' => result.push('d'),
+            'Đ' => result.push('D'),
+            'ł' => result.push('l'),
+            'Ł' => result.push('L'),
+            'ø' => result.push('o'),
+            'Ø' => result.push('O'),
+            'ß' => result.push_str("ss"),
+            'æ' => result.push_str("ae"),
+            'Æ' => result.push_str("AE"),
+            _ => result.push(c),
+        }
+    }
+    result.nfc().collect()
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || matches!(c,
+        '„' | '“' | '”' | '‘' | '’' | '‚' | '«' | '»' | '‹' | '›' |
+        '–' | '—' | '…' | '¿' | '¡' | '·' | '•'
+    )
+}
+
+/// Rewrites a single number written with any combination of `.`, `,` and `'` separators.
+/// Numbers which can't be read unambiguously, like `1.000` which is either one or a thousand,
+/// are left unchanged.
+fn canonical_number(number: &str) -> String {
+    let grouping_valid = |separator: char| number.split(separator)
+        .skip(1)
+        .all(|group| group.chars().take_while(|c| c.is_ascii_digit()).count() == 3);
+
+    let dots = number.matches('.').count();
+    let commas = number.matches(',').count();
+    let (decimal, grouping) = match (dots, commas) {
+        (0, 0) => (None, None),
+        (_, 0) if dots == 1 => (Some('.'), None),
+        (0, _) if commas == 1 => (Some(','), None),
+        (_, 0) => (None, Some('.')),
+        (0, _) => (None, Some(',')),
+        _ => {
+            // The separator which comes last is the decimal one.
+            let last = number.rfind(|c| c == '.' || c == ',').map(|i| number.as_bytes()[i] as char);
+            match last {
+                Some('.') if dots == 1 => (Some('.'), Some(',')),
+                Some(',') if commas == 1 => (Some(','), Some('.')),
+                _ => return number.to_string(),
+            }
+        }
+    };
+
+    if number.contains('\'') && !grouping_valid('\'') {
+        return number.to_string();
+    }
+    if let (Some(decimal), None) = (decimal, grouping) {
+        if !number.contains('\'') && grouping_valid(decimal) {
+            return number.to_string();
+        }
+    }
+    let mut result = number.replace('\'', "");
+    if let Some(grouping) = grouping {
+        let integer = match decimal {
+            Some(decimal) => result.split(decimal).next().unwrap_or_default().to_string(),
+            None => result.clone(),
+        };
+        if !integer.split(grouping).skip(1).all(|group| group.len() == 3) {
+            return number.to_string();
+        }
+        result = result.replace(grouping, "");
+    }
+
+    if let Some(decimal) = decimal {
+        result = result.replace(decimal, ".");
+        let trimmed = result.trim_end_matches('0').trim_end_matches('.');
+        result = trimmed.to_string();
+    }
+
+    result
+}
+
+fn canonical_numbers(text: &str) -> String {
+    NUMBER.replace_all(text, |captures: &Captures| canonical_number(&captures[0])).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_numbers() {
+        let cases = [
+            ("42", "42"),
+            ("3.14", "3.14"),
+            ("3,14", "3.14"),
+            ("2.50", "2.5"),
+            ("10,0", "10"),
+            ("1.000.000", "1000000"),
+            ("1,000,000", "1000000"),
+            ("1,234.5", "1234.5"),
+            ("1.234,50", "1234.5"),
+            ("1'234'567", "1234567"),
+            ("1'234.000", "1234"),
+            ("1.2345", "1.2345"),
+        ];
+
+        for (number, canonical) in cases.iter() {
+            assert_eq!(canonical_number(number), *canonical, "Wrong canonical form of {}", number);
+        }
+    }
+
+    #[test]
+    fn keeps_ambiguous_numbers() {
+        for number in &["1.000", "1,000", "12,345", "1.2.3", "1,23,456", "1.234,5,6", "1'23"] {
+            assert_eq!(canonical_number(number), *number);
+        }
+    }
+
+    #[test]
+    fn applies_normalizers_in_order() {
+        let normalized = apply(&[Normalizer::Trim, Normalizer::CollapseWhitespace, Normalizer::CanonicalNumbers], "  costs \t 1.234,50 €  ");
+        assert_eq!(normalized, "costs 1234.5 €");
+
+        assert_eq!(apply(&[Normalizer::FoldAccents], "Đakovo, Łódź, Straße"), "Dakovo, Lodz, Strasse");
+        assert_eq!(apply(&[Normalizer::StripPunctuation], "„Hello”, world… ¡Hola!"), "Hello world Hola");
+        assert_eq!(apply(&[Normalizer::Nfkc], "ﬁ²"), "fi2");
+    }
+}
@@ -0,0 +1,156 @@
+//! Compiled regular expressions used by regex validation.
+//!
+//! The `regex` crate guarantees matching in linear time with respect to the answer, so answers
+//! can't cause catastrophic backtracking. Compiled patterns are additionally limited in size so a
+//! quiz can't exhaust server memory.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+
+use crate::data::AnswerValidation;
+use crate::metrics;
+use crate::normalize;
+use crate::validation::validation_name;
+
+/// Longest pattern source which is accepted.
+pub const MAX_PATTERN_LENGTH: usize = 4096;
+/// Limit of compiled program size in bytes.
+const SIZE_LIMIT: usize = 1 << 20;
+/// Limit of lazy DFA cache size in bytes (per thread).
+const DFA_SIZE_LIMIT: usize = 1 << 20;
+const NEST_LIMIT: u32 = 64;
+/// Cache is cleared once it holds this many patterns.
+const CACHE_CAPACITY: usize = 1024;
+
+/// Flags a pattern was compiled with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PatternFlags {
+    pub case_sensitive: bool,
+    pub full_match: bool,
+    pub multiline: bool,
+}
+
+static CACHE: Lazy<Mutex<HashMap<(String, PatternFlags), Arc<Regex>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn build(expr: &str, flags: PatternFlags) -> Result<Regex, String> {
+    if expr.len() > MAX_PATTERN_LENGTH {
+        return Err(format!("Pattern is longer than {} characters.", MAX_PATTERN_LENGTH));
+    }
+
+    // \A and \z always match at the start and end of text, even in multiline mode.
+    let source = if flags.full_match {
+        Cow::Owned(format!(r"\A(?:{})\z", expr))
+    } else {
+        Cow::Borrowed(expr)
+    };
+
+    let builder = |source: &str| RegexBuilder::new(source)
+        .case_insensitive(!flags.case_sensitive)
+        .multi_line(flags.multiline)
+        .size_limit(SIZE_LIMIT)
+        .dfa_size_limit(DFA_SIZE_LIMIT)
+        .nest_limit(NEST_LIMIT)
+        .build();
+
+    builder(&source).map_err(|e| match flags.full_match {
+        // Report errors in the pattern as it was written instead of the anchored one.
+        true => builder(expr).err().unwrap_or(e).to_string(),
+        false => e.to_string(),
+    })
+}
+
+/// Returns compiled pattern, compiling it only if it wasn't used before.
+pub fn compile(expr: &str, flags: PatternFlags) -> Result<Arc<Regex>, String> {
+    let key = (expr.to_string(), flags);
+
+    if let Some(regex) = CACHE.lock().expect("Pattern cache poisoned.").get(&key) {
+        return Ok(regex.clone());
+    }
+
+    let regex = Arc::new(build(expr, flags)?);
+
+    let mut cache = CACHE.lock().expect("Pattern cache poisoned.");
+    if cache.len() >= CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(key, regex.clone());
+
+    Ok(regex)
+}
+
+/// Trims text and collapses all whitespace in it into single spaces.
+pub fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Compiles every pattern of a regex validation so quizzes are graded without compiling patterns
+/// on demand. Returns an error for the first pattern that can't be compiled along with its index
+/// (`None` for the main pattern).
+pub fn precompile(validation: &AnswerValidation) -> Result<(), (Option<usize>, String)> {
+    if let AnswerValidation::Regex { case_sensitive, expr, full_match, multiline, alternatives, .. } = validation {
+        let flags = PatternFlags {
+            case_sensitive: *case_sensitive,
+            full_match: *full_match,
+            multiline: *multiline,
+        };
+
+        compile(expr, flags).map_err(|e| (None, e))?;
+        for (index, alternative) in alternatives.iter().enumerate() {
+            compile(&alternative.expr, flags).map_err(|e| (Some(index), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Matches text against a regex validation and returns fraction of points it deserves.
+///
+/// `None` is returned if any of the patterns can't be compiled.
+pub fn score(validation: &AnswerValidation, text: &str) -> Option<f32> {
+    let (case_sensitive, expr, full_match, multiline, collapse, alternatives, normalizers) = match validation {
+        AnswerValidation::Regex { case_sensitive, expr, full_match, multiline, normalize_whitespace, alternatives, normalize } =>
+            (*case_sensitive, expr, *full_match, *multiline, *normalize_whitespace, alternatives, normalize),
+        _ => return None,
+    };
+    let flags = PatternFlags {
+        case_sensitive,
+        full_match,
+        multiline,
+    };
+
+    let text = normalize::apply(normalizers, text);
+    let text = if collapse {
+        Cow::Owned(normalize_whitespace(&text))
+    } else {
+        text
+    };
+
+    let matches = |expr: &str| -> Option<bool> {
+        match compile(expr, flags) {
+            Ok(regex) => Some(regex.is_match(&text)),
+            Err(e) => {
+                warn!("Unable to compile validation regex '{}': {}", expr, e);
+                metrics::VALIDATION_FAILURES.with_label_values(&[validation_name(validation)]).inc();
+                None
+            }
+        }
+    };
+
+    if matches(expr)? {
+        return Some(1.0);
+    }
+
+    let mut best: f32 = 0.0;
+    for alternative in alternatives {
+        if alternative.score > best && matches(&alternative.expr)? {
+            best = alternative.score;
+        }
+    }
+
+    Some(best.max(0.0).min(1.0))
+}
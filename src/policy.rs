@@ -0,0 +1,274 @@
+use chrono::Utc;
+use rocket::http::Status;
+
+use crate::course::{Course, CourseRole, Enrollment};
+use crate::data::{Capability, ParticipantInfo, Quiz};
+use crate::error::Problem;
+use crate::jwt::UserRolesToken;
+use crate::role::Role;
+use crate::token::Scope;
+use crate::user::User;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Read,
+    Update,
+    Delete,
+    Grade,
+    /// Finishes an attempt. Unlike updates it's allowed after the time limit ran out.
+    Finish,
+}
+
+#[inline]
+pub fn forbidden_problem<S: Into<String>>(detail: S) -> Problem {
+    Problem::new_untyped(
+        Status::Forbidden,
+        "Action not permitted.",
+    )
+        .detail(detail)
+        .clone()
+}
+
+/// Decides whether an authenticated user may perform an action on a resource.
+///
+/// `Context` carries any additional resources required to reach a decision, e.g. attempts are
+/// authorized against the quiz they belong to.
+pub trait Policy<Context = ()> {
+    fn authorize(&self, auth: &UserRolesToken, action: Action, context: Context) -> Result<(), Problem>;
+}
+
+impl Policy for User {
+    fn authorize(&self, auth: &UserRolesToken, action: Action, _: ()) -> Result<(), Problem> {
+        match action {
+            Action::Read => auth.require_scope(Scope::UsersRead)?,
+            _ => auth.require_scope(Scope::UsersWrite)?,
+        }
+
+        if auth.has_min_role(Role::Admin) {
+            return Ok(());
+        }
+
+        match action {
+            Action::Read | Action::Delete if self.id == auth.user => Ok(()),
+            _ => Err(forbidden_problem("Users can only read and delete their own account.")),
+        }
+    }
+}
+
+impl Quiz {
+    /// Indicates whether user owns the quiz. Owners have every capability and are the only ones
+    /// who can delete the quiz or change its collaborators.
+    pub fn is_owned_by(&self, auth: &UserRolesToken) -> bool {
+        auth.has_min_role(Role::Admin) || self.author == auth.user
+    }
+
+    /// Returns course enrollment of the user if it applies to this quiz.
+    fn course_enrollment<'e>(&self, enrollment: Option<&'e Enrollment>) -> Option<&'e Enrollment> {
+        enrollment.filter(|e| Some(e.course) == self.course)
+    }
+
+    pub fn has_capability(&self, auth: &UserRolesToken, enrollment: Option<&Enrollment>, capability: Capability) -> bool {
+        if self.is_owned_by(auth) {
+            return true;
+        }
+
+        if let Some(c) = self.collaborator(auth.user) {
+            if c.capabilities.contains(&capability) {
+                return true;
+            }
+        }
+
+        match self.course_enrollment(enrollment) {
+            Some(e) => e.role.quiz_capabilities().contains(&capability),
+            None => false,
+        }
+    }
+
+    pub fn require_capability(&self, auth: &UserRolesToken, enrollment: Option<&Enrollment>, capability: Capability) -> Result<(), Problem> {
+        if self.has_capability(auth, enrollment, capability) {
+            Ok(())
+        } else {
+            Err(
+                forbidden_problem("User lacks required quiz capability.")
+                    .insert_serialized("capability", capability)
+                    .clone()
+            )
+        }
+    }
+
+    /// Indicates whether user may see expected answers. Content editors and graders always can,
+    /// participants only after finishing their `attempt` and only if quiz shows answers.
+    pub fn may_see_answers(&self, auth: &UserRolesToken, enrollment: Option<&Enrollment>, attempt: Option<&ParticipantInfo>) -> bool {
+        if self.has_capability(auth, enrollment, Capability::EditContent) ||
+            self.has_capability(auth, enrollment, Capability::Grade) {
+            return true;
+        }
+
+        self.show_answer && attempt.map_or(false, |a| a.id == auth.user && a.finished_on.is_some())
+    }
+
+    /// Indicates whether user is allowed to participate in the quiz, either because quiz is public,
+    /// user was explicitly invited or is enrolled in the course quiz belongs to.
+    pub fn is_eligible(&self, auth: &UserRolesToken, enrollment: Option<&Enrollment>) -> bool {
+        self.public ||
+            self.participants.contains(&auth.user) ||
+            self.course_enrollment(enrollment).is_some()
+    }
+}
+
+impl<'e> Policy<Option<&'e Enrollment>> for Quiz {
+    fn authorize(&self, auth: &UserRolesToken, action: Action, enrollment: Option<&'e Enrollment>) -> Result<(), Problem> {
+        match action {
+            Action::Read => auth.require_scope(Scope::QuizRead)?,
+            _ => auth.require_scope(Scope::QuizWrite)?,
+        }
+
+        match action {
+            Action::Create => {
+                if !auth.has_min_role(Role::Author) {
+                    return Err(forbidden_problem("Permission level too low."));
+                }
+                if self.author != auth.user && !auth.has_min_role(Role::Admin) {
+                    return Err(forbidden_problem("Quizzes can only be created on behalf of self."));
+                }
+                if self.course.is_some() && !auth.has_min_role(Role::Admin) {
+                    match self.course_enrollment(enrollment) {
+                        Some(e) if e.role == CourseRole::Teacher => {}
+                        _ => return Err(forbidden_problem("Only course teachers can add quizzes to a course.")),
+                    }
+                }
+                Ok(())
+            }
+            Action::Read => {
+                if self.is_owned_by(auth) ||
+                    self.collaborator(auth.user).is_some() ||
+                    self.is_eligible(auth, enrollment) {
+                    Ok(())
+                } else {
+                    Err(forbidden_problem("User isn't a participant of the quiz."))
+                }
+            }
+            Action::Update | Action::Finish => self.require_capability(auth, enrollment, Capability::EditContent),
+            Action::Grade => self.require_capability(auth, enrollment, Capability::Grade),
+            Action::Delete => {
+                if self.is_owned_by(auth) {
+                    Ok(())
+                } else {
+                    Err(forbidden_problem("Quiz not owned by user."))
+                }
+            }
+        }
+    }
+}
+
+impl<'q> Policy<(&'q Quiz, Option<&'q Enrollment>)> for ParticipantInfo {
+    fn authorize(&self, auth: &UserRolesToken, action: Action, context: (&'q Quiz, Option<&'q Enrollment>)) -> Result<(), Problem> {
+        let (quiz, enrollment) = context;
+
+        match action {
+            Action::Read => auth.require_scope(Scope::GradesRead)?,
+            Action::Grade | Action::Delete => auth.require_scope(Scope::GradesWrite)?,
+            Action::Create | Action::Update | Action::Finish => auth.require_scope(Scope::QuizWrite)?,
+        }
+
+        match action {
+            Action::Create => {
+                if !quiz.is_eligible(auth, enrollment) {
+                    return Err(forbidden_problem("User isn't a participant of the quiz."));
+                }
+                if !quiz.is_open() {
+                    return Err(forbidden_problem("Quiz isn't open for attempts."));
+                }
+                Ok(())
+            }
+            Action::Read => {
+                if self.id == auth.user ||
+                    quiz.has_capability(auth, enrollment, Capability::ViewReports) ||
+                    quiz.has_capability(auth, enrollment, Capability::Grade) {
+                    Ok(())
+                } else {
+                    Err(forbidden_problem("Attempt not owned by user."))
+                }
+            }
+            Action::Update => {
+                if self.id != auth.user {
+                    return Err(forbidden_problem("Attempt not owned by user."));
+                }
+                if self.finished_on.is_some() {
+                    return Err(forbidden_problem("Attempt has already been finished."));
+                }
+                if !quiz.is_open() {
+                    return Err(forbidden_problem("Quiz is closed."));
+                }
+                if let Some(limit) = quiz.time_limit {
+                    let deadline = chrono::Duration::from_std(limit).ok()
+                        .and_then(|limit| self.started_on.checked_add_signed(limit));
+                    if let Some(deadline) = deadline {
+                        if Utc::now() > deadline {
+                            return Err(forbidden_problem("Attempt time limit has been exceeded."));
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Action::Finish => {
+                if self.id != auth.user {
+                    return Err(forbidden_problem("Attempt not owned by user."));
+                }
+                if self.finished_on.is_some() {
+                    return Err(forbidden_problem("Attempt has already been finished."));
+                }
+                Ok(())
+            }
+            Action::Grade => {
+                quiz.require_capability(auth, enrollment, Capability::Grade)?;
+                if self.finished_on.is_none() {
+                    return Err(forbidden_problem("Attempt hasn't been finished yet."));
+                }
+                Ok(())
+            }
+            Action::Delete => quiz.require_capability(auth, enrollment, Capability::ManageParticipants),
+        }
+    }
+}
+
+impl<'e> Policy<Option<&'e Enrollment>> for Course {
+    fn authorize(&self, auth: &UserRolesToken, action: Action, enrollment: Option<&'e Enrollment>) -> Result<(), Problem> {
+        match action {
+            Action::Read => auth.require_scope(Scope::UsersRead)?,
+            _ => auth.require_scope(Scope::UsersWrite)?,
+        }
+
+        if auth.has_min_role(Role::Admin) {
+            return Ok(());
+        }
+
+        let role = enrollment
+            .filter(|e| e.course == self.id && e.user == auth.user)
+            .map(|e| e.role);
+
+        match action {
+            Action::Create => {
+                if auth.has_min_role(Role::Author) {
+                    Ok(())
+                } else {
+                    Err(forbidden_problem("Permission level too low."))
+                }
+            }
+            Action::Read => match role {
+                Some(_) => Ok(()),
+                None => Err(forbidden_problem("User isn't enrolled in the course.")),
+            },
+            // Course staff is allowed to review course members and their results.
+            Action::Grade => match role {
+                Some(CourseRole::Teacher) | Some(CourseRole::TeachingAssistant) => Ok(()),
+                _ => Err(forbidden_problem("Only course staff can view course members.")),
+            },
+            Action::Update | Action::Delete | Action::Finish => match role {
+                Some(CourseRole::Teacher) => Ok(()),
+                _ => Err(forbidden_problem("Only course teachers can manage the course.")),
+            },
+        }
+    }
+}
@@ -25,6 +25,19 @@ impl std::fmt::Display for Role {
     }
 }
 
+impl std::str::FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "normal" => Ok(Role::Normal),
+            "author" => Ok(Role::Author),
+            "admin" => Ok(Role::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
 impl std::convert::Into<String> for Role {
     fn into(self) -> String {
         self.to_string()
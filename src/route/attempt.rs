@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+
+use crate::data::{Answer, AnswerType, Capability, Grade, Part, ParticipantInfo};
+use crate::grading::grade_attempt;
+use crate::body::JsonBody;
+use crate::error::Problem;
+use crate::id::{PartId, QuizId, UserId};
+use crate::jwt::UserRolesToken;
+use crate::logging::SECURITY_TARGET;
+use crate::metrics;
+use crate::policy::{Action, Policy, forbidden_problem};
+use crate::token::Scope;
+use crate::route::quiz::load_quiz;
+use crate::course::find_enrollment;
+use crate::store::{AttemptStore, Storage, StoreError};
+
+#[inline]
+fn attempt_not_found(quiz: QuizId, user: UserId) -> Problem {
+    Problem::new_untyped(
+        Status::NotFound,
+        "Attempt doesn't exist."
+    )
+        .insert_serialized("quiz", quiz.to_string())
+        .insert_serialized("user", user.to_string())
+        .clone()
+}
+
+#[inline]
+fn attempt_exists_problem(quiz: QuizId) -> Problem {
+    Problem::new_untyped(
+        Status::Conflict,
+        "Quiz has already been attempted."
+    )
+        .insert_serialized("quiz", quiz.to_string())
+        .clone()
+}
+
+#[inline]
+fn bad_part_problem<S: Into<String>>(part: PartId, detail: S) -> Problem {
+    Problem::new_untyped(
+        Status::BadRequest,
+        "Bad quiz part."
+    )
+        .insert_serialized("part", part.to_string())
+        .detail(detail)
+        .clone()
+}
+
+#[inline]
+fn bad_answer_problem<S: Into<String>>(part: PartId, detail: S) -> Problem {
+    Problem::new_untyped(
+        Status::UnprocessableEntity,
+        "Bad answer."
+    )
+        .insert_serialized("part", part.to_string())
+        .detail(detail)
+        .clone()
+}
+
+/// Checks that chosen options of a choice answer exist in the part.
+fn check_answer(part: PartId, ans: &AnswerType, answer: &Answer) -> Result<(), Problem> {
+    let (options, chosen) = match (ans, answer) {
+        (AnswerType::Single { options, .. }, Answer::Single(i)) => (options, std::slice::from_ref(i)),
+        (AnswerType::Multiple { options, .. }, Answer::Multiple(chosen)) => (options, chosen.as_slice()),
+        _ => return Ok(()),
+    };
+
+    match chosen.iter().find(|&&i| i < 0 || i as usize >= options.len()) {
+        Some(i) => Err(
+            bad_answer_problem(part, "Chosen option doesn't exist.")
+                .insert_serialized("option", *i)
+                .clone()
+        ),
+        None => Ok(()),
+    }
+}
+
+#[inline]
+fn attempt_finished_problem() -> Problem {
+    forbidden_problem("Attempt has already been finished.")
+}
+
+#[inline]
+fn attempt_conflict_problem(quiz: QuizId) -> Problem {
+    Problem::new_untyped(
+        Status::Conflict,
+        "Attempt is being modified concurrently."
+    )
+        .insert_serialized("quiz", quiz.to_string())
+        .detail("Answers kept changing while the attempt was being finished. Try again.")
+        .clone()
+}
+
+/// Number of times finishing is attempted before giving up because answers keep changing.
+const FINISH_RETRIES: usize = 3;
+
+/// Returns an attempt that was successfully updated or a problem if it doesn't exist (anymore).
+#[inline]
+fn updated_attempt(attempt: Option<ParticipantInfo>, quiz: QuizId, user: UserId) -> Result<ParticipantInfo, Problem> {
+    match attempt {
+        Some(it) => Ok(it),
+        None => Err(attempt_not_found(quiz, user)),
+    }
+}
+
+#[post("/<id>/attempt")]
+pub async fn attempt_start(id: QuizId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    let attempt = ParticipantInfo::new(auth.user, quiz.id);
+    attempt.authorize(&auth, Action::Create, (&quiz, enrollment.as_ref()))?;
+
+    match store.insert_attempt(&attempt).await {
+        Ok(()) => {}
+        Err(StoreError::Duplicate(_)) => return Err(attempt_exists_problem(quiz.id)),
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(Json(attempt))
+}
+
+#[get("/<id>/attempt")]
+pub async fn attempt_get(id: QuizId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    let attempt = match store.find_attempt(quiz.id, auth.user).await? {
+        Some(it) => it,
+        None => return Err(attempt_not_found(quiz.id, auth.user)),
+    };
+    attempt.authorize(&auth, Action::Read, (&quiz, enrollment.as_ref()))?;
+
+    Ok(Json(attempt))
+}
+
+#[get("/<id>/attempt/<user>")]
+pub async fn attempt_get_user(id: QuizId, user: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    let attempt = match store.find_attempt(quiz.id, user).await? {
+        Some(it) => it,
+        None => return Err(attempt_not_found(quiz.id, user)),
+    };
+    attempt.authorize(&auth, Action::Read, (&quiz, enrollment.as_ref()))?;
+
+    Ok(Json(attempt))
+}
+
+#[post("/<id>/attempt/<part>", data = "<answer>", rank = 2)]
+pub async fn attempt_submit(id: QuizId, part: PartId, answer: Result<JsonBody<Answer>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let answer = answer?;
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    let attempt = match store.find_attempt(quiz.id, auth.user).await? {
+        Some(it) => it,
+        None => return Err(attempt_not_found(quiz.id, auth.user)),
+    };
+    attempt.authorize(&auth, Action::Update, (&quiz, enrollment.as_ref()))?;
+
+    match quiz.part(part) {
+        Some(Part::Interact { ans, .. }) => check_answer(part, ans, &answer.0)?,
+        Some(Part::Content { .. }) => return Err(bad_part_problem(part, "Content parts can't be answered.")),
+        None => return Err(bad_part_problem(part, "Quiz doesn't contain the part.")),
+    }
+
+    match store.set_answer(quiz.id, auth.user, part, &answer.0).await? {
+        Some(it) => {
+            metrics::ANSWER_SUBMISSIONS.inc();
+            Ok(Json(it))
+        }
+        // Attempt was finished or deleted after it was read.
+        None => match store.find_attempt(quiz.id, auth.user).await? {
+            Some(_) => Err(attempt_finished_problem()),
+            None => Err(attempt_not_found(quiz.id, auth.user)),
+        },
+    }
+}
+
+#[post("/<id>/attempt/finish", rank = 1)]
+pub async fn attempt_finish(id: QuizId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    // Answers submitted while grades are being computed change attempt version, in which case
+    // grading is repeated with the new answers.
+    for _ in 0..FINISH_RETRIES {
+        let attempt = match store.find_attempt(quiz.id, auth.user).await? {
+            Some(it) => it,
+            None => return Err(attempt_not_found(quiz.id, auth.user)),
+        };
+        // Participants must be able to finish their attempt even after the time limit ran out.
+        attempt.authorize(&auth, Action::Finish, (&quiz, enrollment.as_ref()))?;
+
+        let grades = grade_attempt(&quiz, &attempt);
+
+        if let Some(it) = store.finish_attempt(quiz.id, auth.user, attempt.version, Utc::now(), &grades).await? {
+            return Ok(Json(it));
+        }
+    }
+
+    Err(attempt_conflict_problem(quiz.id))
+}
+
+#[delete("/<id>/attempt/<user>")]
+pub async fn attempt_delete(id: QuizId, user: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    let attempt = match store.find_attempt(quiz.id, user).await? {
+        Some(it) => it,
+        None => return Err(attempt_not_found(quiz.id, user)),
+    };
+    attempt.authorize(&auth, Action::Delete, (&quiz, enrollment.as_ref()))?;
+
+    store.delete_attempt(quiz.id, user).await?;
+
+    Ok(Json(attempt))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GradeInfo {
+    score: f32,
+}
+
+#[put("/<id>/attempt/<user>/grade/<part>", data = "<grade>")]
+pub async fn attempt_grade(id: QuizId, user: UserId, part: PartId, grade: Result<JsonBody<GradeInfo>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ParticipantInfo>, Problem> {
+    let grade = grade?;
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+
+    let attempt = match store.find_attempt(quiz.id, user).await? {
+        Some(it) => it,
+        None => return Err(attempt_not_found(quiz.id, user)),
+    };
+    attempt.authorize(&auth, Action::Grade, (&quiz, enrollment.as_ref()))?;
+
+    let max_score = match quiz.part(part) {
+        Some(Part::Interact { value, .. }) => *value,
+        Some(Part::Content { .. }) => return Err(bad_part_problem(part, "Content parts can't be graded.")),
+        None => return Err(bad_part_problem(part, "Quiz doesn't contain the part.")),
+    };
+
+    if !(0.0..=max_score).contains(&grade.score) {
+        return Err(
+            bad_part_problem(part, "Score must be between 0 and part value.")
+                .insert_serialized("max_score", max_score)
+                .clone()
+        );
+    }
+
+    let grade = Grade {
+        score: grade.score,
+        graded_by: Some(auth.user),
+        graded_on: Utc::now(),
+    };
+
+    let mut grades = HashMap::new();
+    grades.insert(part, grade.clone());
+
+    let attempt = store.set_grades(quiz.id, user, &grades).await?;
+    let attempt = updated_attempt(attempt, quiz.id, user)?;
+    log::info!(target: SECURITY_TARGET, "{} graded part {} of attempt by {} at quiz {} with {}", auth.user, part, user, quiz.id, grade.score);
+
+    Ok(Json(attempt))
+}
+
+#[get("/<id>/attempts")]
+pub async fn attempt_list(id: QuizId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Vec<ParticipantInfo>>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.require_capability(&auth, enrollment.as_ref(), Capability::ViewReports)?;
+    auth.require_scope(Scope::GradesRead)?;
+
+    let attempts = store.list_attempts(quiz.id).await?;
+
+    Ok(Json(attempts))
+}
@@ -0,0 +1,119 @@
+use rocket::State;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+
+use crate::convert::{self, ConversionReport, Format};
+use crate::course::find_enrollment;
+use crate::data::Quiz;
+use crate::error::{Problem, problems};
+use crate::id::QuizId;
+use crate::jwt::UserRolesToken;
+use crate::policy::{Action, Policy};
+use crate::route::quiz::load_quiz;
+use crate::store::{QuizStore, Storage};
+use crate::validation::validate_quiz;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    quiz: Quiz,
+    report: ConversionReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportResult {
+    filename: String,
+    content_type: String,
+    /// Content of binary formats is base64 encoded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    content: String,
+    report: ConversionReport,
+}
+
+#[inline]
+fn import_problem<S: Into<String>>(status: Status, detail: S) -> Problem {
+    Problem::new_untyped(
+        status,
+        "Unable to read imported quiz."
+    )
+        .detail(detail)
+        .clone()
+}
+
+fn parse_format(format: &String) -> Result<Format, Problem> {
+    format.parse::<Format>().map_err(|_| {
+        problems::parse_problem()
+            .insert_serialized("format", format.clone())
+            .detail("Format must be one of: moodle, gift, qti, yaml, toml.")
+            .clone()
+    })
+}
+
+/// Creates a new quiz from questions in a foreign format. Questions which couldn't be converted
+/// are listed in the returned report.
+#[post("/<format>?<name>", data = "<source>")]
+pub async fn quiz_import(format: String, name: Option<String>, source: Data, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ImportResult>, Problem> {
+    let format = parse_format(&format)?;
+
+    let source = match source.open(16.mebibytes()).into_bytes().await {
+        Ok(it) if it.is_complete() => it.into_inner(),
+        Ok(_) => return Err(import_problem(Status::PayloadTooLarge, "Imported file is too large.")),
+        Err(e) => return Err(import_problem(Status::BadRequest, format!("Unable to read request body: {}", e))),
+    };
+    let imported = convert::import(format, &source)?;
+
+    if imported.quiz.parts.is_empty() {
+        return Err(
+            Problem::new_untyped(
+                Status::UnprocessableEntity,
+                "Imported quiz has no questions."
+            )
+                .insert_serialized("report", &imported.report)
+                .clone()
+        );
+    }
+
+    let mut quiz = imported.quiz;
+    quiz.author = auth.user;
+    if let Some(name) = name {
+        quiz.name = name;
+    }
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.authorize(&auth, Action::Create, enrollment.as_ref())?;
+    validate_quiz(&quiz)?;
+
+    store.insert_quiz(&quiz).await?;
+
+    Ok(Json(ImportResult {
+        quiz,
+        report: imported.report,
+    }))
+}
+
+/// Exports quiz questions into a foreign format. Exported files contain expected answers so only
+/// users who can edit the quiz are allowed to export it.
+#[get("/<id>/export/<format>")]
+pub async fn quiz_export(id: QuizId, format: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ExportResult>, Problem> {
+    let format = parse_format(&format)?;
+
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.authorize(&auth, Action::Update, enrollment.as_ref())?;
+
+    let (content, report) = convert::export(format, &quiz)?;
+
+    let (encoding, content) = if format.is_binary() {
+        (Some("base64".to_string()), base64::encode(&content))
+    } else {
+        (None, String::from_utf8(content).expect("Text export formats produce UTF-8."))
+    };
+
+    Ok(Json(ExportResult {
+        filename: format!("{}.{}", quiz.id, format.extension()),
+        content_type: format.content_type().to_string(),
+        encoding,
+        content,
+        report,
+    }))
+}
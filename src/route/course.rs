@@ -0,0 +1,161 @@
+use chrono::Utc;
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::course::{Course, CourseRole, Enrollment, RosterEntry, RosterIssue, enroll_roster};
+use crate::body::JsonBody;
+use crate::error::{Problem, problems};
+use crate::id::UserId;
+use crate::jwt::UserRolesToken;
+use crate::policy::{Action, Policy};
+use crate::route::parse_id;
+use crate::store::{CourseStore, QuizStore, Storage, UserStore};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CourseCreateInfo {
+    name: String,
+    #[serde(default)]
+    desc: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterReport {
+    enrolled: Vec<RosterEntry>,
+    failed: Vec<RosterIssue>,
+}
+
+#[inline]
+fn course_not_found(id: Uuid) -> Problem {
+    Problem::new_untyped(
+        Status::NotFound,
+        "Course doesn't exist."
+    )
+        .insert_serialized("id", id.to_string())
+        .clone()
+}
+
+#[inline]
+fn user_not_found(id: UserId) -> Problem {
+    Problem::new_untyped(
+        Status::NotFound,
+        "User doesn't exist."
+    )
+        .insert_serialized("id", id.to_string())
+        .clone()
+}
+
+async fn load_course(store: &Storage, id: &str, auth: &UserRolesToken) -> Result<(Course, Option<Enrollment>), Problem> {
+    let uuid: Uuid = parse_id(id)?;
+
+    let course = match store.find_course(uuid).await? {
+        Some(it) => it,
+        None => return Err(course_not_found(uuid)),
+    };
+    let enrollment = store.find_enrollment(course.id, auth.user).await?;
+
+    Ok((course, enrollment))
+}
+
+#[post("/", data = "<info>")]
+pub async fn course_create(info: Result<JsonBody<CourseCreateInfo>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Course>, Problem> {
+    let info = info?;
+    let course = Course {
+        id: Uuid::new_v4(),
+        name: info.name.clone(),
+        desc: info.desc.clone(),
+        created: Utc::now(),
+    };
+    course.authorize(&auth, Action::Create, None)?;
+
+    store.insert_course(&course).await?;
+    store.save_enrollment(&Enrollment::new(course.id, auth.user, CourseRole::Teacher)).await?;
+
+    Ok(Json(course))
+}
+
+#[get("/<id>")]
+pub async fn course_get(id: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Course>, Problem> {
+    let (course, enrollment) = load_course(&store, &id, &auth).await?;
+    course.authorize(&auth, Action::Read, enrollment.as_ref())?;
+
+    Ok(Json(course))
+}
+
+/// Deletes a course and all of its enrollments. Quizzes which belonged to the course are kept,
+/// but are no longer associated with it.
+#[delete("/<id>")]
+pub async fn course_delete(id: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Course>, Problem> {
+    let (course, enrollment) = load_course(&store, &id, &auth).await?;
+    course.authorize(&auth, Action::Delete, enrollment.as_ref())?;
+
+    store.detach_course(course.id).await?;
+    store.delete_course_enrollments(course.id).await?;
+    store.delete_course(course.id).await?;
+
+    Ok(Json(course))
+}
+
+#[get("/<id>/enrollments")]
+pub async fn course_enrollments(id: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Vec<Enrollment>>, Problem> {
+    let (course, enrollment) = load_course(&store, &id, &auth).await?;
+    course.authorize(&auth, Action::Grade, enrollment.as_ref())?;
+
+    let enrollments = store.list_enrollments(course.id).await?;
+
+    Ok(Json(enrollments))
+}
+
+#[put("/<id>/enrollments/<user>/<role>")]
+pub async fn course_enroll(id: String, user: UserId, role: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Enrollment>, Problem> {
+    let (course, enrollment) = load_course(&store, &id, &auth).await?;
+    course.authorize(&auth, Action::Update, enrollment.as_ref())?;
+
+    if store.find_user(user).await?.is_none() {
+        return Err(user_not_found(user));
+    }
+
+    let role = match role.parse::<CourseRole>() {
+        Ok(it) => it,
+        Err(_) => return Err(
+            problems::parse_problem()
+                .insert_serialized("role", role)
+                .detail("Course role must be one of: student, ta, teacher.")
+                .clone()
+        ),
+    };
+
+    let enrollment = Enrollment::new(course.id, user, role);
+    store.save_enrollment(&enrollment).await?;
+
+    Ok(Json(enrollment))
+}
+
+#[delete("/<id>/enrollments/<user>")]
+pub async fn course_unenroll(id: String, user: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Option<String>, Problem> {
+    let (course, enrollment) = load_course(&store, &id, &auth).await?;
+    course.authorize(&auth, Action::Update, enrollment.as_ref())?;
+
+
+    if !store.delete_enrollment(course.id, user).await? {
+        return Ok(None);
+    }
+
+    Ok(Some(user.to_string()))
+}
+
+/// Enrolls users listed in a CSV roster with `username[,role]` rows. Rows which couldn't be
+/// enrolled are listed in the returned report.
+#[post("/<id>/roster", format = "text/csv", data = "<roster>")]
+pub async fn course_roster(id: String, roster: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<RosterReport>, Problem> {
+    let (course, enrollment) = load_course(&store, &id, &auth).await?;
+    course.authorize(&auth, Action::Update, enrollment.as_ref())?;
+
+    let (enrolled, failed) = enroll_roster(&store, course.id, &roster).await?;
+
+    Ok(Json(RosterReport {
+        enrolled,
+        failed,
+    }))
+}
@@ -3,9 +3,10 @@ use std::path::PathBuf;
 use rocket::State;
 use rocket::response::NamedFile;
 
-use crate::config::Config;
+use crate::config::LiveConfig;
 
-pub async fn app_index_file(c: State<'_, Config>) -> NamedFile {
+pub async fn app_index_file(c: State<'_, LiveConfig>) -> NamedFile {
+    let c = c.get();
     NamedFile::open(c.public_content.as_path().join("index.html"))
         .await
         .expect(
@@ -18,13 +19,14 @@ pub async fn app_index_file(c: State<'_, Config>) -> NamedFile {
 }
 
 #[get("/", format = "text/html")]
-pub async fn app(c: State<'_, Config>) -> NamedFile {
+pub async fn app(c: State<'_, LiveConfig>) -> NamedFile {
     app_index_file(c).await
 }
 
 #[get("/<path..>", format = "text/html", rank = 10)]
-pub async fn app_path(path: PathBuf, c: State<'_, Config>) -> NamedFile {
-    NamedFile::open(c.public_content.as_path().join(path.as_path())).await
+pub async fn app_path(path: PathBuf, c: State<'_, LiveConfig>) -> NamedFile {
+    let public_content = c.get().public_content.clone();
+    NamedFile::open(public_content.as_path().join(path.as_path())).await
         .ok()
         .unwrap_or(app_index_file(c).await)
 }
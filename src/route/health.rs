@@ -0,0 +1,47 @@
+use rocket::State;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::Content;
+
+use crate::error::Problem;
+use crate::metrics;
+use crate::store::{AttemptStore, StatusStore, Storage};
+
+/// Liveness probe, succeeds as long as the server handles requests.
+#[get("/healthz")]
+pub async fn healthz() -> &'static str {
+    "OK"
+}
+
+/// Readiness probe, fails while the database can't be reached.
+#[get("/readyz")]
+pub async fn readyz(store: State<'_, Storage>) -> Result<&'static str, Problem> {
+    match store.ping().await {
+        Ok(()) => Ok("OK"),
+        Err(e) => {
+            warn!("Readiness check failed: {}", e);
+            Err(
+                Problem::new_untyped(
+                    Status::ServiceUnavailable,
+                    "Database isn't available."
+                )
+                    .detail("Readiness check couldn't reach the database.")
+                    .clone()
+            )
+        }
+    }
+}
+
+/// Metrics in Prometheus text format. They aren't authenticated, so the server should be deployed
+/// so that `/metrics` is only reachable from the internal network.
+#[get("/metrics")]
+pub async fn metrics_text(store: State<'_, Storage>) -> Content<String> {
+    match store.count_active_attempts().await {
+        Ok(count) => metrics::ACTIVE_ATTEMPTS.set(count as i64),
+        Err(e) => warn!("Unable to count active attempts: {}", e),
+    }
+
+    let content_type = metrics::content_type();
+    let content_type = content_type.parse::<ContentType>().unwrap_or(ContentType::Plain);
+
+    Content(content_type, metrics::encode())
+}
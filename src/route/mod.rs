@@ -3,20 +3,34 @@ use rocket::{Rocket, Route};
 mod users;
 mod files;
 mod quiz;
+mod tokens;
+mod attempt;
+mod course;
+mod convert;
+mod health;
 
 use users::*;
 use files::*;
 use quiz::*;
+use tokens::*;
+use attempt::*;
+use course::*;
+use convert::*;
+use health::*;
+
+pub use users::{remove_user, UserAuthInfo};
 use crate::error::{Problem, problems};
-use uuid::Uuid;
+use crate::logging::traced;
+use std::str::FromStr;
 
+/// Parses a plain `Uuid` or a typed ID from a query value. Path segments use `FromParam` instead.
 #[inline]
-pub fn parse_uuid<'r>(id: &String) -> Result<Uuid, Problem> {
-    match Uuid::parse_str(id.clone().as_str()) {
+pub fn parse_id<T: FromStr>(id: &str) -> Result<T, Problem> {
+    match id.parse() {
         Ok(it) => Ok(it),
         Err(_) => Err(
             problems::parse_problem()
-                .insert_serialized("parsed", id.clone())
+                .insert_serialized("parsed", id)
                 .detail("UUID parsing failed.")
                 .clone()
         )
@@ -29,14 +43,45 @@ pub fn user_routes() -> Vec<Route> {
         user_get,
         user_create,
         user_delete,
+        user_role_grant,
+        user_role_revoke,
     ]
 }
 
 pub fn mount_routes(rocket: Rocket) -> Rocket {
     rocket
-        .mount("/user", user_routes())
-        .mount("/login", routes![app, login_submit])
-        .mount("/api", routes![app])
-        .mount("/quiz", routes![quiz_create, quiz_info, quiz_delete])
-        .mount("/", routes![app, app_path])
+        .mount("/user", traced(user_routes()))
+        .mount("/login", traced(routes![app, login_submit]))
+        .mount("/api", traced(routes![app]))
+        .mount("/quiz", traced(routes![
+            quiz_create,
+            quiz_info,
+            quiz_delete,
+            quiz_update,
+            quiz_collaborator_set,
+            quiz_collaborator_remove,
+            quiz_participant_add,
+            quiz_participant_remove,
+            attempt_list,
+            attempt_start,
+            attempt_get,
+            attempt_get_user,
+            attempt_submit,
+            attempt_finish,
+            attempt_delete,
+            attempt_grade,
+            quiz_export,
+        ]))
+        .mount("/import", traced(routes![quiz_import]))
+        .mount("/course", traced(routes![
+            course_create,
+            course_get,
+            course_delete,
+            course_enrollments,
+            course_enroll,
+            course_unenroll,
+            course_roster,
+        ]))
+        .mount("/token", traced(routes![token_create, token_list, token_revoke]))
+        .mount("/", traced(routes![app, app_path, healthz, readyz, metrics_text]))
 }
@@ -1,95 +1,190 @@
 use rocket_contrib::json::Json;
-use crate::data::{Quiz, QUIZ_COLLECTION_NAME};
-use rocket::http::CookieJar;
+use crate::data::{Capability, Collaborator, Quiz};
+use rocket::http::Status;
 use rocket::State;
-use mongodb::Database;
-use crate::config::Config;
-use crate::error::{Problem, problems};
+use crate::body::JsonBody;
+use crate::error::Problem;
 use crate::jwt::{UserRolesToken, auth_problem};
-use crate::role::Role;
-use uuid::Uuid;
-use crate::route::parse_uuid;
-use bson::{Document, from_bson, Bson, doc};
-use bson::spec::BinarySubtype;
-
-// pub static PART_COLLECTION_NAME: &'static str = "parts";
-// pub static PARTICIPANT_COLLECTION_NAME: &'static str = "participants";
-// pub static QUIZ_COLLECTION_NAME: &'static str = "quizzes";
-
-#[post("/", format = "application/json", data = "<quiz>")]
-pub async fn quiz_create<'a>(quiz: Json<Quiz>, auth: UserRolesToken, db: State<'_, Database>) -> Result<(), Problem> {
-    if !auth.has_min_role(Role::Author) {
-        return Err(auth_problem("Permission level too low."));
-    }
-
-    db.collection(QUIZ_COLLECTION_NAME)
-        .insert_one(bson::to_document(&quiz.0)
-                        .expect("Unable to serialize Quiz struct into BSON."),
-                    None).await
-        .map_err(|e| Problem::from(e))?;
+use crate::policy::{Action, Policy, forbidden_problem};
+use crate::token::Scope;
+use crate::course::find_enrollment;
+use crate::store::{AttemptStore, QuizStore, Storage, UserStore};
+use crate::validation::validate_quiz;
+use crate::id::{QuizId, UserId};
+
+#[post("/", data = "<quiz>")]
+pub async fn quiz_create<'a>(quiz: Result<JsonBody<Quiz>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<(), Problem> {
+    let quiz = quiz?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.authorize(&auth, Action::Create, enrollment.as_ref())?;
+    validate_quiz(&quiz)?;
+
+    store.insert_quiz(&quiz.0).await?;
 
     Ok(())
 }
 
 #[inline]
-pub fn quiz_id_filter(id: Uuid) -> Document {
-    doc! {
-        "id": Bson::Binary(bson::Binary {
-            subtype: BinarySubtype::Uuid,
-            bytes: id.as_bytes().to_vec(),
-        })
-    }
+pub fn quiz_not_found(id: QuizId) -> Problem {
+    Problem::new_untyped(
+        Status::NotFound,
+        "Quiz doesn't exist."
+    )
+        .insert_serialized("id", id.to_string())
+        .clone()
 }
 
 #[post("/<id>")]
-pub async fn quiz_info<'a>(id: String, db: State<'_, Database>) -> Result<Option<Json<Quiz>>, Problem> {
-    let uuid = parse_uuid(&id)?;
-
-    let quiz_document = db.collection(QUIZ_COLLECTION_NAME).find_one(
-        quiz_id_filter(uuid),
-        None,
-    ).await.expect("Unable to query by id.");
-
-    let quiz: Option<Quiz> = match quiz_document {
-        Some(doc) => Some(
-            from_bson(Bson::Document(doc))
-                .map_err(|e| Problem::from(e))?
-        ),
-        None => None
+pub async fn quiz_info<'a>(id: QuizId, auth: Option<UserRolesToken>, store: State<'_, Storage>) -> Result<Option<Json<Quiz>>, Problem> {
+    let mut quiz = match store.find_quiz(id).await? {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+
+    let show_answers = match auth {
+        Some(auth) => {
+            let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+            quiz.authorize(&auth, Action::Read, enrollment.as_ref())?;
+
+            let attempt = if quiz.show_answer {
+                store.find_attempt(quiz.id, auth.user).await?
+            } else {
+                None
+            };
+            quiz.may_see_answers(&auth, enrollment.as_ref(), attempt.as_ref())
+        }
+        None if quiz.public => false,
+        None => return Err(auth_problem("Quiz isn't public.")),
     };
 
-    Ok(quiz.map(|u| Json(u)))
+    if !show_answers {
+        quiz.hide_answers();
+    }
+
+    Ok(Some(Json(quiz)))
 }
 
 
 #[delete("/<id>")]
-pub async fn quiz_delete<'a>(id: String, auth: UserRolesToken, db: State<'_, Database>) -> Result<Option<String>, Problem> {
-    if !auth.has_min_role(Role::Author) {
-        return Err(auth_problem("Permission level too low."));
+pub async fn quiz_delete<'a>(id: QuizId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Option<String>, Problem> {
+    let quiz = match store.find_quiz(id).await? {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+
+    quiz.authorize(&auth, Action::Delete, None)?;
+
+    store.delete_quiz(id).await?;
+    store.delete_quiz_attempts(id).await?;
+
+    Ok(Some(id.to_string()))
+}
+
+pub async fn load_quiz(store: &Storage, id: QuizId) -> Result<Quiz, Problem> {
+    match store.find_quiz(id).await? {
+        Some(it) => Ok(it),
+        None => Err(quiz_not_found(id)),
     }
+}
 
-    let uuid = parse_uuid(&id)?;
+#[inline]
+fn require_owner(quiz: &Quiz, auth: &UserRolesToken) -> Result<(), Problem> {
+    auth.require_scope(Scope::QuizWrite)?;
+    if !quiz.is_owned_by(auth) {
+        return Err(forbidden_problem("Only quiz owner can manage collaborators."));
+    }
+    Ok(())
+}
 
-    let quiz_document = db.collection(QUIZ_COLLECTION_NAME).find_one(
-        quiz_id_filter(uuid),
-        None,
-    ).await.expect("Unable to query by id.");
+async fn load_collaborator_user(store: &Storage, user: UserId) -> Result<UserId, Problem> {
+    match store.find_user(user).await? {
+        Some(it) => Ok(it.id),
+        None => Err(
+            Problem::new_untyped(
+                Status::NotFound,
+                "User doesn't exist."
+            )
+                .insert_serialized("id", user.to_string())
+                .clone()
+        ),
+    }
+}
 
-    let quiz: Quiz = match quiz_document {
-        Some(doc) => from_bson(Bson::Document(doc))
-                .map_err(|e| Problem::from(e))?,
-        None => return Ok(None)
+/// Replaces quiz content. Ownership, collaborators and participants are kept as they were.
+#[put("/<id>", data = "<update>")]
+pub async fn quiz_update(id: QuizId, update: Result<JsonBody<Quiz>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Quiz>, Problem> {
+    let update = update?;
+    let quiz = load_quiz(&store, id).await?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.authorize(&auth, Action::Update, enrollment.as_ref())?;
+
+    let updated = Quiz {
+        id: quiz.id,
+        author: quiz.author,
+        created: quiz.created,
+        course: quiz.course,
+        participants: quiz.participants.clone(),
+        collaborators: quiz.collaborators.clone(),
+        ..update.0
     };
+    validate_quiz(&updated)?;
+
+    updated_quiz(store.update_quiz(&updated).await?, id)
+}
+
+#[put("/<id>/collaborators/<user>", data = "<capabilities>")]
+pub async fn quiz_collaborator_set(id: QuizId, user: UserId, capabilities: Result<JsonBody<Vec<Capability>>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Quiz>, Problem> {
+    let capabilities = capabilities?;
+    let quiz = load_quiz(&store, id).await?;
+    require_owner(&quiz, &auth)?;
 
-    if !auth.has_min_role(Role::Admin) && quiz.author != auth.user {
-        return Err(auth_problem("Quiz not owned by user."));
+    let user = load_collaborator_user(&store, user).await?;
+    if user == quiz.author {
+        return Err(forbidden_problem("Quiz author already has every capability."));
     }
 
-    db.collection(QUIZ_COLLECTION_NAME).delete_one(
-        quiz_id_filter(uuid),
-        None,
-    ).await
-        .map_err(|e| Problem::from(e))?;
+    let collaborator = Collaborator {
+        user,
+        capabilities: capabilities.0,
+    };
+    updated_quiz(store.set_collaborator(id, &collaborator).await?, id)
+}
+
+#[delete("/<id>/collaborators/<user>")]
+pub async fn quiz_collaborator_remove(id: QuizId, user: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Quiz>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    require_owner(&quiz, &auth)?;
+
+    updated_quiz(store.remove_collaborator(id, user).await?, id)
+}
 
-    Ok(Some(uuid.to_string()))
+#[put("/<id>/participants/<user>")]
+pub async fn quiz_participant_add(id: QuizId, user: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Quiz>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    auth.require_scope(Scope::QuizWrite)?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.require_capability(&auth, enrollment.as_ref(), Capability::ManageParticipants)?;
+
+    let user = load_collaborator_user(&store, user).await?;
+
+    updated_quiz(store.add_quiz_participant(id, user).await?, id)
+}
+
+#[delete("/<id>/participants/<user>")]
+pub async fn quiz_participant_remove(id: QuizId, user: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Quiz>, Problem> {
+    let quiz = load_quiz(&store, id).await?;
+    auth.require_scope(Scope::QuizWrite)?;
+    let enrollment = find_enrollment(&store, quiz.course, auth.user).await?;
+    quiz.require_capability(&auth, enrollment.as_ref(), Capability::ManageParticipants)?;
+
+    updated_quiz(store.remove_quiz_participant(id, user).await?, id)
+}
+
+/// Quiz as it is after an update, which finds nothing if it was deleted after it was loaded.
+#[inline]
+fn updated_quiz(quiz: Option<Quiz>, id: QuizId) -> Result<Json<Quiz>, Problem> {
+    match quiz {
+        Some(it) => Ok(Json(it)),
+        None => Err(quiz_not_found(id)),
+    }
 }
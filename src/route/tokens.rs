@@ -0,0 +1,117 @@
+use chrono::{DateTime, Duration, Utc};
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::body::JsonBody;
+use crate::error::Problem;
+use crate::jwt::{UserRolesToken, auth_problem};
+use crate::logging::SECURITY_TARGET;
+use crate::route::parse_id;
+use crate::store::{Storage, TokenStore};
+use crate::token::{ApiToken, Scope};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenCreateInfo {
+    name: String,
+    scopes: Vec<Scope>,
+    #[serde(default)]
+    expires: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    info: ApiToken,
+    jwt: String,
+}
+
+#[inline]
+fn bad_token_problem<S: Into<String>>(detail: S) -> Problem {
+    Problem::new_untyped(
+        Status::BadRequest,
+        "Bad API token request."
+    )
+        .detail(detail)
+        .clone()
+}
+
+#[inline]
+fn token_not_found(id: String) -> Problem {
+    Problem::new_untyped(
+        Status::NotFound,
+        "API token doesn't exist."
+    )
+        .insert_serialized("id", id)
+        .clone()
+}
+
+/// API tokens can only be managed from an interactive session so that a leaked token can't be
+/// used to mint new ones.
+#[inline]
+fn require_session(auth: &UserRolesToken) -> Result<(), Problem> {
+    if auth.is_api_token() {
+        return Err(auth_problem("API tokens can't be managed using an API token."));
+    }
+    Ok(())
+}
+
+#[post("/", data = "<info>")]
+pub async fn token_create(info: Result<JsonBody<TokenCreateInfo>, Problem>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<CreatedApiToken>, Problem> {
+    let info = info?;
+    require_session(&auth)?;
+
+    if info.name.trim().is_empty() || info.name.len() > 64 {
+        return Err(bad_token_problem("Token name must be between 1 and 64 characters (bytes) long."));
+    }
+
+    if info.scopes.is_empty() {
+        return Err(bad_token_problem("Token must be granted at least one scope."));
+    }
+
+    let now = Utc::now();
+    let expires = info.expires.unwrap_or(now + Duration::days(90));
+    if expires <= now {
+        return Err(bad_token_problem("Token expiry must be in the future."));
+    }
+    if expires > now + Duration::days(365) {
+        return Err(bad_token_problem("Token can't be valid for longer than a year."));
+    }
+
+    let token = ApiToken::new(auth.user, info.name.clone(), info.scopes.clone(), expires);
+
+    store.insert_token(&token).await?;
+    log::info!(target: SECURITY_TARGET, "{} created API token {} with scopes {:?}", auth.user, token.id, token.scopes);
+
+    let jwt = UserRolesToken::for_api_token(&auth, &token).encode_jwt()?;
+
+    Ok(Json(CreatedApiToken {
+        info: token,
+        jwt,
+    }))
+}
+
+#[get("/")]
+pub async fn token_list(auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<Vec<ApiToken>>, Problem> {
+    require_session(&auth)?;
+
+    let tokens = store.list_tokens(auth.user).await?;
+
+    Ok(Json(tokens))
+}
+
+#[delete("/<id>")]
+pub async fn token_revoke(id: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Json<ApiToken>, Problem> {
+    require_session(&auth)?;
+
+    let uuid: Uuid = parse_id(&id)?;
+
+    match store.revoke_token(uuid, auth.user).await? {
+        Some(token) => {
+            log::info!(target: SECURITY_TARGET, "{} revoked API token {}", auth.user, token.id);
+            Ok(Json(token))
+        }
+        None => Err(token_not_found(id))
+    }
+}
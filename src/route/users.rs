@@ -1,17 +1,20 @@
-use bson::{Bson, doc, Document, from_bson, from_document};
-use bson::spec::BinarySubtype;
-use mongodb::Database;
 use rocket::{State};
 use rocket::http::{CookieJar, Status};
 use rocket::request::{Form, FromForm};
-use uuid::Uuid;
 
-use crate::jwt::UserRolesToken;
-use crate::user::{User, USER_COLLECTION_NAME};
+use crate::jwt::{UserRolesToken, auth_problem};
+use crate::user::{hash_password, User};
 use crate::role::Role;
+use crate::audit::{AuditAction, AuditEntry};
+use crate::token::Scope;
+use crate::policy::{Action, Policy};
 use crate::error::{Problem, problems};
-use crate::config::Config;
-use crate::route::parse_uuid;
+use crate::id::UserId;
+use crate::config::LiveConfig;
+use crate::logging::SECURITY_TARGET;
+use crate::metrics;
+use crate::route::parse_id;
+use crate::store::{AttemptStore, QuizStore, Storage, StoreError, UserStore};
 
 /* TODO: Support paging
 // Responder isn't implemented for Vec.
@@ -40,40 +43,14 @@ pub async fn user_list(db: State<'_, Database>) -> Result<Vec<User>, Problem> {
 }
 */
 
-#[inline]
-pub fn filter_user_id(id: Uuid) -> Document {
-    doc! {
-        "id": Bson::Binary(bson::Binary {
-            subtype: BinarySubtype::Uuid,
-            bytes: id.as_bytes().to_vec(),
-        })
-    }
-}
-
-#[inline]
-pub fn filter_user_username(username: String) -> Document {
-    doc! {
-        "username": username
-    }
-}
-
 #[get("/<id>")]
-pub async fn user_get(id: String, db: State<'_, Database>) -> Result<Option<User>, Problem> {
-    let uuid = parse_uuid(&id)?;
-
-    let user_document = db.collection(USER_COLLECTION_NAME).find_one(
-        filter_user_id(uuid),
-        None,
-    ).await
-        .map_err(|e| Problem::from(e))?;
-
-    match user_document {
-        Some(doc) => Ok(Some(
-            from_bson(Bson::Document(doc))
-                .map_err(|e| Problem::from(e))?
-        )),
-        None => Ok(None)
+pub async fn user_get(id: UserId, auth: UserRolesToken, store: State<'_, Storage>) -> Result<Option<User>, Problem> {
+    let user = store.find_user(id).await?;
+    if let Some(user) = &user {
+        user.authorize(&auth, Action::Read, ())?;
     }
+
+    Ok(user)
 }
 
 #[derive(Clone, FromForm)]
@@ -93,6 +70,17 @@ fn bad_username_problem<Username: Into<String>, Detail: Into<String>>(username:
         .clone()
 }
 
+#[inline]
+fn username_taken_problem<Username: Into<String>>(username: Username) -> Problem {
+    Problem::new_untyped(
+        Status::Conflict,
+        "Username is taken."
+    )
+        .insert_serialized("username", username.into())
+        .detail("User with that username already exists.")
+        .clone()
+}
+
 #[inline]
 fn bad_password_problem<S: Into<String>>(detail: S) -> Problem {
     Problem::new_untyped(
@@ -104,7 +92,7 @@ fn bad_password_problem<S: Into<String>>(detail: S) -> Problem {
 }
 
 #[inline]
-fn user_not_found(id: Uuid) -> Problem {
+fn user_not_found(id: UserId) -> Problem {
     Problem::new_untyped(
         Status::NotFound,
         "User doesn't exist."
@@ -121,6 +109,13 @@ fn login_problem() -> Problem {
 }
 
 impl UserAuthInfo {
+    pub fn new(username: String, password: String) -> UserAuthInfo {
+        UserAuthInfo {
+            username,
+            password,
+        }
+    }
+
     pub fn validate(&self) -> Result<(), Problem> {
 
         if self.username.len() < 5 {
@@ -158,7 +153,7 @@ impl UserAuthInfo {
 }
 
 #[post("/", data = "<create_user>")]
-pub async fn user_create<'a>(create_user: Form<UserAuthInfo>, cookies: &'a CookieJar<'_>, db: State<'_, Database>, c: State<'_, Config>) -> Result<User, Problem> {
+pub async fn user_create<'a>(create_user: Form<UserAuthInfo>, cookies: &'a CookieJar<'_>, store: State<'_, Storage>, c: State<'_, LiveConfig>) -> Result<User, Problem> {
     create_user.validate()?;
 
     let mut user = User::new(
@@ -166,27 +161,18 @@ pub async fn user_create<'a>(create_user: Form<UserAuthInfo>, cookies: &'a Cooki
         create_user.password.clone(),
     );
 
-    if c.admin_usernames.contains(&user.username) {
+    if c.get().admin_usernames.contains(&user.username) {
         user.user_roles.push(Role::Admin);
     }
 
-    if db.collection(USER_COLLECTION_NAME).find_one(
-        filter_user_username(user.username.clone()),
-        None,
-    ).await.expect("Unable to query by username.").is_some() {
-        return Err(
-            bad_username_problem(
-                user.username.clone(),
-                "User with that username already exists."
-            )
-        );
+    match store.insert_user(&user).await {
+        Ok(()) => {}
+        Err(StoreError::Duplicate(key)) if key == "username" => {
+            return Err(username_taken_problem(user.username.clone()));
+        }
+        Err(e) => return Err(e.into()),
     }
-
-    db.collection(USER_COLLECTION_NAME)
-        .insert_one(bson::to_document(&user)
-                        .expect("Unable to serialize User struct into BSON."),
-                    None).await
-        .map_err(|e| Problem::from(e))?;
+    log::info!(target: SECURITY_TARGET, "User {} registered as '{}' with roles {:?}", user.id, user.username, user.user_roles);
 
     let urt = UserRolesToken::new(&user.clone());
     cookies.add_private(urt.cookie()?);
@@ -195,41 +181,30 @@ pub async fn user_create<'a>(create_user: Form<UserAuthInfo>, cookies: &'a Cooki
 }
 
 #[post("/", data = "<login_user>")]
-pub async fn login_submit<'a>(login_user: Form<UserAuthInfo>, cookies: &'a CookieJar<'_>, db: State<'_, Database>) -> Result<User, Problem> {
+pub async fn login_submit<'a>(login_user: Form<UserAuthInfo>, cookies: &'a CookieJar<'_>, store: State<'_, Storage>) -> Result<User, Problem> {
     login_user.validate()?;
 
-    let user = User::new(
-        login_user.username.clone(),
-        login_user.password.clone(),
-    );
+    let pw_hash = hash_password(&login_user.password);
 
-    let user_document = db.collection(USER_COLLECTION_NAME).find_one(
-        filter_user_username(user.username.clone()),
-        None,
-    ).await.expect("Unable to query by username.");
-
-    let db_user: Option<User> = match user_document {
-        Some(doc) => Some(
-            from_bson(Bson::Document(doc))
-                .map_err(|e| Problem::from(e))?
-        ),
-        None => None
-    };
+    let db_user = store.find_user_by_username(&login_user.username).await?;
 
     let password_correct = match db_user.clone() {
         Some(it) => {
-            it.pw_hash == user.pw_hash
+            it.pw_hash == pw_hash
         }
         None => {
             false
         }
     };
 
-    if password_correct {
+    if !password_correct {
+        log::warn!(target: SECURITY_TARGET, "Failed login as '{}'", login_user.username);
+        metrics::LOGIN_FAILURES.inc();
         return Err(login_problem())
     }
 
     let existing_db_user = db_user.expect("Should've returned earlier.");
+    log::info!(target: SECURITY_TARGET, "User {} logged in", existing_db_user.id);
 
     let urt = UserRolesToken::new(&existing_db_user.clone());
     cookies.add_private(urt.cookie()?);
@@ -237,28 +212,191 @@ pub async fn login_submit<'a>(login_user: Form<UserAuthInfo>, cookies: &'a Cooki
     Ok(existing_db_user)
 }
 
-#[delete("/<id>")]
-pub async fn user_delete(id: String, db: State<'_, Database>) -> Result<User, Problem> {
-    let uuid = parse_uuid(&id)?;
+#[inline]
+fn authored_quizzes_problem(id: UserId, count: u64) -> Problem {
+    Problem::new_untyped(
+        Status::Conflict,
+        "User has authored quizzes."
+    )
+        .insert_serialized("id", id.to_string())
+        .insert_serialized("quizzes", count)
+        .detail("Specify whether authored quizzes should be transferred (quizzes=transfer&transfer_to=<id>) or deleted (quizzes=cascade).")
+        .clone()
+}
 
-    let removed_document = db.collection(USER_COLLECTION_NAME).find_one_and_delete(
-        filter_user_id(uuid),
-        None,
-    ).await
-        .map_err(|e| Problem::from(e))?;
+/// Handles quizzes authored by a user that's being deleted.
+async fn handle_authored_quizzes(store: &Storage, id: UserId, quizzes: Option<String>, transfer_to: Option<String>) -> Result<(), Problem> {
+    let authored = store.count_quizzes_by_author(id).await?;
+
+    if authored == 0 {
+        return Ok(());
+    }
 
+    match quizzes.as_deref().unwrap_or("block") {
+        "block" => Err(authored_quizzes_problem(id, authored)),
+        "transfer" => {
+            let new_author = match transfer_to {
+                Some(it) => parse_id(&it)?,
+                None => return Err(
+                    problems::parse_problem()
+                        .detail("Quiz transfer requires 'transfer_to' user ID.")
+                        .clone()
+                ),
+            };
+
+            let new_author_user = match store.find_user(new_author).await? {
+                Some(it) => it,
+                None => return Err(user_not_found(new_author)),
+            };
+
+            if new_author == id || !new_author_user.user_roles.iter().any(|r| r.can_author()) {
+                return Err(
+                    problems::parse_problem()
+                        .insert_serialized("transfer_to", new_author.to_string())
+                        .detail("Quizzes can only be transferred to another author.")
+                        .clone()
+                );
+            }
 
-    match removed_document {
-        Some(user_document) => {
-            let user: User = from_document(user_document)
-                .expect("Unable to deserialize User struct from BSON.");
+            store.transfer_quizzes(id, new_author).await?;
 
-            Ok(user)
+            Ok(())
         }
-        None => {
-            Err(
-                user_not_found(uuid)
-            )
+        "cascade" => {
+            for quiz in store.find_quizzes_by_author(id).await? {
+                store.delete_quiz_attempts(quiz.id).await?;
+            }
+
+            store.delete_quizzes_by_author(id).await?;
+
+            Ok(())
         }
+        other => Err(
+            problems::parse_problem()
+                .insert_serialized("quizzes", other)
+                .detail("Authored quizzes can be handled with: block, transfer, cascade.")
+                .clone()
+        ),
+    }
+}
+
+/// Deletes a user along with their attempts, API tokens and enrollments. Last admin can't be
+/// deleted, `quizzes` and `transfer_to` are handled like in `user_delete`.
+pub async fn remove_user(store: &Storage, user: &User, quizzes: Option<String>, transfer_to: Option<String>) -> Result<(), Problem> {
+    let id = user.id;
+
+    // Fails early so authored quizzes aren't handled for nothing. Deletion checks this again
+    // atomically, in case another admin is concurrently removed.
+    if user.user_roles.contains(&Role::Admin) && store.count_users_with_role(Role::Admin).await? <= 1 {
+        return Err(last_admin_problem(id));
+    }
+
+    handle_authored_quizzes(store, id, quizzes, transfer_to).await?;
+
+    match store.delete_user(id).await {
+        Ok(_) => Ok(()),
+        Err(StoreError::LastAdmin) => Err(last_admin_problem(id)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Deletes a user along with their attempts and API tokens.
+///
+/// If the user authored any quizzes, `quizzes` query parameter decides whether deletion is
+/// blocked (default), quizzes are transferred to `transfer_to` author, or deleted as well.
+#[delete("/<id>?<quizzes>&<transfer_to>")]
+pub async fn user_delete(id: UserId, quizzes: Option<String>, transfer_to: Option<String>, auth: UserRolesToken, store: State<'_, Storage>) -> Result<User, Problem> {
+    let user = match store.find_user(id).await? {
+        Some(it) => it,
+        None => return Err(user_not_found(id)),
+    };
+    user.authorize(&auth, Action::Delete, ())?;
+
+    remove_user(&store, &user, quizzes, transfer_to).await?;
+    log::info!(target: SECURITY_TARGET, "{} deleted user {}", auth.user, id);
+
+    Ok(user)
+}
+
+#[inline]
+fn bad_role_problem(role: String) -> Problem {
+    problems::parse_problem()
+        .insert_serialized("role", role)
+        .detail("Role must be one of: normal, author, admin.")
+        .clone()
+}
+
+#[inline]
+fn last_admin_problem(id: UserId) -> Problem {
+    Problem::new_untyped(
+        Status::Conflict,
+        "Last admin can't be demoted."
+    )
+        .insert_serialized("id", id.to_string())
+        .clone()
+}
+
+async fn revoke_role(store: &Storage, id: UserId, role: Role) -> Result<User, Problem> {
+    match store.remove_user_role(id, role).await {
+        Ok(Some(it)) => Ok(it),
+        Ok(None) => Err(user_not_found(id)),
+        Err(StoreError::LastAdmin) => Err(last_admin_problem(id)),
+        Err(e) => Err(e.into()),
     }
 }
+
+#[inline]
+fn require_admin(auth: &UserRolesToken) -> Result<(), Problem> {
+    if !auth.has_min_role(Role::Admin) {
+        return Err(auth_problem("Permission level too low."));
+    }
+    auth.require_scope(Scope::UsersWrite)
+}
+
+#[inline]
+fn parse_role(role: String) -> Result<Role, Problem> {
+    role.parse::<Role>().map_err(|_| bad_role_problem(role.clone()))
+}
+
+/// Role changes take effect on the next request made by the affected user as roles in their
+/// session are refreshed whenever the session is used (see `UserRolesToken::from_request`).
+#[put("/<id>/roles/<role>")]
+pub async fn user_role_grant(id: UserId, role: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<User, Problem> {
+    require_admin(&auth)?;
+
+    let role = parse_role(role)?;
+
+    let user = match store.add_user_role(id, role).await? {
+        Some(it) => it,
+        None => return Err(user_not_found(id)),
+    };
+
+    AuditEntry::new(auth.user, id, AuditAction::RoleGranted(role))
+        .record(&store)
+        .await?;
+
+    Ok(user)
+}
+
+#[delete("/<id>/roles/<role>")]
+pub async fn user_role_revoke(id: UserId, role: String, auth: UserRolesToken, store: State<'_, Storage>) -> Result<User, Problem> {
+    require_admin(&auth)?;
+
+    let role = parse_role(role)?;
+
+    if role == Role::Normal {
+        return Err(
+            bad_role_problem(role.to_string())
+                .detail("Normal role can't be revoked.")
+                .clone()
+        );
+    }
+
+    let user = revoke_role(&store, id, role).await?;
+
+    AuditEntry::new(auth.user, id, AuditAction::RoleRevoked(role))
+        .record(&store)
+        .await?;
+
+    Ok(user)
+}
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sled::{IVec, Transactional, Tree};
+use sled::transaction::{ConflictableTransactionError, ConflictableTransactionResult, TransactionalTree, TransactionError, TransactionResult};
+use uuid::Uuid;
+
+use crate::audit::{AUDIT_COLLECTION_NAME, AuditEntry};
+use crate::course::{Course, COURSE_COLLECTION_NAME, Enrollment, ENROLLMENT_COLLECTION_NAME};
+use crate::data::{Answer, Collaborator, Grade, ParticipantInfo, PARTICIPANT_COLLECTION_NAME, Quiz, QUIZ_COLLECTION_NAME};
+use crate::id::{PartId, QuizId, UserId};
+use crate::role::Role;
+use crate::store::{AttemptStore, AuditStore, CourseStore, QuizStore, StatusStore, StoreError, StoreResult, TokenStore, UserStore};
+use crate::token::{API_TOKEN_COLLECTION_NAME, ApiToken};
+use crate::user::{User, USER_COLLECTION_NAME};
+
+/// Key of a value which belongs to two entities, e.g. an attempt of a user at a quiz. Values are
+/// keyed by the first ID so that they can be listed using a prefix scan.
+#[inline]
+fn pair_key<A: Into<Uuid>, B: Into<Uuid>>(first: A, second: B) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[..16].copy_from_slice(first.into().as_bytes());
+    key[16..].copy_from_slice(second.into().as_bytes());
+    key
+}
+
+fn get<T: DeserializeOwned>(tree: &Tree, key: &[u8]) -> StoreResult<Option<T>> {
+    match tree.get(key)? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+fn put<T: Serialize>(tree: &Tree, key: &[u8], value: &T) -> StoreResult<()> {
+    tree.insert(key, serde_json::to_vec(value)?)?;
+    Ok(())
+}
+
+fn values<T: DeserializeOwned>(iter: sled::Iter) -> StoreResult<Vec<T>> {
+    let mut result = vec![];
+    for entry in iter {
+        let (_, value) = entry?;
+        result.push(serde_json::from_slice(&value)?);
+    }
+    Ok(result)
+}
+
+/// Stores a value unless `key` is already taken, in which case the write fails with
+/// `StoreError::Duplicate` naming the `index`.
+fn put_new<T: Serialize>(tree: &Tree, key: &[u8], value: &T, index: &str) -> StoreResult<()> {
+    match tree.compare_and_swap(key, None as Option<&[u8]>, Some(serde_json::to_vec(value)?))? {
+        Ok(()) => Ok(()),
+        Err(_) => Err(StoreError::Duplicate(index.to_string())),
+    }
+}
+
+/// Removes every value in `tree` that satisfies `predicate`.
+fn remove_where<T: DeserializeOwned, P: Fn(&T) -> bool>(tree: &Tree, predicate: P) -> StoreResult<()> {
+    for key in keys_where(tree, predicate)? {
+        tree.remove(key)?;
+    }
+    Ok(())
+}
+
+/// Keys of every value in `tree` that satisfies `predicate`.
+fn keys_where<T: DeserializeOwned, P: Fn(&T) -> bool>(tree: &Tree, predicate: P) -> StoreResult<Vec<IVec>> {
+    let mut keys = vec![];
+    for entry in tree.iter() {
+        let (key, value) = entry?;
+        if predicate(&serde_json::from_slice(&value)?) {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
+
+/// Keys of values in a tree keyed by `pair_key` whose second ID is `user`.
+fn keys_of_user(tree: &Tree, user: UserId) -> StoreResult<Vec<IVec>> {
+    let mut keys = vec![];
+    for entry in tree.iter() {
+        let (key, _) = entry?;
+        if &key[16..] == user.as_bytes() {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
+
+/// Atomically modifies a stored value. `modify` returns `false` if the value isn't in the state
+/// the update expects, in which case it's left unchanged and `None` is returned.
+fn update<T, F>(tree: &Tree, key: &[u8], mut modify: F) -> StoreResult<Option<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnMut(&mut T) -> bool,
+{
+    loop {
+        let current = match tree.get(key)? {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+
+        let mut value: T = serde_json::from_slice(&current)?;
+        if !modify(&mut value) {
+            return Ok(None);
+        }
+
+        let swapped = tree.compare_and_swap(key, Some(current), Some(serde_json::to_vec(&value)?))?;
+        if swapped.is_ok() {
+            return Ok(Some(value));
+        }
+        // Value was changed concurrently, retry with the new one.
+    }
+}
+
+/// Applies `modify` to every value in `tree`. Values for which it returns `false` aren't written.
+fn update_all<T, F>(tree: &Tree, modify: F) -> StoreResult<()>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Fn(&mut T) -> bool,
+{
+    for entry in tree.iter() {
+        let (key, _) = entry?;
+        update(tree, &key, |value: &mut T| modify(value))?;
+    }
+    Ok(())
+}
+
+type TxResult<T> = ConflictableTransactionResult<T, StoreError>;
+
+/// Aborts a transaction with an error.
+#[inline]
+fn abort<E: Into<StoreError>>(e: E) -> ConflictableTransactionError<StoreError> {
+    ConflictableTransactionError::Abort(e.into())
+}
+
+/// Result of a transaction, with the error it was aborted with if it was.
+fn committed<T>(result: TransactionResult<T, StoreError>) -> StoreResult<T> {
+    result.map_err(|e| match e {
+        TransactionError::Abort(e) => e,
+        TransactionError::Storage(e) => StoreError::Embedded(e),
+    })
+}
+
+fn tx_get<T: DeserializeOwned>(tree: &TransactionalTree, key: &[u8]) -> TxResult<Option<T>> {
+    match tree.get(key)? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value).map_err(abort)?)),
+        None => Ok(None),
+    }
+}
+
+fn tx_put<T: Serialize>(tree: &TransactionalTree, key: &[u8], value: &T) -> TxResult<()> {
+    tree.insert(key, serde_json::to_vec(value).map_err(abort)?)?;
+    Ok(())
+}
+
+/// How long a count of active attempts is reused, see `EmbeddedStore::active_attempts`.
+const ACTIVE_ATTEMPTS_TTL: Duration = Duration::from_secs(30);
+
+/// Key of the set of admin IDs in `guards` tree.
+static ADMINS_KEY: &'static [u8] = b"admins";
+
+fn tx_admins(guards: &TransactionalTree) -> TxResult<Vec<UserId>> {
+    Ok(tx_get(guards, ADMINS_KEY)?.unwrap_or_default())
+}
+
+fn tx_add_admin(guards: &TransactionalTree, id: UserId) -> TxResult<()> {
+    let mut admins = tx_admins(guards)?;
+    if !admins.contains(&id) {
+        admins.push(id);
+        tx_put(guards, ADMINS_KEY, &admins)?;
+    }
+    Ok(())
+}
+
+/// Removes an admin from the set, unless they're the last one.
+fn tx_remove_admin(guards: &TransactionalTree, id: UserId) -> TxResult<()> {
+    let mut admins = tx_admins(guards)?;
+    if admins == [id] {
+        return Err(abort(StoreError::LastAdmin));
+    }
+    admins.retain(|admin| admin != &id);
+    tx_put(guards, ADMINS_KEY, &admins)
+}
+
+/// Storage kept in a local directory, for deployments which don't want to run MongoDB.
+///
+/// Every collection is stored in its own tree with JSON encoded values. Lookups by anything other
+/// than the key scan the whole tree, which is fine for the amount of data a single class produces.
+pub struct EmbeddedStore {
+    users: Tree,
+    /// Maps usernames to user IDs, which keeps usernames unique.
+    usernames: Tree,
+    quizzes: Tree,
+    attempts: Tree,
+    courses: Tree,
+    enrollments: Tree,
+    tokens: Tree,
+    audit: Tree,
+    /// Values which keep invariants spanning several records, like the set of admins which
+    /// mustn't become empty. They're only changed by transactions.
+    guards: Tree,
+    /// Number of unfinished attempts and when it was counted. Counting reads every attempt, so a
+    /// count is reused for a while instead of being repeated on every metrics scrape.
+    active_attempts: Mutex<Option<(Instant, u64)>>,
+}
+
+impl EmbeddedStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> StoreResult<EmbeddedStore> {
+        let db = sled::open(path)?;
+
+        let users = db.open_tree(USER_COLLECTION_NAME)?;
+        let usernames = db.open_tree("usernames")?;
+        // Databases created before usernames were indexed don't have the index populated.
+        if usernames.is_empty() {
+            for user in values::<User>(users.iter())? {
+                usernames.insert(user.username.as_bytes(), user.id.as_bytes())?;
+            }
+        }
+
+        // Only one process can open the database, so nothing can change users while this runs.
+        let guards = db.open_tree("guards")?;
+        let admins: Vec<UserId> = values::<User>(users.iter())?
+            .into_iter()
+            .filter(|user| user.user_roles.contains(&Role::Admin))
+            .map(|user| user.id)
+            .collect();
+        put(&guards, ADMINS_KEY, &admins)?;
+
+        Ok(EmbeddedStore {
+            users,
+            usernames,
+            quizzes: db.open_tree(QUIZ_COLLECTION_NAME)?,
+            attempts: db.open_tree(PARTICIPANT_COLLECTION_NAME)?,
+            courses: db.open_tree(COURSE_COLLECTION_NAME)?,
+            enrollments: db.open_tree(ENROLLMENT_COLLECTION_NAME)?,
+            tokens: db.open_tree(API_TOKEN_COLLECTION_NAME)?,
+            audit: db.open_tree(AUDIT_COLLECTION_NAME)?,
+            guards,
+            active_attempts: Mutex::new(None),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl UserStore for EmbeddedStore {
+    async fn find_user(&self, id: UserId) -> StoreResult<Option<User>> {
+        get(&self.users, id.as_bytes())
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> StoreResult<Option<User>> {
+        match self.usernames.get(username.as_bytes())? {
+            Some(id) => get(&self.users, &id),
+            None => Ok(None),
+        }
+    }
+
+    async fn insert_user(&self, user: &User) -> StoreResult<()> {
+        let value = serde_json::to_vec(user)?;
+
+        committed((&self.users, &self.usernames, &self.guards).transaction(|(users, usernames, guards)| {
+            if usernames.get(user.username.as_bytes())?.is_some() {
+                return Err(abort(StoreError::Duplicate("username".to_string())));
+            }
+            if users.get(user.id.as_bytes())?.is_some() {
+                return Err(abort(StoreError::Duplicate("id".to_string())));
+            }
+
+            usernames.insert(user.username.as_bytes(), &user.id.as_bytes()[..])?;
+            users.insert(&user.id.as_bytes()[..], value.clone())?;
+            if user.user_roles.contains(&Role::Admin) {
+                tx_add_admin(guards, user.id)?;
+            }
+            Ok(())
+        }))
+    }
+
+    /// Transactional trees can't be iterated, so keys of records which belong to the user are
+    /// collected first and removed by the same transaction which removes the user.
+    async fn delete_user(&self, id: UserId) -> StoreResult<bool> {
+        let attempt_keys = keys_of_user(&self.attempts, id)?;
+        let enrollment_keys = keys_of_user(&self.enrollments, id)?;
+        let token_keys = keys_where(&self.tokens, |token: &ApiToken| token.user == id)?;
+        let quiz_keys = keys_where(&self.quizzes, |quiz: &Quiz| quiz.participants.contains(&id))?;
+
+        let trees = (&self.users, &self.usernames, &self.guards, &self.attempts, &self.enrollments, &self.tokens, &self.quizzes);
+        committed(trees.transaction(|(users, usernames, guards, attempts, enrollments, tokens, quizzes)| {
+            let user: User = match tx_get(users, id.as_bytes())? {
+                Some(it) => it,
+                None => return Ok(false),
+            };
+
+            if user.user_roles.contains(&Role::Admin) {
+                tx_remove_admin(guards, id)?;
+            }
+            for key in &attempt_keys {
+                attempts.remove(key)?;
+            }
+            for key in &enrollment_keys {
+                enrollments.remove(key)?;
+            }
+            for key in &token_keys {
+                tokens.remove(key)?;
+            }
+            for key in &quiz_keys {
+                if let Some(mut quiz) = tx_get::<Quiz>(quizzes, key)? {
+                    quiz.participants.retain(|p| p != &id);
+                    tx_put(quizzes, key, &quiz)?;
+                }
+            }
+            usernames.remove(user.username.as_bytes())?;
+            users.remove(&id.as_bytes()[..])?;
+            Ok(true)
+        }))
+    }
+
+    async fn count_users_with_role(&self, role: Role) -> StoreResult<u64> {
+        let users: Vec<User> = values(self.users.iter())?;
+        Ok(users.iter().filter(|user| user.user_roles.contains(&role)).count() as u64)
+    }
+
+    async fn add_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>> {
+        committed((&self.users, &self.guards).transaction(|(users, guards)| {
+            let mut user: User = match tx_get(users, id.as_bytes())? {
+                Some(it) => it,
+                None => return Ok(None),
+            };
+
+            if !user.user_roles.contains(&role) {
+                user.user_roles.push(role);
+                tx_put(users, id.as_bytes(), &user)?;
+            }
+            if role == Role::Admin {
+                tx_add_admin(guards, id)?;
+            }
+            Ok(Some(user))
+        }))
+    }
+
+    async fn remove_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>> {
+        committed((&self.users, &self.guards).transaction(|(users, guards)| {
+            let mut user: User = match tx_get(users, id.as_bytes())? {
+                Some(it) => it,
+                None => return Ok(None),
+            };
+
+            if !user.user_roles.contains(&role) {
+                return Ok(Some(user));
+            }
+            if role == Role::Admin {
+                tx_remove_admin(guards, id)?;
+            }
+            user.user_roles.retain(|r| r != &role);
+            tx_put(users, id.as_bytes(), &user)?;
+            Ok(Some(user))
+        }))
+    }
+
+    async fn set_password(&self, id: UserId, pw_hash: [u8; 24]) -> StoreResult<Option<User>> {
+        update(&self.users, id.as_bytes(), |user: &mut User| {
+            user.pw_hash = pw_hash;
+            true
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl QuizStore for EmbeddedStore {
+    async fn find_quiz(&self, id: QuizId) -> StoreResult<Option<Quiz>> {
+        get(&self.quizzes, id.as_bytes())
+    }
+
+    async fn find_quizzes_by_author(&self, author: UserId) -> StoreResult<Vec<Quiz>> {
+        let quizzes: Vec<Quiz> = values(self.quizzes.iter())?;
+        Ok(quizzes.into_iter().filter(|quiz| quiz.author == author).collect())
+    }
+
+    async fn count_quizzes_by_author(&self, author: UserId) -> StoreResult<u64> {
+        Ok(self.find_quizzes_by_author(author).await?.len() as u64)
+    }
+
+    async fn insert_quiz(&self, quiz: &Quiz) -> StoreResult<()> {
+        put_new(&self.quizzes, quiz.id.as_bytes(), quiz, "id")
+    }
+
+    async fn update_quiz(&self, quiz: &Quiz) -> StoreResult<Option<Quiz>> {
+        update(&self.quizzes, quiz.id.as_bytes(), |stored: &mut Quiz| {
+            stored.replace_content(quiz);
+            true
+        })
+    }
+
+    async fn set_collaborator(&self, quiz: QuizId, collaborator: &Collaborator) -> StoreResult<Option<Quiz>> {
+        update(&self.quizzes, quiz.as_bytes(), |stored: &mut Quiz| {
+            stored.set_collaborator(collaborator.clone());
+            true
+        })
+    }
+
+    async fn remove_collaborator(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        update(&self.quizzes, quiz.as_bytes(), |stored: &mut Quiz| {
+            stored.collaborators.retain(|c| c.user != user);
+            true
+        })
+    }
+
+    async fn add_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        update(&self.quizzes, quiz.as_bytes(), |stored: &mut Quiz| {
+            if !stored.participants.contains(&user) {
+                stored.participants.push(user);
+            }
+            true
+        })
+    }
+
+    async fn remove_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        update(&self.quizzes, quiz.as_bytes(), |stored: &mut Quiz| {
+            stored.participants.retain(|p| p != &user);
+            true
+        })
+    }
+
+    async fn delete_quiz(&self, id: QuizId) -> StoreResult<bool> {
+        Ok(self.quizzes.remove(id.as_bytes())?.is_some())
+    }
+
+    async fn delete_quizzes_by_author(&self, author: UserId) -> StoreResult<()> {
+        remove_where(&self.quizzes, |quiz: &Quiz| quiz.author == author)
+    }
+
+    async fn transfer_quizzes(&self, from: UserId, to: UserId) -> StoreResult<()> {
+        update_all(&self.quizzes, |quiz: &mut Quiz| {
+            if quiz.author != from {
+                return false;
+            }
+            quiz.author = to;
+            true
+        })
+    }
+
+    async fn detach_course(&self, course: Uuid) -> StoreResult<()> {
+        update_all(&self.quizzes, |quiz: &mut Quiz| {
+            if quiz.course != Some(course) {
+                return false;
+            }
+            quiz.course = None;
+            true
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl AttemptStore for EmbeddedStore {
+    async fn find_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<ParticipantInfo>> {
+        get(&self.attempts, &pair_key(quiz, user))
+    }
+
+    async fn list_attempts(&self, quiz: QuizId) -> StoreResult<Vec<ParticipantInfo>> {
+        values(self.attempts.scan_prefix(quiz.as_bytes()))
+    }
+
+    async fn count_active_attempts(&self) -> StoreResult<u64> {
+        let mut cached = self.active_attempts.lock().expect("Active attempt count lock poisoned.");
+        if let Some((counted_on, count)) = *cached {
+            if counted_on.elapsed() < ACTIVE_ATTEMPTS_TTL {
+                return Ok(count);
+            }
+        }
+
+        /// Only the part of an attempt which is needed to count it, answers aren't decoded.
+        #[derive(Deserialize)]
+        struct AttemptState {
+            finished_on: Option<DateTime<Utc>>,
+        }
+
+        let mut count = 0;
+        for entry in self.attempts.iter() {
+            let (_, value) = entry?;
+            let attempt: AttemptState = serde_json::from_slice(&value)?;
+            if attempt.finished_on.is_none() {
+                count += 1;
+            }
+        }
+
+        *cached = Some((Instant::now(), count));
+        Ok(count)
+    }
+
+    async fn insert_attempt(&self, attempt: &ParticipantInfo) -> StoreResult<()> {
+        put_new(&self.attempts, &pair_key(attempt.quiz, attempt.id), attempt, "participant")
+    }
+
+    async fn delete_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<bool> {
+        Ok(self.attempts.remove(pair_key(quiz, user))?.is_some())
+    }
+
+    async fn delete_quiz_attempts(&self, quiz: QuizId) -> StoreResult<()> {
+        for entry in self.attempts.scan_prefix(quiz.as_bytes()) {
+            let (key, _) = entry?;
+            self.attempts.remove(key)?;
+        }
+        Ok(())
+    }
+
+    async fn set_answer(&self, quiz: QuizId, user: UserId, part: PartId, answer: &Answer) -> StoreResult<Option<ParticipantInfo>> {
+        update(&self.attempts, &pair_key(quiz, user), |attempt: &mut ParticipantInfo| {
+            if attempt.finished_on.is_some() {
+                return false;
+            }
+            attempt.answers.insert(part, answer.clone());
+            attempt.version += 1;
+            true
+        })
+    }
+
+    async fn finish_attempt(&self, quiz: QuizId, user: UserId, version: i64, finished_on: DateTime<Utc>, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>> {
+        update(&self.attempts, &pair_key(quiz, user), |attempt: &mut ParticipantInfo| {
+            if attempt.finished_on.is_some() || attempt.version != version {
+                return false;
+            }
+            attempt.finished_on = Some(finished_on);
+            attempt.grades = grades.clone();
+            attempt.version += 1;
+            true
+        })
+    }
+
+    async fn set_grades(&self, quiz: QuizId, user: UserId, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>> {
+        update(&self.attempts, &pair_key(quiz, user), |attempt: &mut ParticipantInfo| {
+            if attempt.finished_on.is_none() {
+                return false;
+            }
+            attempt.grades.extend(grades.iter().map(|(part, grade)| (*part, grade.clone())));
+            attempt.version += 1;
+            true
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl CourseStore for EmbeddedStore {
+    async fn find_course(&self, id: Uuid) -> StoreResult<Option<Course>> {
+        get(&self.courses, id.as_bytes())
+    }
+
+    async fn insert_course(&self, course: &Course) -> StoreResult<()> {
+        put_new(&self.courses, course.id.as_bytes(), course, "id")
+    }
+
+    async fn delete_course(&self, id: Uuid) -> StoreResult<bool> {
+        Ok(self.courses.remove(id.as_bytes())?.is_some())
+    }
+
+    async fn find_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<Option<Enrollment>> {
+        get(&self.enrollments, &pair_key(course, user))
+    }
+
+    async fn list_enrollments(&self, course: Uuid) -> StoreResult<Vec<Enrollment>> {
+        values(self.enrollments.scan_prefix(course.as_bytes()))
+    }
+
+    async fn save_enrollment(&self, enrollment: &Enrollment) -> StoreResult<()> {
+        put(&self.enrollments, &pair_key(enrollment.course, enrollment.user), enrollment)
+    }
+
+    async fn delete_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<bool> {
+        Ok(self.enrollments.remove(pair_key(course, user))?.is_some())
+    }
+
+    async fn delete_course_enrollments(&self, course: Uuid) -> StoreResult<()> {
+        for entry in self.enrollments.scan_prefix(course.as_bytes()) {
+            let (key, _) = entry?;
+            self.enrollments.remove(key)?;
+        }
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl TokenStore for EmbeddedStore {
+    async fn find_token(&self, id: Uuid) -> StoreResult<Option<ApiToken>> {
+        get(&self.tokens, id.as_bytes())
+    }
+
+    async fn list_tokens(&self, user: UserId) -> StoreResult<Vec<ApiToken>> {
+        let tokens: Vec<ApiToken> = values(self.tokens.iter())?;
+        Ok(tokens.into_iter().filter(|token| token.user == user).collect())
+    }
+
+    async fn insert_token(&self, token: &ApiToken) -> StoreResult<()> {
+        put_new(&self.tokens, token.id.as_bytes(), token, "id")
+    }
+
+    async fn revoke_token(&self, id: Uuid, user: UserId) -> StoreResult<Option<ApiToken>> {
+        update(&self.tokens, id.as_bytes(), |token: &mut ApiToken| {
+            if token.user != user {
+                return false;
+            }
+            token.revoked = true;
+            true
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl AuditStore for EmbeddedStore {
+    async fn record_audit(&self, entry: &AuditEntry) -> StoreResult<()> {
+        put(&self.audit, entry.id.as_bytes(), entry)
+    }
+}
+
+#[rocket::async_trait]
+impl StatusStore for EmbeddedStore {
+    /// Database is local, reading from it is enough to surface I/O errors.
+    async fn ping(&self) -> StoreResult<()> {
+        self.users.first()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::hash::Hash;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::audit::AuditEntry;
+use crate::course::{Course, Enrollment};
+use crate::data::{Answer, Collaborator, Grade, ParticipantInfo, Quiz};
+use crate::id::{PartId, QuizId, UserId};
+use crate::role::Role;
+use crate::store::{AttemptStore, AuditStore, CourseStore, QuizStore, StatusStore, StoreError, StoreResult, TokenStore, UserStore};
+use crate::token::ApiToken;
+use crate::user::User;
+
+#[inline]
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().expect("Memory store lock poisoned.")
+}
+
+#[inline]
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().expect("Memory store lock poisoned.")
+}
+
+/// Inserts a value unless `key` is already taken, like a unique `index` would.
+fn insert_new<K: Eq + Hash, V>(map: &mut HashMap<K, V>, key: K, value: V, index: &str) -> StoreResult<()> {
+    match map.entry(key) {
+        Entry::Occupied(_) => Err(StoreError::Duplicate(index.to_string())),
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+            Ok(())
+        }
+    }
+}
+
+/// Whether user is an admin and there are no others.
+fn is_last_admin(users: &HashMap<UserId, User>, id: UserId) -> bool {
+    let is_admin = |user: &User| user.user_roles.contains(&Role::Admin);
+
+    users.get(&id).map_or(false, is_admin)
+        && !users.values().any(|user| user.id != id && is_admin(user))
+}
+
+/// Storage which keeps everything in memory, so nothing outlives it. Used by tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    users: RwLock<HashMap<UserId, User>>,
+    quizzes: RwLock<HashMap<QuizId, Quiz>>,
+    /// Attempts keyed by quiz and user ID.
+    attempts: RwLock<HashMap<(QuizId, UserId), ParticipantInfo>>,
+    courses: RwLock<HashMap<Uuid, Course>>,
+    /// Enrollments keyed by course and user ID.
+    enrollments: RwLock<HashMap<(Uuid, UserId), Enrollment>>,
+    tokens: RwLock<HashMap<Uuid, ApiToken>>,
+    audit: RwLock<Vec<AuditEntry>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+#[rocket::async_trait]
+impl UserStore for MemoryStore {
+    async fn find_user(&self, id: UserId) -> StoreResult<Option<User>> {
+        Ok(read(&self.users).get(&id).cloned())
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> StoreResult<Option<User>> {
+        Ok(read(&self.users).values().find(|user| user.username == username).cloned())
+    }
+
+    async fn insert_user(&self, user: &User) -> StoreResult<()> {
+        let mut users = write(&self.users);
+        if users.values().any(|u| u.username == user.username) {
+            return Err(StoreError::Duplicate("username".to_string()));
+        }
+        insert_new(&mut users, user.id, user.clone(), "id")
+    }
+
+    async fn delete_user(&self, id: UserId) -> StoreResult<bool> {
+        let mut users = write(&self.users);
+        if is_last_admin(&users, id) {
+            return Err(StoreError::LastAdmin);
+        }
+        if users.remove(&id).is_none() {
+            return Ok(false);
+        }
+
+        write(&self.attempts).retain(|(_, user), _| user != &id);
+        for quiz in write(&self.quizzes).values_mut() {
+            quiz.participants.retain(|p| p != &id);
+        }
+        write(&self.tokens).retain(|_, token| token.user != id);
+        write(&self.enrollments).retain(|(_, user), _| user != &id);
+        Ok(true)
+    }
+
+    async fn count_users_with_role(&self, role: Role) -> StoreResult<u64> {
+        Ok(read(&self.users).values().filter(|user| user.user_roles.contains(&role)).count() as u64)
+    }
+
+    async fn add_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>> {
+        Ok(write(&self.users).get_mut(&id).map(|user| {
+            if !user.user_roles.contains(&role) {
+                user.user_roles.push(role);
+            }
+            user.clone()
+        }))
+    }
+
+    async fn remove_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>> {
+        let mut users = write(&self.users);
+        if role == Role::Admin && is_last_admin(&users, id) {
+            return Err(StoreError::LastAdmin);
+        }
+        Ok(users.get_mut(&id).map(|user| {
+            user.user_roles.retain(|r| r != &role);
+            user.clone()
+        }))
+    }
+
+    async fn set_password(&self, id: UserId, pw_hash: [u8; 24]) -> StoreResult<Option<User>> {
+        Ok(write(&self.users).get_mut(&id).map(|user| {
+            user.pw_hash = pw_hash;
+            user.clone()
+        }))
+    }
+}
+
+#[rocket::async_trait]
+impl QuizStore for MemoryStore {
+    async fn find_quiz(&self, id: QuizId) -> StoreResult<Option<Quiz>> {
+        Ok(read(&self.quizzes).get(&id).cloned())
+    }
+
+    async fn find_quizzes_by_author(&self, author: UserId) -> StoreResult<Vec<Quiz>> {
+        Ok(read(&self.quizzes).values().filter(|quiz| quiz.author == author).cloned().collect())
+    }
+
+    async fn count_quizzes_by_author(&self, author: UserId) -> StoreResult<u64> {
+        Ok(read(&self.quizzes).values().filter(|quiz| quiz.author == author).count() as u64)
+    }
+
+    async fn insert_quiz(&self, quiz: &Quiz) -> StoreResult<()> {
+        insert_new(&mut write(&self.quizzes), quiz.id, quiz.clone(), "id")
+    }
+
+    async fn update_quiz(&self, quiz: &Quiz) -> StoreResult<Option<Quiz>> {
+        Ok(write(&self.quizzes).get_mut(&quiz.id).map(|stored| {
+            stored.replace_content(quiz);
+            stored.clone()
+        }))
+    }
+
+    async fn set_collaborator(&self, quiz: QuizId, collaborator: &Collaborator) -> StoreResult<Option<Quiz>> {
+        Ok(write(&self.quizzes).get_mut(&quiz).map(|stored| {
+            stored.set_collaborator(collaborator.clone());
+            stored.clone()
+        }))
+    }
+
+    async fn remove_collaborator(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        Ok(write(&self.quizzes).get_mut(&quiz).map(|stored| {
+            stored.collaborators.retain(|c| c.user != user);
+            stored.clone()
+        }))
+    }
+
+    async fn add_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        Ok(write(&self.quizzes).get_mut(&quiz).map(|stored| {
+            if !stored.participants.contains(&user) {
+                stored.participants.push(user);
+            }
+            stored.clone()
+        }))
+    }
+
+    async fn remove_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        Ok(write(&self.quizzes).get_mut(&quiz).map(|stored| {
+            stored.participants.retain(|p| p != &user);
+            stored.clone()
+        }))
+    }
+
+    async fn delete_quiz(&self, id: QuizId) -> StoreResult<bool> {
+        Ok(write(&self.quizzes).remove(&id).is_some())
+    }
+
+    async fn delete_quizzes_by_author(&self, author: UserId) -> StoreResult<()> {
+        write(&self.quizzes).retain(|_, quiz| quiz.author != author);
+        Ok(())
+    }
+
+    async fn transfer_quizzes(&self, from: UserId, to: UserId) -> StoreResult<()> {
+        for quiz in write(&self.quizzes).values_mut().filter(|quiz| quiz.author == from) {
+            quiz.author = to;
+        }
+        Ok(())
+    }
+
+    async fn detach_course(&self, course: Uuid) -> StoreResult<()> {
+        for quiz in write(&self.quizzes).values_mut().filter(|quiz| quiz.course == Some(course)) {
+            quiz.course = None;
+        }
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl AttemptStore for MemoryStore {
+    async fn find_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<ParticipantInfo>> {
+        Ok(read(&self.attempts).get(&(quiz, user)).cloned())
+    }
+
+    async fn list_attempts(&self, quiz: QuizId) -> StoreResult<Vec<ParticipantInfo>> {
+        Ok(read(&self.attempts).values().filter(|attempt| attempt.quiz == quiz).cloned().collect())
+    }
+
+    async fn count_active_attempts(&self) -> StoreResult<u64> {
+        Ok(read(&self.attempts).values().filter(|attempt| attempt.finished_on.is_none()).count() as u64)
+    }
+
+    async fn insert_attempt(&self, attempt: &ParticipantInfo) -> StoreResult<()> {
+        insert_new(&mut write(&self.attempts), (attempt.quiz, attempt.id), attempt.clone(), "participant")
+    }
+
+    async fn delete_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<bool> {
+        Ok(write(&self.attempts).remove(&(quiz, user)).is_some())
+    }
+
+    async fn delete_quiz_attempts(&self, quiz: QuizId) -> StoreResult<()> {
+        write(&self.attempts).retain(|(q, _), _| q != &quiz);
+        Ok(())
+    }
+
+    async fn set_answer(&self, quiz: QuizId, user: UserId, part: PartId, answer: &Answer) -> StoreResult<Option<ParticipantInfo>> {
+        Ok(write(&self.attempts).get_mut(&(quiz, user))
+            .filter(|attempt| attempt.finished_on.is_none())
+            .map(|attempt| {
+                attempt.answers.insert(part, answer.clone());
+                attempt.version += 1;
+                attempt.clone()
+            }))
+    }
+
+    async fn finish_attempt(&self, quiz: QuizId, user: UserId, version: i64, finished_on: DateTime<Utc>, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>> {
+        Ok(write(&self.attempts).get_mut(&(quiz, user))
+            .filter(|attempt| attempt.finished_on.is_none() && attempt.version == version)
+            .map(|attempt| {
+                attempt.finished_on = Some(finished_on);
+                attempt.grades = grades.clone();
+                attempt.version += 1;
+                attempt.clone()
+            }))
+    }
+
+    async fn set_grades(&self, quiz: QuizId, user: UserId, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>> {
+        Ok(write(&self.attempts).get_mut(&(quiz, user))
+            .filter(|attempt| attempt.finished_on.is_some())
+            .map(|attempt| {
+                attempt.grades.extend(grades.iter().map(|(part, grade)| (*part, grade.clone())));
+                attempt.version += 1;
+                attempt.clone()
+            }))
+    }
+}
+
+#[rocket::async_trait]
+impl CourseStore for MemoryStore {
+    async fn find_course(&self, id: Uuid) -> StoreResult<Option<Course>> {
+        Ok(read(&self.courses).get(&id).cloned())
+    }
+
+    async fn insert_course(&self, course: &Course) -> StoreResult<()> {
+        insert_new(&mut write(&self.courses), course.id, course.clone(), "id")
+    }
+
+    async fn delete_course(&self, id: Uuid) -> StoreResult<bool> {
+        Ok(write(&self.courses).remove(&id).is_some())
+    }
+
+    async fn find_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<Option<Enrollment>> {
+        Ok(read(&self.enrollments).get(&(course, user)).cloned())
+    }
+
+    async fn list_enrollments(&self, course: Uuid) -> StoreResult<Vec<Enrollment>> {
+        Ok(read(&self.enrollments).values().filter(|e| e.course == course).cloned().collect())
+    }
+
+    async fn save_enrollment(&self, enrollment: &Enrollment) -> StoreResult<()> {
+        write(&self.enrollments).insert((enrollment.course, enrollment.user), enrollment.clone());
+        Ok(())
+    }
+
+    async fn delete_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<bool> {
+        Ok(write(&self.enrollments).remove(&(course, user)).is_some())
+    }
+
+    async fn delete_course_enrollments(&self, course: Uuid) -> StoreResult<()> {
+        write(&self.enrollments).retain(|(c, _), _| c != &course);
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl TokenStore for MemoryStore {
+    async fn find_token(&self, id: Uuid) -> StoreResult<Option<ApiToken>> {
+        Ok(read(&self.tokens).get(&id).cloned())
+    }
+
+    async fn list_tokens(&self, user: UserId) -> StoreResult<Vec<ApiToken>> {
+        Ok(read(&self.tokens).values().filter(|token| token.user == user).cloned().collect())
+    }
+
+    async fn insert_token(&self, token: &ApiToken) -> StoreResult<()> {
+        insert_new(&mut write(&self.tokens), token.id, token.clone(), "id")
+    }
+
+    async fn revoke_token(&self, id: Uuid, user: UserId) -> StoreResult<Option<ApiToken>> {
+        Ok(write(&self.tokens).get_mut(&id)
+            .filter(|token| token.user == user)
+            .map(|token| {
+                token.revoked = true;
+                token.clone()
+            }))
+    }
+}
+
+#[rocket::async_trait]
+impl AuditStore for MemoryStore {
+    async fn record_audit(&self, entry: &AuditEntry) -> StoreResult<()> {
+        write(&self.audit).push(entry.clone());
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl StatusStore for MemoryStore {
+    async fn ping(&self) -> StoreResult<()> {
+        Ok(())
+    }
+}
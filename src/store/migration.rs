@@ -0,0 +1,271 @@
+//! MongoDB schema migrations which are applied when the server starts.
+//!
+//! Every migration is applied once, in order of versions. Applied versions are recorded in the
+//! `migrations` collection so that restarting the server doesn't repeat them. New migrations must
+//! be appended to `MIGRATIONS` and handled in `apply`; applied ones mustn't be changed.
+
+use bson::{Binary, Bson, doc, Document};
+use bson::spec::BinarySubtype;
+use chrono::Utc;
+use mongodb::Database;
+use mongodb::options::{FindOptions, UpdateOptions};
+use tokio::stream::StreamExt;
+use uuid::Uuid;
+
+use crate::audit::AUDIT_COLLECTION_NAME;
+use crate::course::{COURSE_COLLECTION_NAME, ENROLLMENT_COLLECTION_NAME};
+use crate::data::{PARTICIPANT_COLLECTION_NAME, QUIZ_COLLECTION_NAME};
+use crate::id::uuid_bson;
+use crate::role::Role;
+use crate::store::mongo::{ADMINS_GUARD_ID, GUARD_COLLECTION_NAME};
+use crate::store::StoreResult;
+use crate::token::API_TOKEN_COLLECTION_NAME;
+use crate::user::USER_COLLECTION_NAME;
+
+pub static MIGRATION_COLLECTION_NAME: &'static str = "migrations";
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "Create unique indexes" },
+    Migration { version: 2, name: "Store UUIDs as strings" },
+    Migration { version: 3, name: "Add attempt versions" },
+    Migration { version: 4, name: "List admins in guard" },
+    Migration { version: 5, name: "Store quiz participants as user IDs" },
+];
+
+async fn apply(db: &Database, version: i32) -> StoreResult<()> {
+    match version {
+        1 => {
+            create_unique_index(db, USER_COLLECTION_NAME, "id", doc! { "id": 1 }).await?;
+            create_unique_index(db, USER_COLLECTION_NAME, "username", doc! { "username": 1 }).await?;
+            create_unique_index(db, QUIZ_COLLECTION_NAME, "id", doc! { "id": 1 }).await?;
+            create_unique_index(db, PARTICIPANT_COLLECTION_NAME, "participant", doc! { "quiz": 1, "id": 1 }).await?;
+            create_unique_index(db, COURSE_COLLECTION_NAME, "id", doc! { "id": 1 }).await?;
+            create_unique_index(db, ENROLLMENT_COLLECTION_NAME, "enrollment", doc! { "course": 1, "user": 1 }).await?;
+            create_unique_index(db, API_TOKEN_COLLECTION_NAME, "id", doc! { "id": 1 }).await?;
+            create_unique_index(db, MIGRATION_COLLECTION_NAME, "version", doc! { "version": 1 }).await
+        }
+        2 => {
+            for collection in &[
+                USER_COLLECTION_NAME,
+                QUIZ_COLLECTION_NAME,
+                PARTICIPANT_COLLECTION_NAME,
+                COURSE_COLLECTION_NAME,
+                ENROLLMENT_COLLECTION_NAME,
+                API_TOKEN_COLLECTION_NAME,
+                AUDIT_COLLECTION_NAME,
+            ] {
+                let fixed = rewrite_binary_uuids(db, collection).await?;
+                if fixed > 0 {
+                    info!("Converted UUIDs of {} documents in {}", fixed, collection);
+                }
+            }
+            Ok(())
+        }
+        3 => {
+            // Version filters don't match documents which don't have the field at all.
+            db.collection(PARTICIPANT_COLLECTION_NAME)
+                .update_many(
+                    doc! { "version": { "$exists": false } },
+                    doc! { "$set": { "version": 0i64 } },
+                    None,
+                )
+                .await?;
+            Ok(())
+        }
+        4 => {
+            let filter = doc! { "user_roles": bson::to_bson(&Role::Admin)? };
+            let mut cursor = db.collection(USER_COLLECTION_NAME).find(filter, None).await?;
+
+            let mut admins = vec![];
+            while let Some(document) = cursor.next().await {
+                if let Some(id) = document?.get("id") {
+                    admins.push(id.clone());
+                }
+            }
+
+            let options = UpdateOptions::builder()
+                .upsert(true)
+                .build();
+            db.collection(GUARD_COLLECTION_NAME)
+                .update_one(
+                    doc! { "_id": ADMINS_GUARD_ID },
+                    doc! { "$addToSet": { "users": { "$each": admins } } },
+                    options,
+                )
+                .await?;
+            Ok(())
+        }
+        5 => {
+            let fixed = resolve_participants(db).await?;
+            if fixed > 0 {
+                info!("Converted participants of {} quizzes", fixed);
+            }
+            Ok(())
+        }
+        _ => unreachable!("Migration {} isn't handled.", version),
+    }
+}
+
+/// Index names are reported back by duplicate key errors, so they should name the key.
+async fn create_unique_index(db: &Database, collection: &str, name: &str, keys: Document) -> StoreResult<()> {
+    db.run_command(doc! {
+        "createIndexes": collection,
+        "indexes": [
+            {
+                "key": keys,
+                "name": name,
+                "unique": true,
+            }
+        ],
+    }, None).await?;
+
+    Ok(())
+}
+
+/// Replaces binary UUIDs with their string form, wherever they're nested in a value. Returns
+/// whether anything was replaced.
+fn stringify_uuids(value: &mut Bson) -> bool {
+    match value {
+        Bson::Binary(binary) if is_uuid_binary(binary) => {
+            let id = Uuid::from_slice(&binary.bytes).expect("UUID binary must have 16 bytes.");
+            *value = uuid_bson(id);
+            true
+        }
+        Bson::Document(document) => stringify_document_uuids(document),
+        Bson::Array(array) => array.iter_mut()
+            .fold(false, |changed, item| stringify_uuids(item) || changed),
+        _ => false,
+    }
+}
+
+fn stringify_document_uuids(document: &mut Document) -> bool {
+    document.iter_mut()
+        .fold(false, |changed, (_, item)| stringify_uuids(item) || changed)
+}
+
+#[inline]
+fn is_uuid_binary(binary: &Binary) -> bool {
+    let subtype_matches = match binary.subtype {
+        BinarySubtype::Uuid | BinarySubtype::UuidOld => true,
+        _ => false,
+    };
+    subtype_matches && binary.bytes.len() == 16
+}
+
+/// Rewrites documents which store UUIDs as BSON binary. Returns number of rewritten documents.
+async fn rewrite_binary_uuids(db: &Database, collection: &str) -> StoreResult<u64> {
+    let collection = db.collection(collection);
+    let mut cursor = collection.find(None, None).await?;
+
+    let mut fixed = 0;
+    while let Some(document) = cursor.next().await {
+        let mut document = document?;
+        if !stringify_document_uuids(&mut document) {
+            continue;
+        }
+
+        let filter = doc! { "_id": document.get("_id").cloned().unwrap_or(Bson::Null) };
+        collection.replace_one(filter, document, None).await?;
+        fixed += 1;
+    }
+
+    Ok(fixed)
+}
+
+/// Quiz participants used to be stored as arbitrary strings, usually usernames. Values which are
+/// UUIDs in any format or usernames of existing users are replaced by IDs, the rest are dropped.
+/// Returns number of rewritten quizzes.
+async fn resolve_participants(db: &Database) -> StoreResult<u64> {
+    let users = db.collection(USER_COLLECTION_NAME);
+    let quizzes = db.collection(QUIZ_COLLECTION_NAME);
+    let mut cursor = quizzes.find(doc! { "participants.0": { "$exists": true } }, None).await?;
+
+    let mut fixed = 0;
+    while let Some(document) = cursor.next().await {
+        let document = document?;
+        let participants = match document.get_array("participants") {
+            Ok(it) => it,
+            Err(_) => continue,
+        };
+
+        let mut resolved = vec![];
+        for participant in participants {
+            let value = match participant {
+                Bson::String(it) => it,
+                other => {
+                    warn!("Dropped participant {} of quiz {:?}, it isn't a string", other, document.get("id"));
+                    continue;
+                }
+            };
+
+            let id = match Uuid::parse_str(value) {
+                Ok(id) => Some(uuid_bson(id)),
+                Err(_) => users.find_one(doc! { "username": value.as_str() }, None).await?
+                    .and_then(|user| user.get("id").cloned()),
+            };
+            match id {
+                Some(id) if !resolved.contains(&id) => resolved.push(id),
+                Some(_) => {}
+                None => warn!("Dropped participant '{}' of quiz {:?}, no such user exists", value, document.get("id")),
+            }
+        }
+
+        if resolved.as_slice() == participants.as_slice() {
+            continue;
+        }
+
+        let filter = doc! { "_id": document.get("_id").cloned().unwrap_or(Bson::Null) };
+        quizzes.update_one(filter, doc! { "$set": { "participants": resolved } }, None).await?;
+        fixed += 1;
+    }
+
+    Ok(fixed)
+}
+
+/// Version of the last applied migration, 0 if none were applied.
+pub async fn applied_version(db: &Database) -> StoreResult<i32> {
+    let options = FindOptions::builder()
+        .sort(doc! { "version": -1 })
+        .limit(1)
+        .build();
+
+    let mut cursor = db.collection(MIGRATION_COLLECTION_NAME)
+        .find(None, options)
+        .await?;
+
+    match cursor.next().await {
+        Some(document) => Ok(document?.get_i32("version").unwrap_or(0)),
+        None => Ok(0),
+    }
+}
+
+/// Migrations newer than the last applied one.
+pub async fn pending(db: &Database) -> StoreResult<Vec<&'static Migration>> {
+    let current = applied_version(db).await?;
+    Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+}
+
+/// Applies every migration newer than the last applied one. Returns applied migrations.
+pub async fn run(db: &Database) -> StoreResult<Vec<&'static Migration>> {
+    let pending = pending(db).await?;
+
+    for migration in &pending {
+        info!("Applying database migration {}: {}", migration.version, migration.name);
+        apply(db, migration.version).await?;
+
+        db.collection(MIGRATION_COLLECTION_NAME)
+            .insert_one(doc! {
+                "version": migration.version,
+                "name": migration.name,
+                "applied_on": Bson::DateTime(Utc::now()),
+            }, None)
+            .await?;
+    }
+
+    Ok(pending)
+}
@@ -0,0 +1,196 @@
+//! Storage of users, quizzes, attempts and everything related to them.
+//!
+//! Routes only talk to storage through traits in this module so the server can run on top of
+//! MongoDB or on top of an embedded database kept in a local directory, which doesn't require any
+//! other service to be running.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::audit::AuditEntry;
+use crate::config::{Backend, Config};
+use crate::course::{Course, Enrollment};
+use crate::data::{Answer, Collaborator, Grade, ParticipantInfo, Quiz};
+use crate::id::{PartId, QuizId, UserId};
+use crate::role::Role;
+use crate::token::ApiToken;
+use crate::user::User;
+
+pub mod mongo;
+pub mod embedded;
+pub mod migration;
+#[cfg(test)]
+pub mod memory;
+
+pub use embedded::EmbeddedStore;
+pub use mongo::MongoStore;
+#[cfg(test)]
+pub use memory::MemoryStore;
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    /// Write would violate a unique key, identified by the name of its index.
+    #[error("duplicate value of unique key: {0}")]
+    Duplicate(String),
+    /// Write would leave the server without any admins.
+    #[error("last admin can't lose admin role")]
+    LastAdmin,
+    #[error("MongoDB error: {0}")]
+    Mongo(#[source] mongodb::error::Error),
+    #[error("embedded database error: {0}")]
+    Embedded(#[from] sled::Error),
+    #[error("unable to decode BSON document: {0}")]
+    BsonDecode(#[from] bson::de::Error),
+    #[error("unable to encode BSON document: {0}")]
+    BsonEncode(#[from] bson::ser::Error),
+    #[error("unable to process stored JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<mongodb::error::Error> for StoreError {
+    fn from(e: mongodb::error::Error) -> Self {
+        match mongo::duplicate_key(&e) {
+            Some(key) => StoreError::Duplicate(key),
+            None => StoreError::Mongo(e),
+        }
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+#[rocket::async_trait]
+pub trait UserStore: Send + Sync {
+    async fn find_user(&self, id: UserId) -> StoreResult<Option<User>>;
+    async fn find_user_by_username(&self, username: &str) -> StoreResult<Option<User>>;
+    /// Stores a new user. Fails with `StoreError::Duplicate` if the ID or username is taken.
+    async fn insert_user(&self, user: &User) -> StoreResult<()>;
+    /// Deletes a user along with their attempts, API tokens and enrollments, and removes them from
+    /// quiz participants. Fails with `StoreError::LastAdmin` if they're the last admin.
+    async fn delete_user(&self, id: UserId) -> StoreResult<bool>;
+    async fn count_users_with_role(&self, role: Role) -> StoreResult<u64>;
+
+    /// Grants a role to user unless they already have it. Returns `None` if user doesn't exist.
+    async fn add_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>>;
+    /// Revokes a role from user. Returns `None` if user doesn't exist.
+    ///
+    /// Revoking admin role of the last admin fails with `StoreError::LastAdmin`. The check is made
+    /// by the same atomic write which revokes the role, so concurrent revokes can't remove every
+    /// admin.
+    async fn remove_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>>;
+    /// Replaces password hash of a user. Returns `None` if user doesn't exist.
+    async fn set_password(&self, id: UserId, pw_hash: [u8; 24]) -> StoreResult<Option<User>>;
+}
+
+#[rocket::async_trait]
+pub trait QuizStore: Send + Sync {
+    async fn find_quiz(&self, id: QuizId) -> StoreResult<Option<Quiz>>;
+    async fn find_quizzes_by_author(&self, author: UserId) -> StoreResult<Vec<Quiz>>;
+    async fn count_quizzes_by_author(&self, author: UserId) -> StoreResult<u64>;
+    async fn insert_quiz(&self, quiz: &Quiz) -> StoreResult<()>;
+    /// Replaces quiz content. Author, creation time, course, participants and collaborators are
+    /// kept as stored, they're only changed by their own updates so concurrent changes aren't
+    /// lost. Returns `None` if quiz doesn't exist.
+    async fn update_quiz(&self, quiz: &Quiz) -> StoreResult<Option<Quiz>>;
+    async fn delete_quiz(&self, id: QuizId) -> StoreResult<bool>;
+    async fn delete_quizzes_by_author(&self, author: UserId) -> StoreResult<()>;
+
+    /// Changes author of every quiz authored by `from` to `to`.
+    async fn transfer_quizzes(&self, from: UserId, to: UserId) -> StoreResult<()>;
+    /// Adds a collaborator to a quiz or replaces capabilities of an existing one. Returns `None` if
+    /// quiz doesn't exist.
+    async fn set_collaborator(&self, quiz: QuizId, collaborator: &Collaborator) -> StoreResult<Option<Quiz>>;
+    async fn remove_collaborator(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>>;
+    async fn add_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>>;
+    async fn remove_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>>;
+    /// Disassociates every quiz from a course.
+    async fn detach_course(&self, course: Uuid) -> StoreResult<()>;
+}
+
+/// Attempts are identified by the quiz and the user who's taking it.
+///
+/// Updates are atomic and only applied to attempts in the expected state so that e.g. answers
+/// can't be submitted after an attempt has concurrently been finished. They return `None` if the
+/// attempt doesn't exist or isn't in that state. Every update increments attempt `version`.
+#[rocket::async_trait]
+pub trait AttemptStore: Send + Sync {
+    async fn find_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<ParticipantInfo>>;
+    async fn list_attempts(&self, quiz: QuizId) -> StoreResult<Vec<ParticipantInfo>>;
+    /// Counts attempts at all quizzes which haven't been finished yet.
+    async fn count_active_attempts(&self) -> StoreResult<u64>;
+    async fn insert_attempt(&self, attempt: &ParticipantInfo) -> StoreResult<()>;
+    async fn delete_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<bool>;
+    async fn delete_quiz_attempts(&self, quiz: QuizId) -> StoreResult<()>;
+
+    /// Stores an answer to an attempt which is still in progress. Answers to other parts are left
+    /// as they are, so concurrent submissions of different parts don't overwrite each other.
+    async fn set_answer(&self, quiz: QuizId, user: UserId, part: PartId, answer: &Answer) -> StoreResult<Option<ParticipantInfo>>;
+    /// Finishes an attempt which is still in progress and hasn't changed since `version`, so that
+    /// grades are never computed from outdated answers.
+    async fn finish_attempt(&self, quiz: QuizId, user: UserId, version: i64, finished_on: DateTime<Utc>, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>>;
+    /// Stores grades of a finished attempt, all of them in the same atomic update. Grades of other
+    /// parts are left as they are.
+    async fn set_grades(&self, quiz: QuizId, user: UserId, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>>;
+}
+
+#[rocket::async_trait]
+pub trait CourseStore: Send + Sync {
+    async fn find_course(&self, id: Uuid) -> StoreResult<Option<Course>>;
+    async fn insert_course(&self, course: &Course) -> StoreResult<()>;
+    async fn delete_course(&self, id: Uuid) -> StoreResult<bool>;
+
+    async fn find_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<Option<Enrollment>>;
+    async fn list_enrollments(&self, course: Uuid) -> StoreResult<Vec<Enrollment>>;
+    /// Stores an enrollment, replacing the one user previously had in the same course.
+    async fn save_enrollment(&self, enrollment: &Enrollment) -> StoreResult<()>;
+    async fn delete_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<bool>;
+    async fn delete_course_enrollments(&self, course: Uuid) -> StoreResult<()>;
+}
+
+#[rocket::async_trait]
+pub trait TokenStore: Send + Sync {
+    async fn find_token(&self, id: Uuid) -> StoreResult<Option<ApiToken>>;
+    async fn list_tokens(&self, user: UserId) -> StoreResult<Vec<ApiToken>>;
+    async fn insert_token(&self, token: &ApiToken) -> StoreResult<()>;
+    /// Revokes a token owned by `user`. Returns `None` if user doesn't have such token.
+    async fn revoke_token(&self, id: Uuid, user: UserId) -> StoreResult<Option<ApiToken>>;
+}
+
+#[rocket::async_trait]
+pub trait AuditStore: Send + Sync {
+    async fn record_audit(&self, entry: &AuditEntry) -> StoreResult<()>;
+}
+
+#[rocket::async_trait]
+pub trait StatusStore: Send + Sync {
+    /// Checks that the database can be reached.
+    async fn ping(&self) -> StoreResult<()>;
+}
+
+/// Complete storage backend.
+pub trait Store: UserStore + QuizStore + AttemptStore + CourseStore + TokenStore + AuditStore + StatusStore {}
+
+impl<T: UserStore + QuizStore + AttemptStore + CourseStore + TokenStore + AuditStore + StatusStore> Store for T {}
+
+/// Storage shared by all routes through managed state.
+pub type Storage = Arc<dyn Store>;
+
+/// Opens storage backend selected by configuration.
+pub async fn open(c: &Config) -> StoreResult<Storage> {
+    match c.storage {
+        Backend::MongoDb => {
+            info!("Connecting to MongoDB: {}", c.redacted().mongodb_uri);
+            let store = MongoStore::connect(&c.mongodb_uri, &c.mongodb_db).await?;
+            info!("Using MongoDB database: {}", c.mongodb_db);
+            store.migrate().await?;
+            Ok(Arc::new(store))
+        }
+        Backend::Embedded => {
+            info!("Opening embedded database: {}", c.embedded_path.display());
+            Ok(Arc::new(EmbeddedStore::open(&c.embedded_path)?))
+        }
+    }
+}
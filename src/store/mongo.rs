@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+
+use bson::{Bson, doc, Document, from_bson};
+use chrono::{DateTime, Utc};
+use mongodb::{Client, Database};
+use mongodb::options::{FindOneAndUpdateOptions, ReplaceOptions, ReturnDocument, UpdateOptions};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::stream::StreamExt;
+use uuid::Uuid;
+
+use crate::audit::{AUDIT_COLLECTION_NAME, AuditEntry};
+use crate::course::{Course, COURSE_COLLECTION_NAME, Enrollment, ENROLLMENT_COLLECTION_NAME};
+use crate::data::{Answer, Collaborator, Grade, ParticipantInfo, PARTICIPANT_COLLECTION_NAME, Quiz, QUIZ_COLLECTION_NAME};
+use crate::id::{PartId, QuizId, UserId, uuid_bson};
+use crate::role::Role;
+use crate::store::migration::{self, Migration};
+use crate::store::{AttemptStore, AuditStore, CourseStore, QuizStore, StatusStore, StoreError, StoreResult, TokenStore, UserStore};
+use crate::token::{API_TOKEN_COLLECTION_NAME, ApiToken};
+use crate::user::{User, USER_COLLECTION_NAME};
+
+/// Documents which keep invariants spanning several documents, see `MongoStore::dismiss_admin`.
+pub static GUARD_COLLECTION_NAME: &'static str = "guards";
+pub static ADMINS_GUARD_ID: &'static str = "admins";
+
+/// Quiz fields which aren't changed by `update_quiz`.
+const QUIZ_KEPT_FIELDS: &[&str] = &["id", "author", "created", "course", "participants", "collaborators"];
+
+#[inline]
+fn filter_id<I: Into<Uuid>>(id: I) -> Document {
+    doc! {
+        "id": uuid_bson(id.into()),
+    }
+}
+
+#[inline]
+fn filter_user_username(username: &str) -> Document {
+    doc! {
+        "username": username
+    }
+}
+
+#[inline]
+fn filter_user_role(role: Role) -> Document {
+    doc! {
+        "user_roles": role_bson(role)
+    }
+}
+
+#[inline]
+fn role_bson(role: Role) -> Bson {
+    bson::to_bson(&role)
+        .expect("Unable to serialize Role into BSON.")
+}
+
+#[inline]
+fn filter_quiz_author(author: UserId) -> Document {
+    doc! {
+        "author": author.to_bson(),
+    }
+}
+
+#[inline]
+fn filter_quiz_course(course: Uuid) -> Document {
+    doc! {
+        "course": uuid_bson(course),
+    }
+}
+
+#[inline]
+fn filter_attempt(quiz: QuizId, user: UserId) -> Document {
+    doc! {
+        "quiz": quiz.to_bson(),
+        "id": user.to_bson(),
+    }
+}
+
+#[inline]
+fn filter_attempt_quiz(quiz: QuizId) -> Document {
+    doc! {
+        "quiz": quiz.to_bson(),
+    }
+}
+
+#[inline]
+fn filter_attempt_user(user: UserId) -> Document {
+    doc! {
+        "id": user.to_bson(),
+    }
+}
+
+/// Matches an attempt which is still in progress (`finished == false`) or one which has been
+/// finished (`finished == true`).
+#[inline]
+fn filter_attempt_state(quiz: QuizId, user: UserId, finished: bool) -> Document {
+    let mut filter = filter_attempt(quiz, user);
+    if finished {
+        filter.insert("finished_on", doc! { "$ne": Bson::Null });
+    } else {
+        filter.insert("finished_on", Bson::Null);
+    }
+    filter
+}
+
+#[inline]
+fn filter_enrollment(course: Uuid, user: UserId) -> Document {
+    doc! {
+        "course": uuid_bson(course),
+        "user": user.to_bson(),
+    }
+}
+
+#[inline]
+fn filter_enrollment_course(course: Uuid) -> Document {
+    doc! {
+        "course": uuid_bson(course),
+    }
+}
+
+#[inline]
+fn filter_user(user: UserId) -> Document {
+    doc! {
+        "user": user.to_bson(),
+    }
+}
+
+#[inline]
+fn filter_token(id: Uuid, user: UserId) -> Document {
+    doc! {
+        "id": uuid_bson(id),
+        "user": user.to_bson(),
+    }
+}
+
+/// Server error code of writes which violate a unique index.
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// Returns name of the unique index violated by a failed write, if that's why it failed.
+pub fn duplicate_key(e: &mongodb::error::Error) -> Option<String> {
+    use mongodb::error::{ErrorKind, WriteFailure};
+
+    let message = match e.kind.as_ref() {
+        ErrorKind::WriteError(WriteFailure::WriteError(err)) if err.code == DUPLICATE_KEY_CODE => &err.message,
+        ErrorKind::CommandError(err) if err.code == DUPLICATE_KEY_CODE => &err.message,
+        ErrorKind::BulkWriteError(failure) => {
+            &failure.write_errors.as_ref()?
+                .iter()
+                .find(|err| err.code == DUPLICATE_KEY_CODE)?
+                .message
+        }
+        _ => return None,
+    };
+
+    // Messages look like: "E11000 duplicate key error collection: db.users index: username dup key: ..."
+    let key = message.split(" index: ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or("unknown");
+
+    Some(key.to_string())
+}
+
+/// Storage backed by a MongoDB database.
+///
+/// Driver doesn't support transactions, so only updates of a single document are atomic. Updates
+/// which must be atomic keep everything they change in one document, e.g. attempts hold their
+/// answers and grades. Writes spanning several documents are ordered so that a failure part way
+/// leaves a state the same write can be retried from.
+pub struct MongoStore {
+    db: Database,
+}
+
+impl MongoStore {
+    pub fn new(db: Database) -> MongoStore {
+        MongoStore {
+            db,
+        }
+    }
+
+    pub async fn connect(uri: &str, db_name: &str) -> StoreResult<MongoStore> {
+        let client = Client::with_uri_str(uri).await?;
+        Ok(MongoStore::new(client.database(db_name)))
+    }
+
+    /// Brings database schema up to date and returns applied migrations. See `migration` module.
+    pub async fn migrate(&self) -> StoreResult<Vec<&'static Migration>> {
+        migration::run(&self.db).await
+    }
+
+    pub async fn migration_version(&self) -> StoreResult<i32> {
+        migration::applied_version(&self.db).await
+    }
+
+    pub async fn pending_migrations(&self) -> StoreResult<Vec<&'static Migration>> {
+        migration::pending(&self.db).await
+    }
+
+    async fn find_one<T: DeserializeOwned>(&self, collection: &str, filter: Document) -> StoreResult<Option<T>> {
+        let document = self.db.collection(collection)
+            .find_one(filter, None)
+            .await?;
+
+        match document {
+            Some(doc) => Ok(Some(from_bson(Bson::Document(doc))?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn find_many<T: DeserializeOwned>(&self, collection: &str, filter: Document) -> StoreResult<Vec<T>> {
+        let mut cursor = self.db.collection(collection)
+            .find(filter, None)
+            .await?;
+
+        let mut result = vec![];
+        while let Some(document) = cursor.next().await {
+            result.push(from_bson(Bson::Document(document?))?);
+        }
+
+        Ok(result)
+    }
+
+    async fn count(&self, collection: &str, filter: Document) -> StoreResult<u64> {
+        let count = self.db.collection(collection)
+            .count_documents(filter, None)
+            .await?;
+
+        Ok(count.max(0) as u64)
+    }
+
+    async fn insert<T: Serialize + Sync>(&self, collection: &str, value: &T) -> StoreResult<()> {
+        self.db.collection(collection)
+            .insert_one(bson::to_document(value)?, None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Applies update to a single document and returns it as it is after the update.
+    async fn update_one<T: DeserializeOwned>(&self, collection: &str, filter: Document, update: Document) -> StoreResult<Option<T>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let document = self.db.collection(collection)
+            .find_one_and_update(filter, update, options)
+            .await?;
+
+        match document {
+            Some(doc) => Ok(Some(from_bson(Bson::Document(doc))?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn update_many(&self, collection: &str, filter: Document, update: Document) -> StoreResult<()> {
+        self.db.collection(collection)
+            .update_many(filter, update, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Document) -> StoreResult<bool> {
+        let result = self.db.collection(collection)
+            .delete_one(filter, None)
+            .await?;
+
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Document) -> StoreResult<()> {
+        self.db.collection(collection)
+            .delete_many(filter, None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Lists a user in the admins guard. Called after admin role was granted.
+    async fn admit_admin(&self, id: UserId) -> StoreResult<()> {
+        let options = UpdateOptions::builder()
+            .upsert(true)
+            .build();
+
+        self.db.collection(GUARD_COLLECTION_NAME)
+            .update_one(
+                doc! { "_id": ADMINS_GUARD_ID },
+                doc! { "$addToSet": { "users": id.to_bson() } },
+                options,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Removes a user from the admins guard before their admin role is revoked.
+    ///
+    /// Without transactions there's no way to count admins and revoke the role atomically, so
+    /// admins are also listed in a single guard document. It's only shrunk by a conditional update
+    /// which requires another admin to remain listed, making concurrent revokes safe. Users are
+    /// listed after the role is granted and unlisted before it's revoked, so a failed write can
+    /// only leave an admin unlisted. Such admin can't be demoted until the role is granted to them
+    /// again, which lists them.
+    async fn dismiss_admin(&self, id: UserId) -> StoreResult<()> {
+        let result = self.db.collection(GUARD_COLLECTION_NAME)
+            .update_one(
+                doc! {
+                    "_id": ADMINS_GUARD_ID,
+                    "users": id.to_bson(),
+                    "users.1": { "$exists": true },
+                },
+                doc! { "$pull": { "users": id.to_bson() } },
+                None,
+            )
+            .await?;
+
+        if result.matched_count == 0 {
+            return Err(StoreError::LastAdmin);
+        }
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl UserStore for MongoStore {
+    async fn find_user(&self, id: UserId) -> StoreResult<Option<User>> {
+        self.find_one(USER_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> StoreResult<Option<User>> {
+        self.find_one(USER_COLLECTION_NAME, filter_user_username(username)).await
+    }
+
+    async fn insert_user(&self, user: &User) -> StoreResult<()> {
+        self.insert(USER_COLLECTION_NAME, user).await?;
+        if user.user_roles.contains(&Role::Admin) {
+            self.admit_admin(user.id).await?;
+        }
+        Ok(())
+    }
+
+    /// User document is deleted last, so the deletion can be repeated if it fails part way.
+    async fn delete_user(&self, id: UserId) -> StoreResult<bool> {
+        let user: User = match self.find_one(USER_COLLECTION_NAME, filter_id(id)).await? {
+            Some(it) => it,
+            None => return Ok(false),
+        };
+        if user.user_roles.contains(&Role::Admin) {
+            self.dismiss_admin(id).await?;
+        }
+
+        self.delete_many(PARTICIPANT_COLLECTION_NAME, filter_attempt_user(id)).await?;
+        self.update_many(
+            QUIZ_COLLECTION_NAME,
+            doc! {},
+            doc! { "$pull": { "participants": id.to_bson() } },
+        ).await?;
+        self.delete_many(API_TOKEN_COLLECTION_NAME, filter_user(id)).await?;
+        self.delete_many(ENROLLMENT_COLLECTION_NAME, filter_user(id)).await?;
+
+        self.delete_one(USER_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn count_users_with_role(&self, role: Role) -> StoreResult<u64> {
+        self.count(USER_COLLECTION_NAME, filter_user_role(role)).await
+    }
+
+    async fn add_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>> {
+        let user = self.update_one(
+            USER_COLLECTION_NAME,
+            filter_id(id),
+            doc! { "$addToSet": { "user_roles": role_bson(role) } },
+        ).await?;
+
+        if user.is_some() && role == Role::Admin {
+            self.admit_admin(id).await?;
+        }
+        Ok(user)
+    }
+
+    async fn remove_user_role(&self, id: UserId, role: Role) -> StoreResult<Option<User>> {
+        if role == Role::Admin {
+            let mut filter = filter_id(id);
+            filter.insert("user_roles", role_bson(Role::Admin));
+
+            if self.count(USER_COLLECTION_NAME, filter).await? > 0 {
+                self.dismiss_admin(id).await?;
+            }
+        }
+
+        self.update_one(
+            USER_COLLECTION_NAME,
+            filter_id(id),
+            doc! { "$pull": { "user_roles": role_bson(role) } },
+        ).await
+    }
+
+    async fn set_password(&self, id: UserId, pw_hash: [u8; 24]) -> StoreResult<Option<User>> {
+        self.update_one(
+            USER_COLLECTION_NAME,
+            filter_id(id),
+            doc! { "$set": { "pw_hash": bson::to_bson(&pw_hash)? } },
+        ).await
+    }
+}
+
+#[rocket::async_trait]
+impl QuizStore for MongoStore {
+    async fn find_quiz(&self, id: QuizId) -> StoreResult<Option<Quiz>> {
+        self.find_one(QUIZ_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn find_quizzes_by_author(&self, author: UserId) -> StoreResult<Vec<Quiz>> {
+        self.find_many(QUIZ_COLLECTION_NAME, filter_quiz_author(author)).await
+    }
+
+    async fn count_quizzes_by_author(&self, author: UserId) -> StoreResult<u64> {
+        self.count(QUIZ_COLLECTION_NAME, filter_quiz_author(author)).await
+    }
+
+    async fn insert_quiz(&self, quiz: &Quiz) -> StoreResult<()> {
+        self.insert(QUIZ_COLLECTION_NAME, quiz).await
+    }
+
+    async fn update_quiz(&self, quiz: &Quiz) -> StoreResult<Option<Quiz>> {
+        let mut content = bson::to_document(quiz)?;
+        for kept in QUIZ_KEPT_FIELDS {
+            content.remove(kept);
+        }
+
+        self.update_one(QUIZ_COLLECTION_NAME, filter_id(quiz.id), doc! { "$set": content }).await
+    }
+
+    async fn set_collaborator(&self, quiz: QuizId, collaborator: &Collaborator) -> StoreResult<Option<Quiz>> {
+        let user = collaborator.user.to_bson();
+        let capabilities = bson::to_bson(&collaborator.capabilities)?;
+        let collaborator = bson::to_bson(collaborator)?;
+
+        // Array elements can't be pulled and pushed by the same update, so an existing
+        // collaborator is updated in place and a new one is only pushed if they're still missing.
+        loop {
+            let mut existing = filter_id(quiz);
+            existing.insert("collaborators.user", user.clone());
+            let updated = self.update_one(
+                QUIZ_COLLECTION_NAME,
+                existing,
+                doc! { "$set": { "collaborators.$.capabilities": capabilities.clone() } },
+            ).await?;
+            if updated.is_some() {
+                return Ok(updated);
+            }
+
+            let mut missing = filter_id(quiz);
+            missing.insert("collaborators.user", doc! { "$ne": user.clone() });
+            let updated = self.update_one(
+                QUIZ_COLLECTION_NAME,
+                missing,
+                doc! { "$push": { "collaborators": collaborator.clone() } },
+            ).await?;
+            if updated.is_some() {
+                return Ok(updated);
+            }
+
+            // Neither matched, either the quiz is gone or the collaborator was added meanwhile.
+            if self.count(QUIZ_COLLECTION_NAME, filter_id(quiz)).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    async fn remove_collaborator(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        self.update_one(
+            QUIZ_COLLECTION_NAME,
+            filter_id(quiz),
+            doc! { "$pull": { "collaborators": { "user": user.to_bson() } } },
+        ).await
+    }
+
+    async fn add_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        self.update_one(
+            QUIZ_COLLECTION_NAME,
+            filter_id(quiz),
+            doc! { "$addToSet": { "participants": user.to_bson() } },
+        ).await
+    }
+
+    async fn remove_quiz_participant(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<Quiz>> {
+        self.update_one(
+            QUIZ_COLLECTION_NAME,
+            filter_id(quiz),
+            doc! { "$pull": { "participants": user.to_bson() } },
+        ).await
+    }
+
+    async fn delete_quiz(&self, id: QuizId) -> StoreResult<bool> {
+        self.delete_one(QUIZ_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn delete_quizzes_by_author(&self, author: UserId) -> StoreResult<()> {
+        self.delete_many(QUIZ_COLLECTION_NAME, filter_quiz_author(author)).await
+    }
+
+    async fn transfer_quizzes(&self, from: UserId, to: UserId) -> StoreResult<()> {
+        self.update_many(
+            QUIZ_COLLECTION_NAME,
+            filter_quiz_author(from),
+            doc! { "$set": filter_quiz_author(to) },
+        ).await
+    }
+
+    async fn detach_course(&self, course: Uuid) -> StoreResult<()> {
+        self.update_many(
+            QUIZ_COLLECTION_NAME,
+            filter_quiz_course(course),
+            doc! { "$set": { "course": Bson::Null } },
+        ).await
+    }
+}
+
+#[rocket::async_trait]
+impl AttemptStore for MongoStore {
+    async fn find_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<Option<ParticipantInfo>> {
+        self.find_one(PARTICIPANT_COLLECTION_NAME, filter_attempt(quiz, user)).await
+    }
+
+    async fn list_attempts(&self, quiz: QuizId) -> StoreResult<Vec<ParticipantInfo>> {
+        self.find_many(PARTICIPANT_COLLECTION_NAME, filter_attempt_quiz(quiz)).await
+    }
+
+    async fn count_active_attempts(&self) -> StoreResult<u64> {
+        // Null also matches attempts which don't have the field at all.
+        self.count(PARTICIPANT_COLLECTION_NAME, doc! { "finished_on": Bson::Null }).await
+    }
+
+    async fn insert_attempt(&self, attempt: &ParticipantInfo) -> StoreResult<()> {
+        self.insert(PARTICIPANT_COLLECTION_NAME, attempt).await
+    }
+
+    async fn delete_attempt(&self, quiz: QuizId, user: UserId) -> StoreResult<bool> {
+        self.delete_one(PARTICIPANT_COLLECTION_NAME, filter_attempt(quiz, user)).await
+    }
+
+    async fn delete_quiz_attempts(&self, quiz: QuizId) -> StoreResult<()> {
+        self.delete_many(PARTICIPANT_COLLECTION_NAME, filter_attempt_quiz(quiz)).await
+    }
+
+    async fn set_answer(&self, quiz: QuizId, user: UserId, part: PartId, answer: &Answer) -> StoreResult<Option<ParticipantInfo>> {
+        let mut set = Document::new();
+        set.insert(format!("answers.{}", part), bson::to_bson(answer)?);
+
+        self.update_one(
+            PARTICIPANT_COLLECTION_NAME,
+            filter_attempt_state(quiz, user, false),
+            doc! { "$set": set, "$inc": { "version": 1 } },
+        ).await
+    }
+
+    /// Finish time and grades are set by a single update of the attempt document, which MongoDB
+    /// applies atomically, together with the version check.
+    async fn finish_attempt(&self, quiz: QuizId, user: UserId, version: i64, finished_on: DateTime<Utc>, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>> {
+        let mut filter = filter_attempt_state(quiz, user, false);
+        filter.insert("version", version);
+
+        self.update_one(
+            PARTICIPANT_COLLECTION_NAME,
+            filter,
+            doc! {
+                "$set": {
+                    "finished_on": bson::to_bson(&finished_on)?,
+                    "grades": bson::to_bson(grades)?,
+                },
+                "$inc": { "version": 1 },
+            },
+        ).await
+    }
+
+    async fn set_grades(&self, quiz: QuizId, user: UserId, grades: &HashMap<PartId, Grade>) -> StoreResult<Option<ParticipantInfo>> {
+        let mut set = Document::new();
+        for (part, grade) in grades {
+            set.insert(format!("grades.{}", part), bson::to_bson(grade)?);
+        }
+
+        self.update_one(
+            PARTICIPANT_COLLECTION_NAME,
+            filter_attempt_state(quiz, user, true),
+            doc! { "$set": set, "$inc": { "version": 1 } },
+        ).await
+    }
+}
+
+#[rocket::async_trait]
+impl CourseStore for MongoStore {
+    async fn find_course(&self, id: Uuid) -> StoreResult<Option<Course>> {
+        self.find_one(COURSE_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn insert_course(&self, course: &Course) -> StoreResult<()> {
+        self.insert(COURSE_COLLECTION_NAME, course).await
+    }
+
+    async fn delete_course(&self, id: Uuid) -> StoreResult<bool> {
+        self.delete_one(COURSE_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn find_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<Option<Enrollment>> {
+        self.find_one(ENROLLMENT_COLLECTION_NAME, filter_enrollment(course, user)).await
+    }
+
+    async fn list_enrollments(&self, course: Uuid) -> StoreResult<Vec<Enrollment>> {
+        self.find_many(ENROLLMENT_COLLECTION_NAME, filter_enrollment_course(course)).await
+    }
+
+    async fn save_enrollment(&self, enrollment: &Enrollment) -> StoreResult<()> {
+        let options = ReplaceOptions::builder()
+            .upsert(true)
+            .build();
+
+        self.db.collection(ENROLLMENT_COLLECTION_NAME)
+            .replace_one(
+                filter_enrollment(enrollment.course, enrollment.user),
+                bson::to_document(enrollment)?,
+                options,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_enrollment(&self, course: Uuid, user: UserId) -> StoreResult<bool> {
+        self.delete_one(ENROLLMENT_COLLECTION_NAME, filter_enrollment(course, user)).await
+    }
+
+    async fn delete_course_enrollments(&self, course: Uuid) -> StoreResult<()> {
+        self.delete_many(ENROLLMENT_COLLECTION_NAME, filter_enrollment_course(course)).await
+    }
+}
+
+#[rocket::async_trait]
+impl TokenStore for MongoStore {
+    async fn find_token(&self, id: Uuid) -> StoreResult<Option<ApiToken>> {
+        self.find_one(API_TOKEN_COLLECTION_NAME, filter_id(id)).await
+    }
+
+    async fn list_tokens(&self, user: UserId) -> StoreResult<Vec<ApiToken>> {
+        self.find_many(API_TOKEN_COLLECTION_NAME, filter_user(user)).await
+    }
+
+    async fn insert_token(&self, token: &ApiToken) -> StoreResult<()> {
+        self.insert(API_TOKEN_COLLECTION_NAME, token).await
+    }
+
+    async fn revoke_token(&self, id: Uuid, user: UserId) -> StoreResult<Option<ApiToken>> {
+        self.update_one(
+            API_TOKEN_COLLECTION_NAME,
+            filter_token(id, user),
+            doc! { "$set": { "revoked": true } },
+        ).await
+    }
+}
+
+#[rocket::async_trait]
+impl AuditStore for MongoStore {
+    async fn record_audit(&self, entry: &AuditEntry) -> StoreResult<()> {
+        self.insert(AUDIT_COLLECTION_NAME, entry).await
+    }
+}
+
+#[rocket::async_trait]
+impl StatusStore for MongoStore {
+    async fn ping(&self) -> StoreResult<()> {
+        self.db.run_command(doc! { "ping": 1 }, None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! Harness for end-to-end tests which run the full server against in-memory storage.
+
+use std::sync::Arc;
+
+use rocket::http::{ContentType, Cookie, Header, Status};
+use rocket::local::asynchronous::{Client, LocalRequest, LocalResponse};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::config::{Config, LiveConfig};
+use crate::jwt::UserRolesToken;
+use crate::logging::RequestLogger;
+use crate::role::Role;
+use crate::route::mount_routes;
+use crate::store::{MemoryStore, Storage, UserStore};
+use crate::user::User;
+
+mod flow;
+
+pub const PASSWORD: &'static str = "correct horse";
+
+/// Server instance with its own storage.
+pub struct TestServer {
+    pub client: Client,
+    pub store: Storage,
+}
+
+impl TestServer {
+    pub async fn new() -> TestServer {
+        let store: Storage = Arc::new(MemoryStore::new());
+
+        let rocket = mount_routes(
+            rocket::ignite()
+                .manage(LiveConfig::new(Config::default()))
+                .manage(store.clone())
+                .attach(RequestLogger)
+        );
+
+        TestServer {
+            client: Client::tracked(rocket).await.expect("Unable to launch test Rocket instance."),
+            store,
+        }
+    }
+
+    /// Stores a new user with `PASSWORD` password and given roles in addition to the normal one.
+    pub async fn create_user(&self, username: &str, roles: &[Role]) -> User {
+        let mut user = User::new(username.to_string(), PASSWORD.to_string());
+        user.user_roles.extend_from_slice(roles);
+
+        self.store.insert_user(&user).await.expect("Unable to store test user.");
+
+        user
+    }
+
+    /// Returns client which sends requests authenticated as `user`.
+    pub fn session(&self, user: &User) -> Session<'_> {
+        let cookie = UserRolesToken::new(user)
+            .cookie()
+            .expect("Unable to create session cookie.");
+
+        Session {
+            client: &self.client,
+            cookie,
+        }
+    }
+
+    /// Creates an API token of `user` with given scopes and returns its JWT.
+    pub async fn api_token(&self, user: &User, scopes: &[&str]) -> String {
+        let session = self.session(user);
+        let response = session
+            .send_json(session.post("/token".to_string()), &json!({"name": "test token", "scopes": scopes}))
+            .await;
+        let created: Value = expect_json(response, Status::Ok).await;
+
+        created["jwt"].as_str().expect("Created token has no JWT.").to_string()
+    }
+}
+
+/// Header which authenticates a request with an API token.
+pub fn bearer(jwt: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", jwt))
+}
+
+/// Client which carries the private JWT cookie of a user with every request.
+pub struct Session<'c> {
+    client: &'c Client,
+    cookie: Cookie<'static>,
+}
+
+impl<'c> Session<'c> {
+    pub fn get(&self, uri: String) -> LocalRequest<'c> {
+        self.client.get(uri).private_cookie(self.cookie.clone())
+    }
+
+    pub fn post(&self, uri: String) -> LocalRequest<'c> {
+        self.client.post(uri).private_cookie(self.cookie.clone())
+    }
+
+    pub fn put(&self, uri: String) -> LocalRequest<'c> {
+        self.client.put(uri).private_cookie(self.cookie.clone())
+    }
+
+    pub fn delete(&self, uri: String) -> LocalRequest<'c> {
+        self.client.delete(uri).private_cookie(self.cookie.clone())
+    }
+
+    /// Sends a JSON body with `method` request.
+    pub async fn send_json<T: serde::Serialize>(&self, request: LocalRequest<'c>, body: &T) -> LocalResponse<'c> {
+        request
+            .header(ContentType::JSON)
+            .body(serde_json::to_string(body).expect("Unable to serialize request body."))
+            .dispatch()
+            .await
+    }
+}
+
+/// Encodes fields as an `application/x-www-form-urlencoded` body.
+pub fn form(fields: &[(&str, &str)]) -> String {
+    fields.iter()
+        .map(|(key, value)| format!("{}={}", key, value.replace(' ', "+")))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Asserts response status and deserializes its JSON body.
+pub async fn expect_json<T: DeserializeOwned>(response: LocalResponse<'_>, status: Status) -> T {
+    let actual = response.status();
+    let body = response.into_string().await.unwrap_or_default();
+    assert_eq!(actual, status, "Unexpected response status, body: {}", body);
+
+    serde_json::from_str(&body).expect("Response body isn't valid JSON.")
+}
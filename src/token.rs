@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::id::UserId;
+
+pub static API_TOKEN_COLLECTION_NAME: &'static str = "api_tokens";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum Scope {
+    #[serde(rename = "quiz:read")]
+    QuizRead,
+    #[serde(rename = "quiz:write")]
+    QuizWrite,
+    #[serde(rename = "grades:read")]
+    GradesRead,
+    #[serde(rename = "grades:write")]
+    GradesWrite,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::QuizRead => write!(f, "quiz:read"),
+            Scope::QuizWrite => write!(f, "quiz:write"),
+            Scope::GradesRead => write!(f, "grades:read"),
+            Scope::GradesWrite => write!(f, "grades:write"),
+            Scope::UsersRead => write!(f, "users:read"),
+            Scope::UsersWrite => write!(f, "users:write"),
+        }
+    }
+}
+
+/// Named personal token which allows scripts and other API clients to authenticate using
+/// `Authorization: Bearer <jwt>` header.
+///
+/// Only token metadata is stored, the JWT itself is returned once when the token is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user: UserId,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+impl ApiToken {
+    pub fn new(user: UserId, name: String, scopes: Vec<Scope>, expires: DateTime<Utc>) -> ApiToken {
+        ApiToken {
+            id: Uuid::new_v4(),
+            user,
+            name,
+            scopes,
+            created: Utc::now(),
+            expires,
+            revoked: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.revoked && self.expires > Utc::now()
+    }
+}
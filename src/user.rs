@@ -3,7 +3,7 @@ use std::io::Cursor;
 use rocket::{Request, Response, response};
 use rocket::http::ContentType;
 use rocket::response::Responder;
-use uuid::Uuid;
+use crate::id::UserId;
 use crate::role::Role;
 use crypto::bcrypt::bcrypt;
 use serde_json::json;
@@ -14,25 +14,31 @@ static SALT: &'static [u8; 16] = include_bytes!("../password.salt");
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
-    pub id: Uuid,
+    pub id: UserId,
     pub username: String,
     pub pw_hash: [u8; 24],
     pub user_roles: Vec<Role>,
 }
 
+pub fn hash_password(password: &str) -> [u8; 24] {
+    let mut pw_hash: [u8; 24] = [0; 24];
+
+    // TODO: Implement password pre-hashing
+    bcrypt(15, SALT, password.as_bytes(), &mut pw_hash);
+
+    pw_hash
+}
+
 impl User {
     pub fn new(username: String, password: String) -> User {
-        let mut pw_hash: [u8; 24] = [0; 24];
-
-        // TODO: Implement password pre-hashing
-        bcrypt(15, SALT, password.as_bytes(), &mut pw_hash);
+        let pw_hash = hash_password(&password);
 
-        let uuid = Uuid::new_v4();
+        let uuid = UserId::new();
 
         info!("Creating a new user with UUID: {}", uuid.to_string());
 
         User {
-            id: uuid, // TODO: While highly unlikely, what if UUID exists?
+            id: uuid,
             username,
             pw_hash,
             user_roles: vec![Role::Normal],
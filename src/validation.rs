@@ -0,0 +1,384 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+
+use rocket::http::Status;
+
+use crate::data::{AnswerType, AnswerValidation, Part, Quiz};
+use crate::error::Problem;
+use crate::network;
+use crate::pattern;
+
+/// Semantic problem with a quiz which deserialized correctly. `pointer` is a JSON pointer
+/// (RFC 6901) to the offending value in the submitted quiz.
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub pointer: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new<P: Into<String>, M: Into<String>>(pointer: P, message: M) -> ValidationIssue {
+        ValidationIssue {
+            pointer: pointer.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pointer, self.message)
+    }
+}
+
+pub fn validation_problem(issues: Vec<ValidationIssue>) -> Problem {
+    Problem::new_untyped(
+        Status::UnprocessableEntity,
+        "Invalid quiz."
+    )
+        .detail(format!("Quiz has {} issue(s).", issues.len()))
+        .insert_serialized("issues", issues)
+        .clone()
+}
+
+/// Checks a quiz and returns every issue found in it.
+pub fn quiz_issues(quiz: &Quiz) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+
+    if quiz.name.trim().is_empty() {
+        issues.push(ValidationIssue::new("/name", "Quiz name can't be empty."));
+    }
+    if let (Some(open), Some(close)) = (quiz.open_on, quiz.close_on) {
+        if open > close {
+            issues.push(ValidationIssue::new("/close_on", "Quiz can't close before it opens."));
+        }
+    }
+    check_duration(&mut issues, "/time_limit", quiz.time_limit);
+
+    let mut ids = HashSet::new();
+    for (index, part) in quiz.parts.iter().enumerate() {
+        let pointer = match part {
+            Part::Content { .. } => format!("/parts/{}/Content", index),
+            Part::Interact { .. } => format!("/parts/{}/Interact", index),
+        };
+
+        if !ids.insert(part.id()) {
+            issues.push(ValidationIssue::new(
+                format!("{}/id", pointer),
+                format!("Part ID '{}' is used by multiple parts.", part.id()),
+            ));
+        }
+
+        if let Part::Interact { ans, time_limit, value, validation, .. } = part {
+            if !value.is_finite() || *value < 0.0 {
+                issues.push(ValidationIssue::new(
+                    format!("{}/value", pointer),
+                    "Part value must be a non-negative number.",
+                ));
+            }
+            check_duration(&mut issues, &format!("{}/time_limit", pointer), *time_limit);
+            check_answer_type(&mut issues, &format!("{}/ans", pointer), ans);
+            if let Some(validation) = validation {
+                check_validation(&mut issues, &format!("{}/validation", pointer), ans, validation, 0);
+            }
+        }
+    }
+
+    issues
+}
+
+/// Checks a quiz before it's stored, failing with a problem listing every issue.
+pub fn validate_quiz(quiz: &Quiz) -> Result<(), Problem> {
+    let issues = quiz_issues(quiz);
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(validation_problem(issues))
+    }
+}
+
+fn check_duration(issues: &mut Vec<ValidationIssue>, pointer: &str, duration: Option<Duration>) {
+    if duration == Some(Duration::from_secs(0)) {
+        issues.push(ValidationIssue::new(pointer, "Time limit can't be zero."));
+    }
+}
+
+fn check_answer_type(issues: &mut Vec<ValidationIssue>, pointer: &str, ans: &AnswerType) {
+    match ans {
+        AnswerType::Single { options, .. } => check_options(issues, &format!("{}/Single/options", pointer), options),
+        AnswerType::Multiple { options, .. } => check_options(issues, &format!("{}/Multiple/options", pointer), options),
+        AnswerType::Match(pairs) if pairs.is_empty() => {
+            issues.push(ValidationIssue::new(format!("{}/Match", pointer), "Match question needs at least one pair."));
+        }
+        _ => {}
+    }
+}
+
+fn check_options(issues: &mut Vec<ValidationIssue>, pointer: &str, options: &Vec<String>) {
+    if options.is_empty() {
+        issues.push(ValidationIssue::new(pointer, "Choice question needs at least one option."));
+    }
+
+    let mut seen = HashSet::new();
+    for (index, option) in options.iter().enumerate() {
+        if !seen.insert(option) {
+            issues.push(ValidationIssue::new(
+                format!("{}/{}", pointer, index),
+                format!("Option '{}' is listed multiple times.", option),
+            ));
+        }
+    }
+}
+
+/// Name of a validation variant, as it appears in JSON. Also labels validation metrics.
+pub fn validation_name(validation: &AnswerValidation) -> &'static str {
+    match validation {
+        AnswerValidation::Bool { .. } => "Bool",
+        AnswerValidation::Exact { .. } => "Exact",
+        AnswerValidation::NumberRange { .. } => "NumberRange",
+        AnswerValidation::Regex { .. } => "Regex",
+        AnswerValidation::Multiple { .. } => "Multiple",
+        AnswerValidation::Fuzzy { .. } => "Fuzzy",
+        AnswerValidation::IpAddress { .. } => "IpAddress",
+        AnswerValidation::Cidr { .. } => "Cidr",
+        AnswerValidation::SubnetMask { .. } => "SubnetMask",
+        AnswerValidation::MacAddress { .. } => "MacAddress",
+        AnswerValidation::Ports { .. } => "Ports",
+        AnswerValidation::AllOf { .. } => "AllOf",
+        AnswerValidation::AnyOf { .. } => "AnyOf",
+        AnswerValidation::FirstMatch { .. } => "FirstMatch",
+        AnswerValidation::Weighted { .. } => "Weighted",
+        AnswerValidation::External { .. } => "External",
+    }
+}
+
+/// Indicates whether answers of `ans` type can be checked by `validation`.
+fn is_compatible(ans: &AnswerType, validation: &AnswerValidation) -> bool {
+    use AnswerValidation as V;
+
+    match (ans, validation) {
+        (_, V::External { .. }) => true,
+        (AnswerType::Bool, V::Bool { .. }) => true,
+        (AnswerType::Number, V::NumberRange { .. }) |
+        (AnswerType::Number, V::Exact { .. }) |
+        (AnswerType::Number, V::Regex { .. }) => true,
+        (AnswerType::Short, V::Exact { .. }) |
+        (AnswerType::Short, V::Regex { .. }) |
+        (AnswerType::Short, V::Multiple { .. }) |
+        (AnswerType::Short, V::NumberRange { .. }) |
+        (AnswerType::Short, V::Fuzzy { .. }) => true,
+        (AnswerType::Long, V::Exact { .. }) |
+        (AnswerType::Long, V::Regex { .. }) |
+        (AnswerType::Long, V::Multiple { .. }) |
+        (AnswerType::Long, V::Fuzzy { .. }) => true,
+        (AnswerType::FillIn, V::Multiple { .. }) => true,
+        (AnswerType::Single { .. }, V::Exact { .. }) |
+        (AnswerType::Single { .. }, V::Regex { .. }) |
+        (AnswerType::Single { .. }, V::Multiple { .. }) => true,
+        (AnswerType::Multiple { .. }, V::Multiple { .. }) => true,
+        (AnswerType::Short, V::IpAddress { .. }) |
+        (AnswerType::Short, V::Cidr { .. }) |
+        (AnswerType::Short, V::SubnetMask { .. }) |
+        (AnswerType::Short, V::MacAddress { .. }) |
+        (AnswerType::Short, V::Ports { .. }) => true,
+        _ => false,
+    }
+}
+
+fn is_option(options: &Vec<String>, value: &str, case_sensitive: bool) -> bool {
+    options.iter().any(|option| if case_sensitive {
+        option.trim() == value.trim()
+    } else {
+        option.trim().to_lowercase() == value.trim().to_lowercase()
+    })
+}
+
+/// Deepest allowed nesting of composite validations.
+const MAX_VALIDATION_DEPTH: usize = 8;
+
+/// Checks branches of a composite validation.
+fn check_branches<'a, I>(issues: &mut Vec<ValidationIssue>, pointer: &str, ans: &AnswerType, branches: I, depth: usize)
+    where I: ExactSizeIterator<Item=(String, &'a AnswerValidation)> {
+    if branches.len() == 0 {
+        issues.push(ValidationIssue::new(pointer, "Composite validation needs at least one validation."));
+    }
+    if depth >= MAX_VALIDATION_DEPTH {
+        issues.push(ValidationIssue::new(
+            pointer,
+            format!("Validations can't be nested more than {} levels deep.", MAX_VALIDATION_DEPTH),
+        ));
+        return;
+    }
+
+    for (branch_pointer, validation) in branches {
+        check_validation(issues, &branch_pointer, ans, validation, depth + 1);
+    }
+}
+
+fn check_validation(issues: &mut Vec<ValidationIssue>, pointer: &str, ans: &AnswerType, validation: &AnswerValidation, depth: usize) {
+    let name = validation_name(validation);
+    let pointer = format!("{}/{}", pointer, name);
+
+    match validation {
+        AnswerValidation::AllOf { validations } | AnswerValidation::AnyOf { validations } => {
+            let branches = validations.iter()
+                .enumerate()
+                .map(|(index, validation)| (format!("{}/validations/{}", pointer, index), validation));
+            return check_branches(issues, &format!("{}/validations", pointer), ans, branches, depth);
+        }
+        AnswerValidation::FirstMatch { branches } => {
+            for (index, branch) in branches.iter().enumerate() {
+                if !branch.score.is_finite() || branch.score < 0.0 || branch.score > 1.0 {
+                    issues.push(ValidationIssue::new(
+                        format!("{}/branches/{}/score", pointer, index),
+                        "Branch score must be between 0 and 1.",
+                    ));
+                }
+            }
+            let branches = branches.iter()
+                .enumerate()
+                .map(|(index, branch)| (format!("{}/branches/{}/validation", pointer, index), &branch.validation));
+            return check_branches(issues, &format!("{}/branches", pointer), ans, branches, depth);
+        }
+        AnswerValidation::Weighted { branches } => {
+            for (index, branch) in branches.iter().enumerate() {
+                if !branch.weight.is_finite() || branch.weight < 0.0 {
+                    issues.push(ValidationIssue::new(
+                        format!("{}/branches/{}/weight", pointer, index),
+                        "Branch weight must be a non-negative number.",
+                    ));
+                }
+            }
+            if !branches.is_empty() && branches.iter().map(|branch| branch.weight).sum::<f32>() <= 0.0 {
+                issues.push(ValidationIssue::new(
+                    format!("{}/branches", pointer),
+                    "At least one branch must have a positive weight.",
+                ));
+            }
+            let branches = branches.iter()
+                .enumerate()
+                .map(|(index, branch)| (format!("{}/branches/{}/validation", pointer, index), &branch.validation));
+            return check_branches(issues, &format!("{}/branches", pointer), ans, branches, depth);
+        }
+        _ => {}
+    }
+
+    if !is_compatible(ans, validation) {
+        issues.push(ValidationIssue::new(
+            pointer,
+            format!("{} validation can't be used with this answer type.", name),
+        ));
+        return;
+    }
+
+    match validation {
+        AnswerValidation::Exact { case_sensitive, expected, .. } => {
+            if let AnswerType::Single { options, .. } = ans {
+                if !is_option(options, expected, *case_sensitive) {
+                    issues.push(ValidationIssue::new(
+                        format!("{}/expected", pointer),
+                        format!("Expected answer '{}' isn't one of the options.", expected),
+                    ));
+                }
+            }
+        }
+        AnswerValidation::NumberRange { min, max } => {
+            if !min.is_finite() || !max.is_finite() {
+                issues.push(ValidationIssue::new(pointer, "Range bounds must be finite numbers."));
+            } else if min > max {
+                issues.push(ValidationIssue::new(
+                    format!("{}/min", pointer),
+                    format!("Range minimum {} is greater than maximum {}.", min, max),
+                ));
+            }
+        }
+        AnswerValidation::Regex { alternatives, .. } => {
+            if let Err((index, e)) = pattern::precompile(validation) {
+                let location = match index {
+                    Some(index) => format!("{}/alternatives/{}/expr", pointer, index),
+                    None => format!("{}/expr", pointer),
+                };
+                issues.push(ValidationIssue::new(location, format!("Invalid regex: {}", e)));
+            }
+
+            for (index, alternative) in alternatives.iter().enumerate() {
+                if !alternative.score.is_finite() || alternative.score < 0.0 || alternative.score > 1.0 {
+                    issues.push(ValidationIssue::new(
+                        format!("{}/alternatives/{}/score", pointer, index),
+                        "Alternative score must be between 0 and 1.",
+                    ));
+                }
+            }
+        }
+        AnswerValidation::Multiple { case_sensitive, expected, .. } => {
+            if expected.is_empty() {
+                issues.push(ValidationIssue::new(
+                    format!("{}/expected", pointer),
+                    "At least one answer must be expected.",
+                ));
+            }
+
+            let options = match ans {
+                AnswerType::Single { options, .. } | AnswerType::Multiple { options, .. } => options,
+                _ => return,
+            };
+            for (index, value) in expected.iter().enumerate() {
+                if !is_option(options, value, *case_sensitive) {
+                    issues.push(ValidationIssue::new(
+                        format!("{}/expected/{}", pointer, index),
+                        format!("Expected answer '{}' isn't one of the options.", value),
+                    ));
+                }
+            }
+        }
+        AnswerValidation::Fuzzy { expected, max_distance, min_similarity, .. } => {
+            if expected.is_empty() {
+                issues.push(ValidationIssue::new(
+                    format!("{}/expected", pointer),
+                    "At least one answer must be expected.",
+                ));
+            }
+            match min_similarity {
+                None if max_distance.is_none() => issues.push(ValidationIssue::new(
+                    pointer.clone(),
+                    "Fuzzy validation needs `max_distance` or `min_similarity`.",
+                )),
+                Some(similarity) if !(0.0..=1.0).contains(similarity) => issues.push(ValidationIssue::new(
+                    format!("{}/min_similarity", pointer),
+                    "Similarity must be between 0 and 1.",
+                )),
+                _ => {}
+            }
+        }
+        AnswerValidation::IpAddress { expected } |
+        AnswerValidation::Cidr { expected, .. } |
+        AnswerValidation::SubnetMask { expected } |
+        AnswerValidation::MacAddress { expected } |
+        AnswerValidation::Ports { expected } => {
+            if expected.is_empty() {
+                issues.push(ValidationIssue::new(
+                    format!("{}/expected", pointer),
+                    "At least one answer must be expected.",
+                ));
+            }
+            for (index, value) in expected.iter().enumerate() {
+                if !network::is_valid_expected(validation, value) {
+                    issues.push(ValidationIssue::new(
+                        format!("{}/expected/{}", pointer, index),
+                        format!("Expected answer '{}' isn't a valid {} value.", value, name),
+                    ));
+                }
+            }
+        }
+        AnswerValidation::External { command } => {
+            if command.trim().is_empty() {
+                issues.push(ValidationIssue::new(
+                    format!("{}/command", pointer),
+                    "External validation needs a command.",
+                ));
+            }
+        }
+        _ => {}
+    }
+}